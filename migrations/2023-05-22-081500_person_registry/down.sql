@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS escalations;
+DROP TABLE IF EXISTS escalation_rules;
+DROP INDEX IF EXISTS violations_person_id_date_time;
+ALTER TABLE violations DROP COLUMN IF EXISTS person_id;
+DROP TABLE IF EXISTS persons;
@@ -0,0 +1,40 @@
+CREATE TABLE persons(
+    id uuid DEFAULT uuid_generate_v4(),
+    external_id VARCHAR(32),
+    first_name VARCHAR(48) NOT NULL,
+    last_name VARCHAR(48) NOT NULL,
+    category SMALLINT NOT NULL CHECK(category IN (1, 2, 3, 4)),
+    department VARCHAR(128),
+    PRIMARY KEY(id),
+    UNIQUE(external_id)
+);
+ALTER TABLE violations
+ADD COLUMN person_id uuid REFERENCES persons(id);
+CREATE INDEX violations_person_id_date_time ON violations(person_id, date_time);
+CREATE TABLE escalation_rules(
+    id uuid DEFAULT uuid_generate_v4(),
+    label VARCHAR(64) NOT NULL,
+    violation_count INTEGER NOT NULL CHECK(violation_count > 0),
+    period_days INTEGER NOT NULL CHECK(period_days > 0),
+    PRIMARY KEY(id)
+);
+CREATE TABLE escalations(
+    id uuid DEFAULT uuid_generate_v4(),
+    person_id uuid NOT NULL REFERENCES persons(id),
+    rule_id uuid NOT NULL REFERENCES escalation_rules(id) ON DELETE CASCADE,
+    violation_count INTEGER NOT NULL,
+    created_time TIMESTAMP NOT NULL,
+    resolved BOOLEAN NOT NULL,
+    PRIMARY KEY(id)
+);
+-- Configure privileges
+GRANT SELECT,
+    INSERT,
+    UPDATE ON persons TO unc_client;
+GRANT SELECT,
+    INSERT,
+    UPDATE,
+    DELETE ON escalation_rules TO unc_client;
+GRANT SELECT,
+    INSERT,
+    UPDATE ON escalations TO unc_client;
@@ -0,0 +1,8 @@
+DROP TABLE IF EXISTS retention_runs;
+DROP INDEX IF EXISTS violations_date_time;
+ALTER TABLE violations DROP COLUMN IF EXISTS image_archive;
+ALTER TABLE violations DROP COLUMN IF EXISTS image_compacted;
+-- Archived images stay in the archive directory
+UPDATE violations SET image_bytes = '' WHERE image_bytes IS NULL;
+ALTER TABLE violations
+ALTER COLUMN image_bytes SET NOT NULL;
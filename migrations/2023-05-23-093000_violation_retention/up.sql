@@ -0,0 +1,22 @@
+ALTER TABLE violations
+ALTER COLUMN image_bytes DROP NOT NULL;
+ALTER TABLE violations
+ADD COLUMN image_compacted BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE violations
+ADD COLUMN image_archive VARCHAR(256);
+CREATE INDEX violations_date_time ON violations(date_time);
+CREATE TABLE retention_runs(
+    id uuid DEFAULT uuid_generate_v4(),
+    started_time TIMESTAMP NOT NULL,
+    finished_time TIMESTAMP NOT NULL,
+    compacted INTEGER NOT NULL,
+    archived INTEGER NOT NULL,
+    purged INTEGER NOT NULL,
+    failed INTEGER NOT NULL,
+    bytes_reclaimed BIGINT NOT NULL,
+    PRIMARY KEY(id)
+);
+-- Configure privileges
+GRANT DELETE ON violations TO unc_client;
+GRANT SELECT,
+    INSERT ON retention_runs TO unc_client;
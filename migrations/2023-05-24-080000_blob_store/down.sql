@@ -0,0 +1,5 @@
+-- Images already moved stay in the blob store
+DROP INDEX IF EXISTS users_avatar_key;
+DROP INDEX IF EXISTS violations_image_key;
+ALTER TABLE users DROP COLUMN IF EXISTS avatar_key;
+ALTER TABLE violations DROP COLUMN IF EXISTS image_key;
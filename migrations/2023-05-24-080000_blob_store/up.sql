@@ -0,0 +1,8 @@
+-- Image bytes are moved into the blob store by the server on start up,
+-- the BYTEA columns are only read until every row has been moved
+ALTER TABLE violations
+ADD COLUMN image_key VARCHAR(64);
+ALTER TABLE users
+ADD COLUMN avatar_key VARCHAR(64);
+CREATE INDEX violations_image_key ON violations(image_key);
+CREATE INDEX users_avatar_key ON users(avatar_key);
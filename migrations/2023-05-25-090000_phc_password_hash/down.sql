@@ -0,0 +1,7 @@
+-- Only hashes that were never upgraded can be converted back,
+-- the other users are locked out until they are given a new password
+ALTER TABLE users
+ALTER COLUMN password_hash TYPE BYTEA USING CASE
+        WHEN password_hash LIKE '$argon2i$v=19$m=1024,t=8,p=4$c2FsdHkjUTlZTmVQU1Rwdw$%' THEN decode(split_part(password_hash, '$', 6) || '==', 'base64')
+        ELSE decode(repeat('00', 64), 'hex')
+    END;
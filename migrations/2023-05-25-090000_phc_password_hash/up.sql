@@ -0,0 +1,7 @@
+-- Existing hashes were made with Argon2i (m=1024, t=8, p=4) and a fixed salt,
+-- they are rewritten as PHC strings and rehashed on the next login
+ALTER TABLE users
+ALTER COLUMN password_hash TYPE VARCHAR(256) USING '$argon2i$v=19$m=1024,t=8,p=4$c2FsdHkjUTlZTmVQU1Rwdw$' || rtrim(
+        replace(encode(password_hash, 'base64'), E'\n', ''),
+        '='
+    );
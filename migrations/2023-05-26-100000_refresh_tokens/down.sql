@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS used_refresh_tokens;
+ALTER TABLE sessions DROP COLUMN IF EXISTS refresh_expires;
+ALTER TABLE sessions DROP COLUMN IF EXISTS refresh_hash;
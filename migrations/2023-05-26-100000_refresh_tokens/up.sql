@@ -0,0 +1,17 @@
+-- Only the SHA-256 of a refresh token is stored, the token itself is handed to the client
+ALTER TABLE sessions
+ADD COLUMN refresh_hash BYTEA UNIQUE;
+ALTER TABLE sessions
+ADD COLUMN refresh_expires TIMESTAMP;
+-- Rotated tokens are kept until they expire so that a replay can be detected
+CREATE TABLE used_refresh_tokens(
+    token_hash BYTEA,
+    session_id uuid NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    used_time TIMESTAMP NOT NULL,
+    PRIMARY KEY(token_hash)
+);
+CREATE INDEX used_refresh_tokens_used_time ON used_refresh_tokens(used_time);
+-- Configure privileges
+GRANT SELECT,
+    INSERT,
+    DELETE ON used_refresh_tokens TO unc_client;
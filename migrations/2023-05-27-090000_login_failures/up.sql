@@ -0,0 +1,14 @@
+-- Every rejected password is kept for review, unknown usernames included
+CREATE TABLE login_failures(
+    id uuid DEFAULT uuid_generate_v4(),
+    username VARCHAR(24) NOT NULL,
+    user_id uuid REFERENCES users(id) ON DELETE SET NULL,
+    ip_address VARCHAR(45),
+    attempt_time TIMESTAMP NOT NULL,
+    PRIMARY KEY(id)
+);
+CREATE INDEX login_failures_username ON login_failures(username, attempt_time);
+CREATE INDEX login_failures_ip_address ON login_failures(ip_address, attempt_time);
+-- Configure privileges
+GRANT SELECT,
+    INSERT ON login_failures TO unc_client;
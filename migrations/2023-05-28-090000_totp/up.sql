@@ -0,0 +1,32 @@
+-- The secret is stored as soon as enrolment starts, totp_enabled is only set once a code was confirmed
+ALTER TABLE users
+ADD COLUMN totp_secret BYTEA;
+ALTER TABLE users
+ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+-- Time step of the last accepted code so it cannot be replayed
+ALTER TABLE users
+ADD COLUMN totp_last_step BIGINT;
+CREATE TABLE totp_recovery_codes(
+    id uuid DEFAULT uuid_generate_v4(),
+    user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    code_hash BYTEA NOT NULL,
+    used_time TIMESTAMP,
+    PRIMARY KEY(id)
+);
+CREATE INDEX totp_recovery_codes_user_id ON totp_recovery_codes(user_id);
+CREATE TABLE totp_policies(
+    assigned_role SMALLINT NOT NULL CHECK(assigned_role IN (1, 2, 3)),
+    required BOOLEAN NOT NULL,
+    PRIMARY KEY(assigned_role)
+);
+INSERT INTO totp_policies(assigned_role, required)
+VALUES (1, FALSE),
+    (2, FALSE),
+    (3, FALSE);
+-- Configure privileges
+GRANT SELECT,
+    INSERT,
+    UPDATE,
+    DELETE ON totp_recovery_codes TO unc_client;
+GRANT SELECT,
+    UPDATE ON totp_policies TO unc_client;
@@ -0,0 +1,8 @@
+ALTER TABLE sessions DROP COLUMN IF EXISTS device_id;
+ALTER TABLE sessions DROP CONSTRAINT IF EXISTS sessions_user_id_device_hash_key;
+DELETE FROM sessions a USING sessions b
+WHERE a.device_hash = b.device_hash
+    AND a.last_login < b.last_login;
+ALTER TABLE sessions
+ADD CONSTRAINT sessions_device_hash_key UNIQUE(device_hash);
+DROP TABLE IF EXISTS devices;
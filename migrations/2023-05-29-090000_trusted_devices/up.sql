@@ -0,0 +1,29 @@
+CREATE TABLE devices(
+    id uuid DEFAULT uuid_generate_v4(),
+    user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    device_hash BYTEA NOT NULL,
+    device_os SMALLINT NOT NULL CHECK(device_os IN (1, 2, 3)),
+    device_name VARCHAR(64) NOT NULL,
+    -- 1 pending, 2 trusted, 3 blocked
+    device_status SMALLINT NOT NULL CHECK(device_status IN (1, 2, 3)),
+    created_time TIMESTAMP NOT NULL,
+    last_seen TIMESTAMP NOT NULL,
+    PRIMARY KEY(id),
+    UNIQUE(user_id, device_hash)
+);
+-- Device hashes stored so far did not depend on the signature alone, so they cannot be
+-- matched again and every open session has to log in once more
+UPDATE sessions
+SET logout_time = NOW()
+WHERE logout_time IS NULL;
+-- A device shared by several users keeps one session per user instead of taking over the row
+ALTER TABLE sessions DROP CONSTRAINT sessions_device_hash_key;
+ALTER TABLE sessions
+ADD CONSTRAINT sessions_user_id_device_hash_key UNIQUE(user_id, device_hash);
+ALTER TABLE sessions
+ADD COLUMN device_id uuid REFERENCES devices(id) ON DELETE CASCADE;
+-- Configure privileges
+GRANT SELECT,
+    INSERT,
+    UPDATE,
+    DELETE ON devices TO unc_client;
@@ -0,0 +1,3 @@
+-- Set by an administrator password reset, cleared once the user picks a new password
+ALTER TABLE users
+ADD COLUMN password_change_required BOOLEAN NOT NULL DEFAULT FALSE;
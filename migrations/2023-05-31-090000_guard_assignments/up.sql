@@ -0,0 +1,21 @@
+CREATE TABLE guard_assignments(
+    id uuid DEFAULT uuid_generate_v4(),
+    user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    area_code VARCHAR(10) NOT NULL REFERENCES areas(code) ON DELETE CASCADE,
+    -- first shift, later ones repeat it every day or week until repeat_until
+    start_time TIMESTAMP NOT NULL,
+    end_time TIMESTAMP NOT NULL,
+    -- 1 once, 2 daily, 3 weekly
+    recurrence SMALLINT NOT NULL CHECK(recurrence IN (1, 2, 3)),
+    repeat_until DATE,
+    PRIMARY KEY(id),
+    CHECK(end_time > start_time),
+    CHECK(end_time <= start_time + INTERVAL '1 day')
+);
+CREATE INDEX guard_assignments_user_id_idx ON guard_assignments(user_id);
+CREATE INDEX guard_assignments_area_code_idx ON guard_assignments(area_code);
+-- Configure privileges
+GRANT SELECT,
+    INSERT,
+    UPDATE,
+    DELETE ON guard_assignments TO unc_client;
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS missed_shifts;
+DROP TABLE IF EXISTS attendance;
+ALTER TABLE areas DROP COLUMN IF EXISTS checkin_secret;
@@ -0,0 +1,38 @@
+-- Secret of the rotating code shown at the post, check-ins at the area need it once it is set
+ALTER TABLE areas
+ADD COLUMN checkin_secret BYTEA;
+CREATE TABLE attendance(
+    id uuid DEFAULT uuid_generate_v4(),
+    user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    area_code VARCHAR(10) NOT NULL REFERENCES areas(code) ON DELETE CASCADE,
+    -- the shift the check-in was matched to, none when the guard was not on the roster
+    assignment_id uuid REFERENCES guard_assignments(id) ON DELETE SET NULL,
+    shift_start TIMESTAMP,
+    check_in_time TIMESTAMP NOT NULL,
+    check_out_time TIMESTAMP,
+    code_verified BOOLEAN NOT NULL,
+    PRIMARY KEY(id),
+    CHECK(check_out_time >= check_in_time)
+);
+-- A guard is checked in at one post at a time
+CREATE UNIQUE INDEX attendance_open_user_id_idx ON attendance(user_id)
+WHERE check_out_time IS NULL;
+CREATE INDEX attendance_check_in_time_idx ON attendance(check_in_time);
+CREATE INDEX attendance_shift_idx ON attendance(assignment_id, shift_start);
+CREATE TABLE missed_shifts(
+    assignment_id uuid NOT NULL REFERENCES guard_assignments(id) ON DELETE CASCADE,
+    shift_start TIMESTAMP NOT NULL,
+    user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    area_code VARCHAR(10) NOT NULL REFERENCES areas(code) ON DELETE CASCADE,
+    reported_time TIMESTAMP NOT NULL,
+    PRIMARY KEY(assignment_id, shift_start)
+);
+-- Configure privileges
+GRANT SELECT,
+    INSERT,
+    UPDATE,
+    DELETE ON attendance TO unc_client;
+GRANT SELECT,
+    INSERT,
+    UPDATE,
+    DELETE ON missed_shifts TO unc_client;
@@ -0,0 +1 @@
+ALTER TABLE areas DROP COLUMN IF EXISTS archived_time;
@@ -0,0 +1,3 @@
+-- Archived areas keep their violations but drop out of the list and take no new guards, shifts or cameras
+ALTER TABLE areas
+ADD COLUMN archived_time TIMESTAMP;
@@ -0,0 +1,15 @@
+-- The user and password of a camera url, sealed with AES-256-GCM under the key `key_id`.
+-- The server moves credentials still written into camera_url here when it starts
+CREATE TABLE camera_credentials(
+    camera_id uuid NOT NULL REFERENCES cameras(id) ON DELETE CASCADE,
+    key_id VARCHAR(32) NOT NULL,
+    nonce BYTEA NOT NULL,
+    ciphertext BYTEA NOT NULL,
+    PRIMARY KEY(camera_id)
+);
+CREATE INDEX camera_credentials_key_id_idx ON camera_credentials(key_id);
+-- Configure privileges
+GRANT SELECT,
+    INSERT,
+    UPDATE,
+    DELETE ON camera_credentials TO unc_client;
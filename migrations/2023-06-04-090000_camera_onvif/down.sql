@@ -0,0 +1,2 @@
+ALTER TABLE cameras DROP COLUMN IF EXISTS onvif_profile;
+ALTER TABLE cameras DROP COLUMN IF EXISTS onvif_url;
@@ -0,0 +1,4 @@
+-- The ONVIF device behind a camera, for PTZ. It signs in with the camera's stored credentials
+ALTER TABLE cameras
+ADD COLUMN onvif_url VARCHAR(512),
+ADD COLUMN onvif_profile VARCHAR(64);
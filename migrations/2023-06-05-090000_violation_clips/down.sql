@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS violations_clip_key;
+ALTER TABLE violations DROP COLUMN IF EXISTS clip_key;
@@ -0,0 +1,5 @@
+-- Blob store key of the clip recorded around the violation, several violations can share one
+ALTER TABLE violations
+ADD COLUMN clip_key VARCHAR(64);
+
+CREATE INDEX violations_clip_key ON violations(clip_key);
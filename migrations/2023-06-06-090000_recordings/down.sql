@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS recording_segments;
+ALTER TABLE cameras DROP COLUMN IF EXISTS recording_quota_mb;
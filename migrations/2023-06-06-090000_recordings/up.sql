@@ -0,0 +1,20 @@
+-- Cap on the bytes kept of a camera's recording, none uses RECORDING_QUOTA_MB
+ALTER TABLE cameras
+ADD COLUMN recording_quota_mb INTEGER CHECK(recording_quota_mb > 0);
+-- One file of the continuous recording of a camera, the path is relative to RECORDING_DIR
+CREATE TABLE recording_segments(
+    id uuid DEFAULT uuid_generate_v4(),
+    camera_id uuid NOT NULL REFERENCES cameras(id) ON DELETE CASCADE,
+    start_time TIMESTAMP NOT NULL,
+    end_time TIMESTAMP NOT NULL,
+    file_path VARCHAR(255) NOT NULL,
+    bytes BIGINT NOT NULL,
+    PRIMARY KEY(id),
+    CHECK(end_time >= start_time)
+);
+CREATE INDEX recording_segments_camera_time_idx ON recording_segments(camera_id, start_time);
+-- Configure privileges
+GRANT SELECT,
+    INSERT,
+    UPDATE,
+    DELETE ON recording_segments TO unc_client;
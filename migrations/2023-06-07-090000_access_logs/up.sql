@@ -0,0 +1,16 @@
+-- Every time footage was shown as it was captured, kept for data-privacy review
+CREATE TABLE access_logs(
+    id uuid DEFAULT uuid_generate_v4(),
+    user_id uuid REFERENCES users(id) ON DELETE SET NULL,
+    access_kind SMALLINT NOT NULL,
+    -- the violation or recording segment that was viewed, none for a report
+    subject_id uuid,
+    ip_address VARCHAR(45),
+    access_time TIMESTAMP NOT NULL,
+    PRIMARY KEY(id)
+);
+CREATE INDEX access_logs_user_id ON access_logs(user_id, access_time);
+CREATE INDEX access_logs_subject_id ON access_logs(subject_id, access_time);
+-- Configure privileges, entries are never changed or removed by the server
+GRANT SELECT,
+    INSERT ON access_logs TO unc_client;
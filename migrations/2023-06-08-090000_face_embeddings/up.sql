@@ -0,0 +1,17 @@
+-- One vector per violation image, searched by brute force on the server since
+-- pgvector is not installed with the database
+CREATE TABLE face_embeddings(
+    violation_id uuid REFERENCES violations(id) ON DELETE CASCADE,
+    -- file name of the model, vectors of other models are computed again
+    model VARCHAR(64) NOT NULL,
+    -- none when the image could not be read
+    embedding REAL [],
+    created_time TIMESTAMP NOT NULL,
+    PRIMARY KEY(violation_id)
+);
+CREATE INDEX face_embeddings_model ON face_embeddings(model);
+-- Configure privileges
+GRANT SELECT,
+    INSERT,
+    UPDATE,
+    DELETE ON face_embeddings TO unc_client;
@@ -0,0 +1 @@
+DROP INDEX IF EXISTS escalations_pending;
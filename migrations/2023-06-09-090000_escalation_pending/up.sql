@@ -0,0 +1,15 @@
+-- Only the earliest of pending escalations that were raised twice for the same rule is kept open
+UPDATE escalations
+SET resolved = TRUE
+WHERE NOT resolved
+    AND id NOT IN (
+        SELECT DISTINCT ON (person_id, rule_id) id
+        FROM escalations
+        WHERE NOT resolved
+        ORDER BY person_id,
+            rule_id,
+            created_time
+    );
+-- A rule is escalated once for a person until that escalation is resolved
+CREATE UNIQUE INDEX escalations_pending ON escalations(person_id, rule_id)
+WHERE NOT resolved;
@@ -0,0 +1,60 @@
+use std::time::Duration as StdDuration;
+
+use actix_web::web::Data;
+use chrono::Utc;
+use tokio::sync::Mutex;
+
+use crate::data::AppData;
+use crate::logging::{LogLevel, LogRecorder, LoggableError};
+use crate::models::{MissedShift, Permission};
+use crate::notifier::Notification;
+use crate::server_config::AttendancePolicy;
+
+pub fn spawn(data: Data<AppData<'static>>, logger: Data<Mutex<LogRecorder>>, policy: AttendancePolicy) {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(StdDuration::from_secs(policy.interval_minutes.max(1) * 60));
+
+        loop {
+            interval.tick().await;
+
+            let pool = data.database_pool();
+            let run_policy = policy.clone();
+
+            let missed = match tokio::task::spawn_blocking(move || {
+                let mut connection = pool.get().map_err(|error| error.to_string())?;
+
+                MissedShift::detect(&mut connection, &run_policy, Utc::now().naive_utc())
+                    .map_err(|error| error.to_string())
+            })
+            .await
+            {
+                Ok(Ok(missed)) => missed,
+                Ok(Err(error)) => {
+                    let log = LoggableError::new(format!("Attendance check failed: {error}"), LogLevel::Error);
+                    logger.lock().await.record(&log, None);
+                    continue;
+                }
+                Err(error) => {
+                    let log = LoggableError::new(format!("Attendance check aborted: {error}"), LogLevel::Error);
+                    logger.lock().await.record(&log, None);
+                    continue;
+                }
+            };
+
+            if missed.is_empty() {
+                continue;
+            }
+
+            let log = LoggableError::new(
+                format!("{} rostered shifts were missed", missed.len()),
+                LogLevel::Warning,
+            );
+            logger.lock().await.record(&log, None);
+
+            data.notifier()
+                .await
+                .notify_permitted(Permission::AttendanceRead, Notification::MissedShifts(missed));
+        }
+    });
+}
@@ -0,0 +1,100 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+
+use super::{BlobError, BlobResult, BlobStore};
+
+pub struct FsBlobStore {
+    root: PathBuf,
+}
+
+impl FsBlobStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    // Blobs are spread over two directory levels, e.g. ab/cd/abcd...
+    fn path(&self, key: &str) -> BlobResult<PathBuf> {
+        // anything but a hex digest could point outside of the root
+        if key.len() < 4 || !key.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(BlobError::NotFound);
+        }
+
+        Ok(self.root.join(&key[0..2]).join(&key[2..4]).join(key))
+    }
+}
+
+#[async_trait]
+impl BlobStore for FsBlobStore {
+    async fn put(&self, key: &str, bytes: Vec<u8>, _content_type: &str) -> BlobResult<()> {
+        let path = self.path(key)?;
+
+        blocking(move || {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            // written beside the target and renamed so readers never see a partial file
+            let temporary = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+
+            std::fs::write(&temporary, &bytes)?;
+            std::fs::rename(&temporary, &path)
+        })
+        .await
+    }
+
+    async fn size(&self, key: &str) -> BlobResult<Option<u64>> {
+        let path = self.path(key)?;
+
+        match blocking(move || std::fs::metadata(path)).await {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(BlobError::NotFound) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    async fn get(&self, key: &str, range: Option<Range<u64>>) -> BlobResult<Vec<u8>> {
+        let path = self.path(key)?;
+
+        blocking(move || {
+            let mut file = File::open(path)?;
+            let mut bytes = Vec::new();
+
+            match range {
+                Some(range) => {
+                    file.seek(SeekFrom::Start(range.start))?;
+                    file.take(range.end - range.start).read_to_end(&mut bytes)?;
+                }
+                None => {
+                    file.read_to_end(&mut bytes)?;
+                }
+            }
+
+            Ok(bytes)
+        })
+        .await
+    }
+
+    async fn delete(&self, key: &str) -> BlobResult<()> {
+        let path = self.path(key)?;
+
+        match blocking(move || std::fs::remove_file(path)).await {
+            Err(BlobError::NotFound) => Ok(()),
+            result => result,
+        }
+    }
+}
+
+async fn blocking<T, F>(operation: F) -> BlobResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> std::io::Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(operation)
+        .await
+        .map_err(|error| BlobError::Backend(error.to_string()))?
+        .map_err(BlobError::from)
+}
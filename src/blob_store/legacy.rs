@@ -0,0 +1,139 @@
+use actix_web::web::Data;
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, QueryResult, RunQueryDsl};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::data::DatabasePool;
+use crate::logging::{LogLevel, LogRecorder, LoggableError};
+use crate::schema::{users, violations};
+
+use super::{BlobStore, SharedBlobStore};
+
+const BATCH_SIZE: i64 = 50;
+
+// Moves the images still kept in the BYTEA columns into the blob store
+pub(crate) fn spawn_legacy_mover(
+    pool: DatabasePool,
+    blob_store: SharedBlobStore,
+    logger: Data<Mutex<LogRecorder>>,
+) {
+    tokio::spawn(async move {
+        let log = match move_legacy(&pool, blob_store.as_ref()).await {
+            Ok((0, 0)) => return,
+            Ok((images, avatars)) => LoggableError::new(
+                format!("Moved {images} violation images and {avatars} avatars into the blob store"),
+                LogLevel::Information,
+            ),
+            Err(error) => LoggableError::new(
+                format!("Moving images into the blob store failed: {error}"),
+                LogLevel::Error,
+            ),
+        };
+
+        logger.lock().await.record(&log, None);
+    });
+}
+
+async fn move_legacy(
+    pool: &DatabasePool,
+    blob_store: &dyn BlobStore,
+) -> Result<(usize, usize), String> {
+    let mut images = 0;
+    let mut avatars = 0;
+
+    loop {
+        let batch: Vec<(Uuid, Option<Vec<u8>>)> = run_query(pool, |connection| {
+            violations::table
+                .filter(violations::image_bytes.is_not_null())
+                .select((violations::id, violations::image_bytes))
+                .limit(BATCH_SIZE)
+                .load(connection)
+        })
+        .await?;
+
+        let batch_len = batch.len();
+        let mut connection = pool.get().map_err(|error| error.to_string())?;
+
+        for (id, bytes) in batch {
+            super::store(
+                blob_store,
+                &mut connection,
+                bytes.unwrap_or_default(),
+                "image/jpeg",
+                |connection, key| {
+                    diesel::update(violations::table.filter(violations::id.eq(id)))
+                        .set((
+                            violations::image_key.eq(Some(key)),
+                            violations::image_bytes.eq(None::<Vec<u8>>),
+                        ))
+                        .execute(connection)
+                },
+            )
+            .await
+            .map_err(|error| error.to_string())?;
+
+            images += 1;
+        }
+
+        if batch_len < BATCH_SIZE as usize {
+            break;
+        }
+    }
+
+    loop {
+        let batch: Vec<(Uuid, Option<Vec<u8>>)> = run_query(pool, |connection| {
+            users::table
+                .filter(users::avatar.is_not_null())
+                .select((users::id, users::avatar))
+                .limit(BATCH_SIZE)
+                .load(connection)
+        })
+        .await?;
+
+        let batch_len = batch.len();
+        let mut connection = pool.get().map_err(|error| error.to_string())?;
+
+        for (id, bytes) in batch {
+            super::store(
+                blob_store,
+                &mut connection,
+                bytes.unwrap_or_default(),
+                "image/jpeg",
+                |connection, key| {
+                    diesel::update(users::table.filter(users::id.eq(id)))
+                        .set((
+                            users::avatar_key.eq(Some(key)),
+                            users::avatar.eq(None::<Vec<u8>>),
+                        ))
+                        .execute(connection)
+                },
+            )
+            .await
+            .map_err(|error| error.to_string())?;
+
+            avatars += 1;
+        }
+
+        if batch_len < BATCH_SIZE as usize {
+            break;
+        }
+    }
+
+    Ok((images, avatars))
+}
+
+async fn run_query<T, F>(pool: &DatabasePool, query: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut PgConnection) -> QueryResult<T> + Send + 'static,
+{
+    let pool = pool.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut connection = pool.get().map_err(|error| error.to_string())?;
+
+        query(&mut connection).map_err(|error| error.to_string())
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
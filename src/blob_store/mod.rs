@@ -0,0 +1,220 @@
+mod fs_store;
+mod legacy;
+mod response;
+mod s3_store;
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use diesel::connection::{AnsiTransactionManager, TransactionManager};
+use diesel::sql_types::Text;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, QueryResult,
+    RunQueryDsl,
+};
+use tokio::runtime::Handle;
+
+use crate::server_config::BlobStoreConfig;
+
+pub use fs_store::FsBlobStore;
+pub use s3_store::S3BlobStore;
+
+pub(crate) use legacy::spawn_legacy_mover;
+pub(crate) use response::{blob_error, blob_response, bytes_response};
+
+pub type SharedBlobStore = Arc<dyn BlobStore>;
+pub type BlobResult<T> = Result<T, BlobError>;
+
+#[derive(Debug)]
+pub enum BlobError {
+    NotFound,
+    Backend(String),
+}
+
+impl std::fmt::Display for BlobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlobError::NotFound => write!(f, "blob not found"),
+            BlobError::Backend(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for BlobError {
+    fn from(error: diesel::result::Error) -> Self {
+        match error {
+            diesel::result::Error::NotFound => BlobError::NotFound,
+            _ => BlobError::Backend(error.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for BlobError {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => BlobError::NotFound,
+            _ => BlobError::Backend(error.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    async fn put(&self, key: &str, bytes: Vec<u8>, content_type: &str) -> BlobResult<()>;
+
+    // None when nothing is stored under the key
+    async fn size(&self, key: &str) -> BlobResult<Option<u64>>;
+
+    async fn get(&self, key: &str, range: Option<Range<u64>>) -> BlobResult<Vec<u8>>;
+
+    async fn delete(&self, key: &str) -> BlobResult<()>;
+}
+
+pub async fn connect(config: &BlobStoreConfig) -> SharedBlobStore {
+    match config {
+        BlobStoreConfig::Filesystem { root } => Arc::new(FsBlobStore::new(root.clone())),
+        BlobStoreConfig::S3 {
+            bucket,
+            region,
+            endpoint,
+        } => Arc::new(S3BlobStore::connect(bucket, region, endpoint.as_deref()).await),
+    }
+}
+
+// Keys are the SHA-256 of the content so identical images are only stored once
+pub fn content_key(bytes: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+// Stores the bytes and has `refer` record what uses them while the key is locked, so that a
+// release of the same content cannot delete the blob in between
+pub async fn store<T>(
+    blob_store: &dyn BlobStore,
+    connection: &mut PgConnection,
+    bytes: Vec<u8>,
+    content_type: &str,
+    refer: impl FnOnce(&mut PgConnection, String) -> QueryResult<T>,
+) -> BlobResult<T> {
+    let key = content_key(&bytes);
+
+    lock_key(connection, &key)?;
+
+    let stored = async {
+        if blob_store.size(&key).await?.is_none() {
+            blob_store.put(&key, bytes, content_type).await?;
+        }
+
+        Ok(refer(connection, key)?)
+    }
+    .await;
+
+    unlock_key(connection, stored)
+}
+
+// Deletes the blob once neither a violation nor an avatar refers to it
+pub async fn release(
+    blob_store: &dyn BlobStore,
+    connection: &mut PgConnection,
+    key: &str,
+) -> BlobResult<()> {
+    lock_key(connection, key)?;
+
+    let released = async {
+        if is_referenced(connection, key)? {
+            return Ok(());
+        }
+
+        blob_store.delete(key).await
+    }
+    .await;
+
+    unlock_key(connection, released)
+}
+
+// Counterparts for code that already runs on the blocking thread pool, never to be called from
+// async code where blocking on the runtime panics. They take the handle of the runtime from the
+// task that moved the work there rather than look for a current one a plain thread does not have
+pub fn store_blocking<T>(
+    runtime: &Handle,
+    blob_store: &dyn BlobStore,
+    connection: &mut PgConnection,
+    bytes: Vec<u8>,
+    content_type: &str,
+    refer: impl FnOnce(&mut PgConnection, String) -> QueryResult<T>,
+) -> BlobResult<T> {
+    runtime.block_on(store(blob_store, connection, bytes, content_type, refer))
+}
+
+pub fn get_blocking(
+    runtime: &Handle,
+    blob_store: &dyn BlobStore,
+    key: &str,
+) -> BlobResult<Vec<u8>> {
+    runtime.block_on(blob_store.get(key, None))
+}
+
+pub fn release_blocking(
+    runtime: &Handle,
+    blob_store: &dyn BlobStore,
+    connection: &mut PgConnection,
+    key: &str,
+) -> BlobResult<()> {
+    runtime.block_on(release(blob_store, connection, key))
+}
+
+// Storing and releasing the same content take turns on an advisory lock of the key, held by a
+// transaction of its own so that it is let go however they end
+fn lock_key(connection: &mut PgConnection, key: &str) -> BlobResult<()> {
+    AnsiTransactionManager::begin_transaction(connection)?;
+
+    let locked = diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext($1))")
+        .bind::<Text, _>(key)
+        .execute(connection);
+
+    if let Err(error) = locked {
+        AnsiTransactionManager::rollback_transaction(connection).ok();
+        return Err(error.into());
+    }
+
+    Ok(())
+}
+
+fn unlock_key<T>(connection: &mut PgConnection, result: BlobResult<T>) -> BlobResult<T> {
+    match result {
+        Ok(value) => {
+            AnsiTransactionManager::commit_transaction(connection)?;
+            Ok(value)
+        }
+        Err(error) => {
+            AnsiTransactionManager::rollback_transaction(connection).ok();
+            Err(error)
+        }
+    }
+}
+
+fn is_referenced(connection: &mut PgConnection, key: &str) -> QueryResult<bool> {
+    use crate::schema::{users, violations};
+
+    let violation = violations::table
+        .filter(violations::image_key.eq(key).or(violations::clip_key.eq(key)))
+        .select(violations::id)
+        .first::<uuid::Uuid>(connection)
+        .optional()?;
+
+    if violation.is_some() {
+        return Ok(true);
+    }
+
+    let user = users::table
+        .filter(users::avatar_key.eq(key))
+        .select(users::id)
+        .first::<uuid::Uuid>(connection)
+        .optional()?;
+
+    Ok(user.is_some())
+}
@@ -0,0 +1,181 @@
+use actix_files::HttpRange;
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder};
+
+use crate::logging::{LogLevel, ResponseError};
+
+use super::{BlobError, BlobStore};
+
+pub(crate) async fn blob_response(
+    blob_store: &dyn BlobStore,
+    key: &str,
+    content_type: &str,
+    request: &HttpRequest,
+) -> crate::routes::Result<HttpResponse> {
+    let etag = format!("\"{key}\"");
+
+    if not_modified(request, &etag) {
+        return Ok(not_modified_response(etag));
+    }
+
+    let size = blob_store
+        .size(key)
+        .await
+        .map_err(blob_error)?
+        .ok_or_else(|| blob_error(BlobError::NotFound))?;
+
+    let mut response = response_builder(content_type, etag.clone());
+
+    match requested_range(request, &etag, size) {
+        Ok(Some(range)) => {
+            let bytes = blob_store
+                .get(key, Some(range.start..range.start + range.length))
+                .await
+                .map_err(blob_error)?;
+
+            Ok(partial_response(response, range, size, bytes))
+        }
+        Ok(None) => {
+            let bytes = blob_store.get(key, None).await.map_err(blob_error)?;
+
+            Ok(response.body(bytes))
+        }
+        Err(()) => Ok(unsatisfiable_response(size)),
+    }
+}
+
+// Same headers for images that are not in the blob store yet
+pub(crate) fn bytes_response(
+    bytes: Vec<u8>,
+    content_type: &str,
+    request: &HttpRequest,
+) -> HttpResponse {
+    let etag = format!("\"{}\"", super::content_key(&bytes));
+
+    if not_modified(request, &etag) {
+        return not_modified_response(etag);
+    }
+
+    let size = bytes.len() as u64;
+    let mut response = response_builder(content_type, etag.clone());
+
+    match requested_range(request, &etag, size) {
+        Ok(Some(range)) => {
+            let start = range.start as usize;
+            let end = (range.start + range.length) as usize;
+            let bytes = bytes[start..end].to_vec();
+
+            partial_response(response, range, size, bytes)
+        }
+        Ok(None) => response.body(bytes),
+        Err(()) => unsatisfiable_response(size),
+    }
+}
+
+fn response_builder(content_type: &str, etag: String) -> HttpResponseBuilder {
+    let mut response = HttpResponse::build(StatusCode::OK);
+
+    response
+        .content_type(content_type)
+        .insert_header((header::ETAG, etag))
+        .insert_header((header::ACCEPT_RANGES, "bytes"))
+        // a key is the hash of its content so the bytes behind it never change
+        .insert_header((header::CACHE_CONTROL, "private, max-age=31536000, immutable"));
+
+    response
+}
+
+fn not_modified(request: &HttpRequest, etag: &str) -> bool {
+    request
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| {
+            value
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == etag || tag == "*")
+        })
+}
+
+fn not_modified_response(etag: String) -> HttpResponse {
+    HttpResponse::build(StatusCode::NOT_MODIFIED)
+        .insert_header((header::ETAG, etag))
+        .finish()
+}
+
+// Only the first range is served, like actix-files does
+fn requested_range(
+    request: &HttpRequest,
+    etag: &str,
+    size: u64,
+) -> Result<Option<HttpRange>, ()> {
+    let range = match request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(range) => range,
+        None => return Ok(None),
+    };
+
+    // a stale If-Range validator means the client wants the whole image again
+    if let Some(if_range) = request
+        .headers()
+        .get(header::IF_RANGE)
+        .and_then(|value| value.to_str().ok())
+    {
+        if if_range.trim() != etag {
+            return Ok(None);
+        }
+    }
+
+    match HttpRange::parse(range, size) {
+        Ok(ranges) if !ranges.is_empty() && ranges[0].length > 0 => Ok(Some(ranges[0])),
+        _ => Err(()),
+    }
+}
+
+fn unsatisfiable_response(size: u64) -> HttpResponse {
+    HttpResponse::build(StatusCode::RANGE_NOT_SATISFIABLE)
+        .insert_header((header::CONTENT_RANGE, format!("bytes */{size}")))
+        .finish()
+}
+
+fn partial_response(
+    mut response: HttpResponseBuilder,
+    range: HttpRange,
+    size: u64,
+    bytes: Vec<u8>,
+) -> HttpResponse {
+    response
+        .status(StatusCode::PARTIAL_CONTENT)
+        .insert_header((
+            header::CONTENT_RANGE,
+            format!(
+                "bytes {}-{}/{}",
+                range.start,
+                range.start + range.length - 1,
+                size
+            ),
+        ))
+        .body(bytes)
+}
+
+pub(crate) fn blob_error(error: BlobError) -> ResponseError {
+    match error {
+        BlobError::NotFound => ResponseError::new(
+            "Image is missing from the blob store",
+            "Image not found",
+            LogLevel::Warning,
+            StatusCode::NOT_FOUND,
+        ),
+        BlobError::Backend(message) => ResponseError::new(
+            format!("Blob store failed: {message}"),
+            "Failed to load image",
+            LogLevel::Error,
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+    }
+}
@@ -0,0 +1,103 @@
+use std::ops::Range;
+
+use async_trait::async_trait;
+use aws_sdk_s3::config::Region;
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+
+use super::{BlobError, BlobResult, BlobStore};
+
+pub struct S3BlobStore {
+    client: Client,
+    bucket: String,
+}
+
+impl S3BlobStore {
+    // Credentials are read from the usual AWS_* variables, the endpoint is
+    // only set for S3 compatible servers such as MinIO
+    pub async fn connect(bucket: &str, region: &str, endpoint: Option<&str>) -> Self {
+        let mut loader = aws_config::from_env().region(Region::new(region.to_owned()));
+
+        if let Some(endpoint) = endpoint {
+            loader = loader.endpoint_url(endpoint);
+        }
+
+        let sdk_config = loader.load().await;
+
+        // MinIO serves buckets by path instead of by subdomain
+        let config = aws_sdk_s3::config::Builder::from(&sdk_config)
+            .force_path_style(endpoint.is_some())
+            .build();
+
+        Self {
+            client: Client::from_conf(config),
+            bucket: bucket.to_owned(),
+        }
+    }
+}
+
+#[async_trait]
+impl BlobStore for S3BlobStore {
+    async fn put(&self, key: &str, bytes: Vec<u8>, content_type: &str) -> BlobResult<()> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type)
+            .body(ByteStream::from(bytes))
+            .send()
+            .await
+            .map_err(backend_error)?;
+
+        Ok(())
+    }
+
+    async fn size(&self, key: &str) -> BlobResult<Option<u64>> {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(output) => Ok(Some(output.content_length().max(0) as u64)),
+            Err(SdkError::ServiceError(error)) if error.err().is_not_found() => Ok(None),
+            Err(error) => Err(backend_error(error)),
+        }
+    }
+
+    async fn get(&self, key: &str, range: Option<Range<u64>>) -> BlobResult<Vec<u8>> {
+        let mut request = self.client.get_object().bucket(&self.bucket).key(key);
+
+        if let Some(range) = range {
+            request = request.range(format!("bytes={}-{}", range.start, range.end - 1));
+        }
+
+        let output = request.send().await.map_err(|error| match error {
+            SdkError::ServiceError(error) if error.err().is_no_such_key() => BlobError::NotFound,
+            error => backend_error(error),
+        })?;
+
+        let bytes = output.body.collect().await.map_err(backend_error)?;
+
+        Ok(bytes.into_bytes().to_vec())
+    }
+
+    async fn delete(&self, key: &str) -> BlobResult<()> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(backend_error)?;
+
+        Ok(())
+    }
+}
+
+fn backend_error(error: impl std::fmt::Display) -> BlobError {
+    BlobError::Backend(format!("S3 request failed: {error}"))
+}
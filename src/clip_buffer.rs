@@ -0,0 +1,87 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::server_config::ClipPolicy;
+
+// The last seconds of a camera, kept to cut a clip around a violation. The time is passed in
+// by the caller, frames are whatever the camera gives
+pub(crate) struct ClipBuffer<T> {
+    policy: ClipPolicy,
+    frames: VecDeque<(Instant, T)>,
+    // violations waiting for the seconds after them, and when those are in
+    violations: Vec<uuid::Uuid>,
+    until: Option<Instant>,
+}
+
+impl<T> ClipBuffer<T> {
+    pub(crate) fn new(policy: ClipPolicy) -> Self {
+        Self {
+            policy,
+            frames: VecDeque::new(),
+            violations: Vec::new(),
+            until: None,
+        }
+    }
+
+    // The frame is only made when it is kept, at most `fps` of them a second
+    pub(crate) fn push(&mut self, now: Instant, frame: impl FnOnce() -> T) {
+        if !self.policy.is_enabled() {
+            return;
+        }
+
+        let interval = Duration::from_secs(1) / self.policy.fps;
+
+        if matches!(self.frames.back(), Some((last, _)) if now - *last < interval) {
+            return;
+        }
+
+        self.frames.push_back((now, frame()));
+
+        // nothing is dropped while a clip records
+        if self.until.is_none() {
+            let before = Duration::from_secs(self.policy.seconds_before);
+
+            while matches!(self.frames.front(), Some((time, _)) if now - *time > before) {
+                self.frames.pop_front();
+            }
+        }
+    }
+
+    // A violation during a recording joins that clip rather than starting another
+    pub(crate) fn record(&mut self, now: Instant, violation: uuid::Uuid) {
+        if !self.policy.is_enabled() {
+            return;
+        }
+
+        self.violations.push(violation);
+        self.until
+            .get_or_insert_with(|| now + Duration::from_secs(self.policy.seconds_after));
+    }
+
+    // The violations and frames of a clip once its last seconds are in, with the rate the
+    // frames were really taken at, which is lower than the policy's when inference lags
+    pub(crate) fn finished(&mut self, now: Instant) -> Option<(Vec<uuid::Uuid>, Vec<T>, f64)> {
+        match self.until {
+            Some(until) if now >= until => self.until = None,
+            _ => return None,
+        }
+
+        let violations = std::mem::take(&mut self.violations);
+        let frames: Vec<(Instant, T)> = self.frames.drain(..).collect();
+
+        let fps = match (frames.first(), frames.last()) {
+            (Some((first, _)), Some((last, _))) if last > first => {
+                (frames.len() - 1) as f64 / (*last - *first).as_secs_f64()
+            }
+            _ => self.policy.fps as f64,
+        };
+
+        (!frames.is_empty()).then(|| {
+            (
+                violations,
+                frames.into_iter().map(|(_, frame)| frame).collect(),
+                fps.clamp(1.0, self.policy.fps as f64),
+            )
+        })
+    }
+}
@@ -1,5 +1,7 @@
 use diesel::RunQueryDsl;
 use std::io::{Cursor, Read, Seek};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use image::ImageOutputFormat;
@@ -9,18 +11,45 @@ use imageproc::rect::Rect;
 use rusttype::{Font, Scale};
 
 use actix_web::http::StatusCode;
+use actix_web::web;
+use actix_web::HttpRequest;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHasher, PasswordVerifier, Version};
 use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
-use diesel::PgConnection;
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
+use diesel::{ExpressionMethods, PgConnection, QueryDsl};
 use tokio::sync::Mutex;
 use xxhash_rust::xxh3::Xxh3;
 
+use crate::blob_store::{BlobError, BlobResult, SharedBlobStore};
 use crate::logging::{LogLevel, ResponseError};
-use crate::models::{JwtClaims, PasswordHash, ViolationKind, ViolationUnknownInsert};
+use crate::models::{
+    GuardAssignment, JwtClaims, PasswordHash, ViolationKind, ViolationUnknownInsert,
+};
 use crate::notifier::{Notification, Notifier};
+use crate::server_config::{
+    AttendancePolicy, EmbeddingPolicy, LoginPolicy, PrivacyPolicy, ServerConfig,
+};
+
+use super::{CredentialVault, JwtKeys};
+
+pub type DatabasePool = Pool<ConnectionManager<PgConnection>>;
 
 pub struct AppData<'a> {
-    db_pool: Pool<ConnectionManager<PgConnection>>,
+    db_pool: DatabasePool,
+    archive_dir: PathBuf,
+    recording_dir: Option<PathBuf>,
+    blob_store: SharedBlobStore,
+    argon2: Argon2<'static>,
+    unknown_user_hash: PasswordHash,
+    login_policy: LoginPolicy,
+    trusted_proxies: Vec<IpAddr>,
+    attendance_policy: AttendancePolicy,
+    privacy_policy: PrivacyPolicy,
+    embedding_policy: EmbeddingPolicy,
+    jwt_keys: JwtKeys,
+    credential_vault: CredentialVault,
+    refresh_ttl: chrono::Duration,
     xxh3: Mutex<Xxh3>,
     font: Font<'a>,
     notifier: RwLock<Notifier>,
@@ -28,10 +57,10 @@ pub struct AppData<'a> {
 }
 
 impl<'a> AppData<'a> {
-    const JWT_SECRET: &'static str = "2b9e6f9ec298c3a7ebde69e941ed2d81";
+    const FONT_BYTES: &'static [u8] = include_bytes!("../../assets/Roboto-Medium.ttf");
 
-    pub fn create(database_url: &str) -> Self {
-        let manager = ConnectionManager::new(database_url);
+    pub fn create(server_config: &ServerConfig, blob_store: SharedBlobStore) -> Self {
+        let manager = ConnectionManager::new(&server_config.database_url);
         let db_pool = Pool::builder()
             .test_on_check_out(true)
             .build(manager)
@@ -39,17 +68,44 @@ impl<'a> AppData<'a> {
 
         let connection = db_pool.get().unwrap();
 
+        let argon2 = Argon2::new(
+            Algorithm::Argon2id,
+            Version::V0x13,
+            Params::new(
+                server_config.password.memory_kib,
+                server_config.password.iterations,
+                server_config.password.parallelism,
+                None,
+            )
+            .expect("Invalid argon2 parameters"),
+        );
+
+        // unknown usernames are checked against this so they take as long as a wrong password
+        let unknown_user_hash = Self::argon2_hash(&argon2, &uuid::Uuid::new_v4().to_string())
+            .expect("Failed to hash password");
+
+        let credential_vault = CredentialVault::load(&server_config.credentials);
+        let surveillance = crate::Surveillance::new(connection, &credential_vault, &server_config.clips);
+
         Self {
             db_pool,
+            archive_dir: server_config.retention.archive_dir.clone(),
+            recording_dir: server_config.recording.dir.clone(),
+            blob_store,
+            argon2,
+            unknown_user_hash,
+            login_policy: server_config.login.clone(),
+            trusted_proxies: server_config.trusted_proxies.clone(),
+            attendance_policy: server_config.attendance.clone(),
+            privacy_policy: server_config.privacy.clone(),
+            embedding_policy: server_config.embedding.clone(),
+            jwt_keys: JwtKeys::load(&server_config.jwt),
+            credential_vault,
+            refresh_ttl: chrono::Duration::days(server_config.jwt.refresh_ttl_days),
             xxh3: Mutex::new(Xxh3::with_seed(0x13ac0750331f23db)),
-            font: {
-                Font::try_from_vec(Vec::from(
-                    include_bytes!("../../assets/Roboto-Medium.ttf") as &[u8]
-                ))
-                .unwrap()
-            },
+            font: Font::try_from_vec(Vec::from(Self::FONT_BYTES)).unwrap(),
             notifier: Notifier::default().into(),
-            surveillance: crate::Surveillance::new(connection),
+            surveillance,
         }
     }
 
@@ -67,77 +123,156 @@ impl<'a> AppData<'a> {
         self.db_pool.get().unwrap()
     }
 
-    #[allow(mutable_transmutes)]
-    #[allow(invalid_value)]
-    #[allow(clippy::uninit_assumed_init)]
-    pub fn argon2(&self, password: &str) -> crate::models::PasswordHash {
-        use std::mem::*;
-
-        let mut salt: [u8; 16] = *b"salty#Q9YNePSTpw";
-        let mut hash: [u8; 64] = unsafe { MaybeUninit::uninit().assume_init() };
-
-        let mut context = argon2::Context {
-            out: &mut hash,
-            pwd: Some(unsafe { transmute::<&[u8], &mut [u8]>(password.as_bytes()) }),
-            salt: Some(&mut salt),
-            secret: None,
-            ad: None,
-            t_cost: 8,
-            m_cost: 1024,
-            lanes: 4,
-            threads: 4,
-            version: argon2::Version::Version13,
-            flags: argon2::Flags::DEFAULT,
-        };
+    pub fn database_pool(&self) -> DatabasePool {
+        self.db_pool.clone()
+    }
 
-        argon2::i_ctx(&mut context).unwrap();
+    #[inline(always)]
+    pub fn archive_dir(&self) -> &Path {
+        &self.archive_dir
+    }
 
-        hash.into()
+    // None when cameras are not recorded
+    pub fn recording_dir(&self) -> Option<&Path> {
+        self.recording_dir.as_deref()
+    }
+
+    #[inline(always)]
+    pub fn blob_store(&self) -> SharedBlobStore {
+        self.blob_store.clone()
     }
 
+    #[inline(always)]
+    pub fn font_bytes(&self) -> &'static [u8] {
+        Self::FONT_BYTES
+    }
+
+    pub fn login_policy(&self) -> &LoginPolicy {
+        &self.login_policy
+    }
+
+    // The address failed logins and footage views are recorded under
+    pub fn client_address(&self, request: &HttpRequest) -> Option<String> {
+        let peer = request.peer_addr()?.ip();
+        let forwarded_for = request
+            .headers()
+            .get("X-Forwarded-For")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+
+        Some(forwarded_client(peer, forwarded_for, &self.trusted_proxies).to_string())
+    }
+
+    pub fn attendance_policy(&self) -> &AttendancePolicy {
+        &self.attendance_policy
+    }
+
+    pub fn privacy_policy(&self) -> &PrivacyPolicy {
+        &self.privacy_policy
+    }
+
+    pub fn embedding_policy(&self) -> &EmbeddingPolicy {
+        &self.embedding_policy
+    }
+
+    pub fn credential_vault(&self) -> &CredentialVault {
+        &self.credential_vault
+    }
+
+    pub fn unknown_user_hash(&self) -> PasswordHash {
+        self.unknown_user_hash.clone()
+    }
+
+    pub async fn hash_password(&self, password: &str) -> crate::Result<PasswordHash, ResponseError> {
+        let argon2 = self.argon2.clone();
+        let password = password.to_owned();
+
+        web::block(move || Self::argon2_hash(&argon2, &password))
+            .await
+            .or(Err(ResponseError::server_error()))?
+    }
+
+    // Returns a new hash when the stored one was made with other parameters
     pub async fn validate_password(
         &self,
         hash: PasswordHash,
         password: &str,
-    ) -> crate::Result<(), ResponseError> {
-        if hash == self.argon2(password) {
-            return Ok(());
-        }
+    ) -> crate::Result<Option<PasswordHash>, ResponseError> {
+        let argon2 = self.argon2.clone();
+        let password = password.to_owned();
+
+        web::block(move || {
+            let parsed = argon2::PasswordHash::new(hash.as_str()).map_err(|error| {
+                ResponseError::new(
+                    format!("Stored password hash is invalid: {error}"),
+                    "Invalid username or password",
+                    LogLevel::Error,
+                    StatusCode::UNAUTHORIZED,
+                )
+            })?;
+
+            // the algorithm and parameters are read from the PHC string
+            if argon2
+                .verify_password(password.as_bytes(), &parsed)
+                .is_err()
+            {
+                return Err(ResponseError::new(
+                    "A user entered invalid password",
+                    "Invalid username or password",
+                    LogLevel::Information,
+                    StatusCode::UNAUTHORIZED,
+                ));
+            }
+
+            let outdated = parsed.algorithm != Algorithm::Argon2id.ident()
+                || Params::try_from(&parsed).map_or(true, |params| {
+                    params.m_cost() != argon2.params().m_cost()
+                        || params.t_cost() != argon2.params().t_cost()
+                        || params.p_cost() != argon2.params().p_cost()
+                });
+
+            if outdated {
+                return Self::argon2_hash(&argon2, &password).map(Some);
+            }
+
+            Ok(None)
+        })
+        .await
+        .or(Err(ResponseError::server_error()))?
+    }
 
-        Err(ResponseError::new(
-            "A user entered invalid password",
-            "Invalid username or password",
-            LogLevel::Information,
-            StatusCode::UNAUTHORIZED,
-        ))
+    fn argon2_hash(argon2: &Argon2<'static>, password: &str) -> crate::Result<PasswordHash, ResponseError> {
+        let salt = SaltString::generate(&mut OsRng);
+
+        argon2
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| PasswordHash::from(hash.to_string()))
+            .or(Err(ResponseError::server_error()))
     }
 
-    pub fn jwt_encode(&self, claims: &JwtClaims) -> crate::Result<String, ResponseError> {
-        match jsonwebtoken::encode(
-            &Header::default(),
-            claims,
-            &EncodingKey::from_secret(Self::JWT_SECRET.as_ref()),
-        ) {
+    pub fn jwt_encode(&self, session_id: uuid::Uuid) -> crate::Result<String, ResponseError> {
+        match self.jwt_keys.encode(session_id) {
             Ok(jwt) => Ok(jwt),
             Err(err) => Err(ResponseError::new(
-                "Encoding JSON Web token failed",
-                match err.kind() {
-                    jsonwebtoken::errors::ErrorKind::ExpiredSignature => "Session expired",
-                    _ => "Invalid Session",
-                },
-                LogLevel::Information,
-                StatusCode::UNAUTHORIZED,
+                format!("Encoding JSON Web token failed: {err}"),
+                "Invalid Session",
+                LogLevel::Error,
+                StatusCode::INTERNAL_SERVER_ERROR,
             )),
         }
     }
 
+    pub fn access_ttl(&self) -> chrono::Duration {
+        self.jwt_keys.access_ttl()
+    }
+
+    pub fn refresh_ttl(&self) -> chrono::Duration {
+        self.refresh_ttl
+    }
+
     pub fn jwt_decode(&self, jwt: &str) -> crate::Result<JwtClaims, ResponseError> {
-        match jsonwebtoken::decode(
-            jwt,
-            &DecodingKey::from_secret(Self::JWT_SECRET.as_ref()),
-            &Validation::default(),
-        ) {
-            Ok(data) => Ok(data.claims),
+        match self.jwt_keys.decode(jwt) {
+            Ok(claims) => Ok(claims),
             Err(err) => Err(ResponseError::new(
                 "Decoding JSON Web token failed",
                 match err.kind() {
@@ -153,6 +288,8 @@ impl<'a> AppData<'a> {
     pub async fn xxh3_128bits<const N: usize>(&self, data: [u8; N]) -> u128 {
         let mut xxh3 = self.xxh3.lock().await;
 
+        // the hasher streams, without a reset every digest would depend on all earlier input
+        xxh3.reset();
         xxh3.update(&data);
         xxh3.digest128()
     }
@@ -162,7 +299,7 @@ impl<'a> AppData<'a> {
         area_code: &str,
         violation_kind: ViolationKind,
         image: image::RgbImage,
-    ) {
+    ) -> BlobResult<uuid::Uuid> {
         use crate::schema::violations;
 
         let mut connection = self.connect_database();
@@ -178,21 +315,78 @@ impl<'a> AppData<'a> {
             stream.read_to_end(&mut image_buffer).unwrap();
         }
 
-        let violation: uuid::Uuid = diesel::insert_into(violations::table)
-            .values(ViolationUnknownInsert {
-                area_code: area_code.to_string(),
-                violation_kind,
-                date_time: chrono::Utc::now().naive_utc(),
-                image_bytes: image_buffer,
-                identified: false,
-            })
-            .returning(violations::id)
-            .get_result(&mut connection)
-            .unwrap();
-
-        self.notifier()
-            .await
-            .notify(Notification::NewViolations(vec![violation]))
+        let violation: uuid::Uuid = crate::blob_store::store(
+            self.blob_store.as_ref(),
+            &mut connection,
+            image_buffer,
+            "image/jpeg",
+            |connection, image_key| {
+                diesel::insert_into(violations::table)
+                    .values(ViolationUnknownInsert {
+                        area_code: area_code.to_string(),
+                        violation_kind,
+                        date_time: chrono::Utc::now().naive_utc(),
+                        image_key,
+                        identified: false,
+                    })
+                    .returning(violations::id)
+                    .get_result(connection)
+            },
+        )
+        .await?;
+
+        // the roster is read without the notifier locked, and without it nobody scoped hears of it
+        let scoped = self.notifier().await.scoped_users();
+        let areas = GuardAssignment::current_areas(
+            &mut connection,
+            &scoped,
+            chrono::Utc::now().naive_utc(),
+        )
+        .unwrap_or_default();
+
+        self.notifier().await.notify_area(
+            area_code,
+            &areas,
+            Notification::NewViolations(vec![violation]),
+        );
+
+        Ok(violation)
+    }
+
+    // Links an encoded clip to the violations it covers. It takes the pool and the store
+    // rather than the app data so it can finish on its own task
+    pub async fn store_clip(
+        pool: DatabasePool,
+        blob_store: SharedBlobStore,
+        violations: Vec<uuid::Uuid>,
+        clip: Vec<u8>,
+    ) -> BlobResult<()> {
+        use crate::schema::violations;
+
+        let mut connection = pool
+            .get()
+            .map_err(|error| BlobError::Backend(error.to_string()))?;
+
+        let (clip_key, linked) = crate::blob_store::store(
+            blob_store.as_ref(),
+            &mut connection,
+            clip,
+            "video/mp4",
+            |connection, clip_key| {
+                diesel::update(violations::table.filter(violations::id.eq_any(&violations)))
+                    .set(violations::clip_key.eq(&clip_key))
+                    .execute(connection)
+                    .map(|linked| (clip_key, linked))
+            },
+        )
+        .await?;
+
+        // a violation purged in the meantime leaves the clip unreferenced
+        if linked == 0 {
+            crate::blob_store::release(blob_store.as_ref(), &mut connection, &clip_key).await?;
+        }
+
+        Ok(())
     }
 
     fn random_color() -> Rgb<u8> {
@@ -262,3 +456,22 @@ impl<'a> AppData<'a> {
         Ok(image_bytes)
     }
 }
+
+// Each trusted proxy appends the address it was reached from, so the client is the last address
+// not added by one. Anything before that was sent by the client and could be made up
+pub fn forwarded_client(peer: IpAddr, forwarded_for: &str, trusted_proxies: &[IpAddr]) -> IpAddr {
+    let mut client = peer;
+
+    for hop in forwarded_for.rsplit(',') {
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+
+        match hop.trim().parse() {
+            Ok(address) => client = address,
+            Err(_) => break,
+        }
+    }
+
+    client
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use jsonwebtoken::errors::{Error, ErrorKind};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+
+use crate::models::JwtClaims;
+use crate::server_config::{JwtConfig, JwtKeyConfig};
+
+// Tokens issued this far in the future are rejected
+const IAT_LEEWAY: i64 = 60;
+
+pub struct JwtKeys {
+    issuer: String,
+    audience: String,
+    access_ttl: chrono::Duration,
+    signing_kid: String,
+    signing_key: EncodingKey,
+    signing_algorithm: Algorithm,
+    verifying_keys: HashMap<String, (Algorithm, DecodingKey)>,
+}
+
+impl JwtKeys {
+    pub fn load(config: &JwtConfig) -> Self {
+        let signing = config
+            .keys
+            .iter()
+            .find(|key| key.kid == config.signing_kid)
+            .unwrap_or_else(|| panic!("JWT signing key {} is not listed", config.signing_kid));
+
+        Self {
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+            access_ttl: chrono::Duration::minutes(config.access_ttl_minutes),
+            signing_kid: signing.kid.clone(),
+            signing_key: Self::encoding_key(signing),
+            signing_algorithm: signing.algorithm,
+            verifying_keys: config
+                .keys
+                .iter()
+                .map(|key| (key.kid.clone(), (key.algorithm, Self::decoding_key(key))))
+                .collect(),
+        }
+    }
+
+    pub fn encode(&self, session_id: uuid::Uuid) -> jsonwebtoken::errors::Result<String> {
+        let mut header = Header::new(self.signing_algorithm);
+        header.kid = Some(self.signing_kid.clone());
+
+        jsonwebtoken::encode(
+            &header,
+            &JwtClaims::new(session_id, &self.issuer, &self.audience, self.access_ttl),
+            &self.signing_key,
+        )
+    }
+
+    pub fn access_ttl(&self) -> chrono::Duration {
+        self.access_ttl
+    }
+
+    pub fn decode(&self, jwt: &str) -> jsonwebtoken::errors::Result<JwtClaims> {
+        let kid = jsonwebtoken::decode_header(jwt)?
+            .kid
+            .ok_or_else(|| Error::from(ErrorKind::InvalidToken))?;
+
+        let (algorithm, key) = self
+            .verifying_keys
+            .get(&kid)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidToken))?;
+
+        let mut validation = Validation::new(*algorithm);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.audience]);
+        validation.set_required_spec_claims(&["exp", "iat", "iss", "aud"]);
+
+        let claims = jsonwebtoken::decode::<JwtClaims>(jwt, key, &validation)?.claims;
+
+        if claims.iat > chrono::Utc::now().timestamp() + IAT_LEEWAY {
+            return Err(ErrorKind::ImmatureSignature.into());
+        }
+
+        Ok(claims)
+    }
+
+    fn encoding_key(key: &JwtKeyConfig) -> EncodingKey {
+        match key.algorithm {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                EncodingKey::from_secret(Self::secret(key))
+            }
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(&Self::pem(key, &key.private_key))
+                .unwrap_or_else(|_| panic!("Invalid RSA private key for JWT key {}", key.kid)),
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(&Self::pem(key, &key.private_key))
+                .unwrap_or_else(|_| panic!("Invalid Ed25519 private key for JWT key {}", key.kid)),
+            algorithm => panic!("Unsupported JWT algorithm {algorithm:?}"),
+        }
+    }
+
+    fn decoding_key(key: &JwtKeyConfig) -> DecodingKey {
+        match key.algorithm {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                DecodingKey::from_secret(Self::secret(key))
+            }
+            Algorithm::RS256 => DecodingKey::from_rsa_pem(&Self::pem(key, &key.public_key))
+                .unwrap_or_else(|_| panic!("Invalid RSA public key for JWT key {}", key.kid)),
+            Algorithm::EdDSA => DecodingKey::from_ed_pem(&Self::pem(key, &key.public_key))
+                .unwrap_or_else(|_| panic!("Invalid Ed25519 public key for JWT key {}", key.kid)),
+            algorithm => panic!("Unsupported JWT algorithm {algorithm:?}"),
+        }
+    }
+
+    fn secret(key: &JwtKeyConfig) -> &[u8] {
+        key.secret
+            .as_deref()
+            .unwrap_or_else(|| panic!("JWT key {} has no secret", key.kid))
+            .as_bytes()
+    }
+
+    fn pem(key: &JwtKeyConfig, path: &Option<std::path::PathBuf>) -> Vec<u8> {
+        let path = path
+            .as_ref()
+            .unwrap_or_else(|| panic!("JWT key {} is missing a PEM file", key.kid));
+
+        std::fs::read(path).unwrap_or_else(|_| panic!("Cannot read {}", path.display()))
+    }
+}
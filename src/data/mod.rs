@@ -1,3 +1,10 @@
 mod app_data;
+pub mod credential_vault;
+mod jwt_keys;
+pub mod totp;
 
-pub use app_data::AppData;
\ No newline at end of file
+pub use app_data::{AppData, DatabasePool};
+#[cfg(test)]
+pub(crate) use app_data::forwarded_client;
+pub use credential_vault::CredentialVault;
+pub use jwt_keys::JwtKeys;
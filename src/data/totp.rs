@@ -0,0 +1,153 @@
+use std::io::Cursor;
+
+use image::{ImageOutputFormat, Luma};
+use qrcode::{Color, QrCode};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+
+// RFC 6238 defaults, which is what authenticator apps assume when the uri leaves them out
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+const SECRET_LENGTH: usize = 20;
+// one step either way for clocks that drift
+const SKEW_STEPS: i64 = 1;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: usize = 10;
+const QR_MODULE_PIXELS: u32 = 8;
+const QR_QUIET_ZONE: u32 = 4;
+
+pub fn generate_secret() -> Option<Vec<u8>> {
+    let mut secret = vec![0u8; SECRET_LENGTH];
+
+    SystemRandom::new().fill(&mut secret).ok()?;
+
+    Some(secret)
+}
+
+pub fn encode_secret(secret: &[u8]) -> String {
+    base32::encode(base32::Alphabet::RFC4648 { padding: false }, secret)
+}
+
+pub fn otpauth_uri(secret: &[u8], issuer: &str, username: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&digits={DIGITS}&period={STEP_SECONDS}",
+        percent_encode(issuer),
+        percent_encode(username),
+        encode_secret(secret),
+        percent_encode(issuer),
+    )
+}
+
+// Returns the time step the code belongs to, so the caller can refuse it a second time
+pub fn verify(secret: &[u8], code: &str, unix_time: i64, last_step: Option<i64>) -> Option<i64> {
+    let code = code.trim();
+
+    if code.len() != DIGITS as usize || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    let current = unix_time / STEP_SECONDS;
+
+    (current - SKEW_STEPS..=current + SKEW_STEPS)
+        .filter(|step| last_step < Some(*step))
+        .find(|step| {
+            ring::constant_time::verify_slices_are_equal(
+                hotp(secret, *step as u64).as_bytes(),
+                code.as_bytes(),
+            )
+            .is_ok()
+        })
+}
+
+pub fn hotp(secret: &[u8], counter: u64) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let digest = hmac::sign(&key, &counter.to_be_bytes());
+    let digest = digest.as_ref();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    format!("{:0width$}", binary % 10u32.pow(DIGITS), width = DIGITS as usize)
+}
+
+// The code of the current step and the seconds until it changes
+pub fn current_code(secret: &[u8], unix_time: i64) -> (String, i64) {
+    let step = unix_time / STEP_SECONDS;
+
+    (hotp(secret, step as u64), (step + 1) * STEP_SECONDS - unix_time)
+}
+
+pub fn generate_recovery_codes() -> Option<Vec<String>> {
+    let random = SystemRandom::new();
+
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; RECOVERY_CODE_LENGTH];
+            random.fill(&mut bytes).ok()?;
+
+            // crockford leaves out the letters that are easily mistaken for digits
+            Some(base32::encode(base32::Alphabet::Crockford, &bytes)[..RECOVERY_CODE_LENGTH].to_owned())
+        })
+        .collect()
+}
+
+pub fn hash_recovery_code(code: &str) -> Vec<u8> {
+    let normalized: String = code
+        .chars()
+        .filter(|char| char.is_ascii_alphanumeric())
+        .map(|char| char.to_ascii_uppercase())
+        .collect();
+
+    ring::digest::digest(&ring::digest::SHA256, normalized.as_bytes())
+        .as_ref()
+        .to_vec()
+}
+
+pub fn qr_png(uri: &str) -> Option<Vec<u8>> {
+    let code = QrCode::new(uri.as_bytes()).ok()?;
+    let width = code.width() as u32;
+    let colors = code.to_colors();
+    let size = (width + QR_QUIET_ZONE * 2) * QR_MODULE_PIXELS;
+
+    let image = image::ImageBuffer::from_fn(size, size, |x, y| {
+        let (x, y) = (x / QR_MODULE_PIXELS, y / QR_MODULE_PIXELS);
+
+        let dark = x >= QR_QUIET_ZONE
+            && y >= QR_QUIET_ZONE
+            && x < width + QR_QUIET_ZONE
+            && y < width + QR_QUIET_ZONE
+            && colors[((y - QR_QUIET_ZONE) * width + x - QR_QUIET_ZONE) as usize] == Color::Dark;
+
+        if dark {
+            Luma([0u8])
+        } else {
+            Luma([255u8])
+        }
+    });
+
+    let mut buffer = Cursor::new(Vec::new());
+
+    image::DynamicImage::ImageLuma8(image)
+        .write_to(&mut buffer, ImageOutputFormat::Png)
+        .ok()?;
+
+    Some(buffer.into_inner())
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
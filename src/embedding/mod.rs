@@ -0,0 +1,196 @@
+mod model;
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use actix_web::web::Data;
+use image::imageops::FilterType;
+use image::RgbImage;
+use ndarray::{Array, IxDyn};
+use tokio::runtime::Handle;
+use tokio::sync::Mutex;
+
+use crate::blob_store::BlobStore;
+use crate::data::{AppData, DatabasePool};
+use crate::logging::{LogLevel, LogRecorder, LoggableError};
+use crate::models::FaceEmbeddingInsert;
+use crate::server_config::EmbeddingPolicy;
+
+use model::Model;
+
+// A previously identified person alike the violator
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Match {
+    pub person_id: uuid::Uuid,
+    // the violation of the person most alike
+    pub violation_id: uuid::Uuid,
+    pub similarity: f32,
+}
+
+// Embeds the images of new violations, and of older ones once, in the background
+pub fn spawn(data: Data<AppData<'static>>, logger: Data<Mutex<LogRecorder>>, policy: EmbeddingPolicy) {
+    let (path, model_name) = match (policy.model.clone(), policy.model_name()) {
+        (Some(path), Some(model_name)) => (path, model_name),
+        _ => return,
+    };
+
+    tokio::spawn(async move {
+        let loaded = tokio::task::spawn_blocking(move || Model::load(&path))
+            .await
+            .map_err(|error| error.to_string())
+            .and_then(|loaded| loaded);
+
+        let model = match loaded {
+            Ok(model) => Arc::new(model),
+            Err(error) => {
+                let log = LoggableError::new(format!("Embedding model not loaded: {error}"), LogLevel::Error);
+                logger.lock().await.record(&log, None);
+                return;
+            }
+        };
+
+        let mut interval = tokio::time::interval(StdDuration::from_secs(policy.interval_seconds.max(1)));
+
+        loop {
+            interval.tick().await;
+
+            let run_data = data.clone();
+            let run_model = model.clone();
+            let run_name = model_name.clone();
+            let run_policy = policy.clone();
+            let runtime = Handle::current();
+
+            let log = match tokio::task::spawn_blocking(move || {
+                embed_pending(
+                    &run_data.database_pool(),
+                    &runtime,
+                    run_data.blob_store().as_ref(),
+                    run_data.archive_dir(),
+                    &run_model,
+                    &run_name,
+                    &run_policy,
+                )
+            })
+            .await
+            {
+                Ok(Ok(0)) => continue,
+                Ok(Ok(embedded)) => {
+                    LoggableError::new(format!("Embedded {embedded} violation images"), LogLevel::Information)
+                }
+                Ok(Err(error)) => LoggableError::new(format!("Embedding failed: {error}"), LogLevel::Error),
+                Err(error) => LoggableError::new(format!("Embedding aborted: {error}"), LogLevel::Error),
+            };
+
+            logger.lock().await.record(&log, None);
+        }
+    });
+}
+
+// One batch of violations without a vector of the model. An image that cannot be read is
+// stored without one so that it is not tried again
+fn embed_pending(
+    pool: &DatabasePool,
+    runtime: &Handle,
+    blob_store: &dyn BlobStore,
+    archive_dir: &Path,
+    model: &Model,
+    model_name: &str,
+    policy: &EmbeddingPolicy,
+) -> Result<usize, String> {
+    let mut connection = pool.get().map_err(|error| error.to_string())?;
+
+    let pending = FaceEmbeddingInsert::pending(&mut connection, model_name, policy.batch_size)
+        .map_err(|error| error.to_string())?;
+
+    for (violation_id, key, bytes, archive) in pending.iter() {
+        let image_bytes = match (key, bytes, archive) {
+            (Some(key), _, _) => crate::blob_store::get_blocking(runtime, blob_store, key).ok(),
+            (None, Some(bytes), _) => Some(bytes.clone()),
+            (None, None, Some(archive)) => crate::retention::read_archived(archive_dir, archive).ok(),
+            (None, None, None) => None,
+        };
+
+        let embedding = match image_bytes.map(|bytes| image::load_from_memory(&bytes)) {
+            Some(Ok(image)) => Some(normalize(model.run(input_tensor(&image.to_rgb8(), policy.input_size))?)),
+            _ => None,
+        };
+
+        FaceEmbeddingInsert {
+            violation_id: *violation_id,
+            model: model_name.to_owned(),
+            embedding,
+            created_time: chrono::Utc::now().naive_utc(),
+        }
+        .upsert(&mut connection)
+        .map_err(|error| error.to_string())?;
+    }
+
+    Ok(pending.len())
+}
+
+// 1x3xNxN RGB scaled to -1..1, the crop is stretched to a square like the model was trained on
+pub fn input_tensor(image: &RgbImage, size: u32) -> Array<f32, IxDyn> {
+    let resized = image::imageops::resize(image, size, size, FilterType::Triangle);
+    let mut tensor = Array::zeros((1, 3, size as usize, size as usize)).into_dyn();
+
+    for (x, y, pixel) in resized.enumerate_pixels() {
+        for channel in 0..3 {
+            tensor[[0, channel, y as usize, x as usize]] = (pixel[channel] as f32 - 127.5) / 127.5;
+        }
+    }
+
+    tensor
+}
+
+// Unit length, so that the similarity of two vectors is their dot product
+pub fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let length = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+
+    if length > 0.0 {
+        vector.iter_mut().for_each(|value| *value /= length);
+    }
+
+    vector
+}
+
+// The persons most alike the vector, each by their most alike violation. Stored vectors are
+// compared one by one, a few thousand identified violations take well under a second
+pub fn rank(
+    target: &[f32],
+    candidates: impl IntoIterator<Item = (uuid::Uuid, uuid::Uuid, Vec<f32>)>,
+    min_similarity: f32,
+    limit: usize,
+) -> Vec<Match> {
+    let mut best: HashMap<uuid::Uuid, Match> = HashMap::new();
+
+    for (person_id, violation_id, vector) in candidates {
+        // a vector of another length came from another model
+        if vector.len() != target.len() {
+            continue;
+        }
+
+        let similarity: f32 = target.iter().zip(vector.iter()).map(|(a, b)| a * b).sum();
+
+        if similarity < min_similarity {
+            continue;
+        }
+
+        let candidate = Match { person_id, violation_id, similarity };
+
+        best.entry(person_id)
+            .and_modify(|current| {
+                if similarity > current.similarity {
+                    *current = candidate;
+                }
+            })
+            .or_insert(candidate);
+    }
+
+    let mut matches: Vec<Match> = best.into_values().collect();
+    matches.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    matches.truncate(limit);
+
+    matches
+}
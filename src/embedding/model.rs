@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use ndarray::{Array, IxDyn};
+use ort::tensor::InputTensor;
+use ort::{Environment, ExecutionProvider, GraphOptimizationLevel, Session, SessionBuilder};
+
+pub struct Model {
+    session: Session,
+}
+
+impl Model {
+    // Runs on the CPU so that matching works without a GPU and leaves it to detection
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let environment = Environment::builder()
+            .with_name("embedding")
+            .with_execution_providers([ExecutionProvider::cpu()])
+            .build()
+            .map_err(|error| error.to_string())?
+            .into_arc();
+
+        let session = SessionBuilder::new(&environment)
+            .and_then(|builder| builder.with_optimization_level(GraphOptimizationLevel::Level3))
+            .and_then(|builder| builder.with_intra_threads(2))
+            .and_then(|builder| builder.with_model_from_file(path))
+            .map_err(|error| format!("Failed to load {}: {error}", path.display()))?;
+
+        Ok(Self { session })
+    }
+
+    pub fn run(&self, input: Array<f32, IxDyn>) -> Result<Vec<f32>, String> {
+        let outputs = self
+            .session
+            .run([InputTensor::FloatTensor(input)])
+            .map_err(|error| error.to_string())?;
+
+        let output = outputs
+            .get(0)
+            .ok_or("The model gave no output")?
+            .try_extract::<f32>()
+            .map_err(|error| error.to_string())?;
+
+        let vector = output.view().iter().copied().collect();
+
+        Ok(vector)
+    }
+}
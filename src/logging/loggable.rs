@@ -103,6 +103,11 @@ impl LoggableResponseError {
         }
     }
 
+    #[inline]
+    pub fn response_message(&self) -> &str {
+        &self.response_message
+    }
+
     pub fn unauthorized(user: UserClaims) -> Self {
         Self {
             log_message: format!("Denied access to user: {}", user.user_id),
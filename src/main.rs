@@ -1,12 +1,21 @@
 use actix_web::{App, HttpServer};
-use logging::LogRecorder;
+use logging::{LogLevel, LogRecorder};
 use tokio::{self, sync::Mutex};
 
 // local imports
+mod attendance;
+mod blob_store;
+mod clip_buffer;
 mod data;
+mod embedding;
 mod logging;
 mod models;
 mod notifier;
+mod onvif;
+mod privacy;
+mod recorder;
+mod reports;
+mod retention;
 mod routes;
 mod schema;
 mod server_config;
@@ -27,6 +36,10 @@ use crate::{notifier::ActiveEntry, surveillance::Surveillance};
 fn main() -> std::io::Result<()> {
     let server_config = ServerConfig::load();
 
+    if std::env::args().nth(1).as_deref() == Some("rotate-credential-keys") {
+        return rotate_credential_keys(&server_config);
+    }
+
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
@@ -35,8 +48,55 @@ fn main() -> std::io::Result<()> {
 }
 
 async fn start_server(server_config: &ServerConfig) -> std::io::Result<()> {
-    let data = actix_web::web::Data::new(AppData::create(&server_config.database_url));
+    let blob_store = blob_store::connect(&server_config.blob_store).await;
+    let data = actix_web::web::Data::new(AppData::create(server_config, blob_store.clone()));
     let logger = actix_web::web::Data::new(Mutex::new(LogRecorder::new()));
+
+    blob_store::spawn_legacy_mover(data.database_pool(), blob_store.clone(), logger.clone());
+
+    match data.credential_vault().seal_legacy(&mut data.connect_database()) {
+        Ok(0) => (),
+        Ok(sealed) => logger.lock().await.record(
+            &Error::new(format!("Moved {sealed} camera credentials into the vault"), LogLevel::Information),
+            None,
+        ),
+        Err(error) => logger.lock().await.record(
+            &Error::new(format!("Moving camera credentials into the vault failed: {error}"), LogLevel::Error),
+            None,
+        ),
+    }
+
+    if server_config.retention.is_enabled() {
+        retention::spawn(
+            data.database_pool(),
+            blob_store,
+            logger.clone(),
+            server_config.retention.clone(),
+        );
+    }
+
+    attendance::spawn(data.clone(), logger.clone(), server_config.attendance.clone());
+
+    if server_config.recording.is_enabled() {
+        recorder::spawn(data.clone(), logger.clone(), server_config.recording.clone());
+    }
+
+    if server_config.embedding.is_enabled() {
+        embedding::spawn(data.clone(), logger.clone(), server_config.embedding.clone());
+    }
+
+    // without the cascade no face is ever found and redacted images are blurred whole
+    if server_config.privacy.mode == server_config::PrivacyMode::Faces
+        && !server_config.privacy.face_cascade.is_file()
+    {
+        logger.lock().await.record(
+            &Error::new(
+                format!("No face cascade at {}, redacted images are blurred whole", server_config.privacy.face_cascade.display()),
+                LogLevel::Warning,
+            ),
+            None,
+        );
+    }
     
     /*let surveillance = actix_web::web::Data::new({
         let mut logger = logger.lock().await;
@@ -60,6 +120,8 @@ async fn start_server(server_config: &ServerConfig) -> std::io::Result<()> {
             .service(routes::logs::scope())
             .service(routes::areas::scope())
             .service(routes::violations::scope())
+            .service(routes::persons::scope())
+            .service(routes::attendance::scope())
             .service(routes::socket::resource())
     })
     .bind(server_config.actix_socket_addr())?
@@ -67,6 +129,30 @@ async fn start_server(server_config: &ServerConfig) -> std::io::Result<()> {
     .await
 }
 
+// Reseals every camera credential under the active key, run it before retiring an old key
+fn rotate_credential_keys(server_config: &ServerConfig) -> std::io::Result<()> {
+    use diesel::Connection;
+
+    let vault = data::CredentialVault::load(&server_config.credentials);
+    let mut connection = diesel::PgConnection::establish(&server_config.database_url)
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::Other, error))?;
+
+    let (resealed, unreadable) = vault
+        .rotate(&mut connection)
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::Other, error))?;
+
+    println!("Resealed {resealed} camera credentials under the active key");
+
+    if unreadable > 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            format!("{unreadable} camera credentials could not be opened, list their keys and run again"),
+        ));
+    }
+
+    Ok(())
+}
+
 /*
 #[allow(unused)]
 fn insert_sample_violations(data: actix_web::web::Data<AppData>) {
@@ -0,0 +1,82 @@
+use chrono::NaiveDateTime;
+use diesel::deserialize::FromSql;
+use diesel::pg::Pg;
+use diesel::serialize::ToSql;
+use diesel::sql_types::SmallInt;
+use diesel::{AsExpression, FromSqlRow, Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+
+// What was shown unredacted
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[derive(AsExpression, FromSqlRow)]
+#[diesel(sql_type = SmallInt)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccessKind {
+    ViolationImage = 1,
+    ViolationClip = 2,
+    RecordingSegment = 3,
+    ViolationReport = 4,
+}
+
+const NUMERIC_VALUES: [i16; 4] = [1, 2, 3, 4];
+
+impl ToSql<SmallInt, Pg> for AccessKind
+where
+    i16: ToSql<SmallInt, Pg>,
+{
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        <i16 as ToSql<SmallInt, Pg>>::to_sql(
+            match self {
+                AccessKind::ViolationImage => &NUMERIC_VALUES[0],
+                AccessKind::ViolationClip => &NUMERIC_VALUES[1],
+                AccessKind::RecordingSegment => &NUMERIC_VALUES[2],
+                AccessKind::ViolationReport => &NUMERIC_VALUES[3],
+            },
+            out,
+        )
+    }
+}
+
+impl FromSql<SmallInt, Pg> for AccessKind
+where
+    i16: FromSql<SmallInt, Pg>,
+{
+    fn from_sql(bytes: diesel::backend::RawValue<'_, Pg>) -> diesel::deserialize::Result<Self> {
+        match <i16 as FromSql<SmallInt, Pg>>::from_sql(bytes)? {
+            1 => Ok(Self::ViolationImage),
+            2 => Ok(Self::ViolationClip),
+            3 => Ok(Self::RecordingSegment),
+            4 => Ok(Self::ViolationReport),
+            _ => Err("Unrecognized AccessKind variant".into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::access_logs)]
+pub struct AccessLogInsert {
+    pub user_id: Option<uuid::Uuid>,
+    pub access_kind: AccessKind,
+    pub subject_id: Option<uuid::Uuid>,
+    pub ip_address: Option<String>,
+    pub access_time: NaiveDateTime,
+}
+
+#[derive(Debug, Queryable, Serialize)]
+pub struct AccessLogSelect {
+    pub id: uuid::Uuid,
+    #[serde(rename = "user-id")]
+    pub user_id: Option<uuid::Uuid>,
+    #[serde(rename = "access-kind")]
+    pub access_kind: AccessKind,
+    #[serde(rename = "subject-id")]
+    pub subject_id: Option<uuid::Uuid>,
+    #[serde(rename = "ip-address")]
+    pub ip_address: Option<String>,
+    #[serde(rename = "access-time")]
+    pub access_time: NaiveDateTime,
+}
@@ -2,7 +2,8 @@ use actix_web::http::StatusCode;
 use diesel::{
     deserialize::FromSqlRow,
     sql_types::{BigInt, Text},
-    AsChangeset, Insertable, Queryable,
+    AsChangeset, ExpressionMethods, Insertable, PgConnection, QueryDsl, QueryResult, Queryable,
+    RunQueryDsl,
 };
 use serde::{Deserialize, Serialize};
 
@@ -22,6 +23,51 @@ pub struct AreaSelect {
     code: String,
 }
 
+// What still points at an area, any of it keeps the area from being deleted
+#[derive(Debug, Serialize)]
+pub struct AreaDependents {
+    pub guards: i64,
+    pub cameras: i64,
+    pub violations: i64,
+    pub attendance: i64,
+}
+
+impl AreaDependents {
+    pub fn count(connection: &mut PgConnection, area_code: &str) -> QueryResult<Self> {
+        use crate::schema::{attendance, cameras, users, violations};
+
+        Ok(Self {
+            guards: users::table
+                .filter(users::assigned_area.eq(area_code))
+                .count()
+                .get_result(connection)?,
+            cameras: cameras::table
+                .filter(cameras::area_code.eq(area_code))
+                .count()
+                .get_result(connection)?,
+            violations: violations::table
+                .filter(violations::area_code.eq(area_code))
+                .count()
+                .get_result(connection)?,
+            attendance: attendance::table
+                .filter(attendance::area_code.eq(area_code))
+                .count()
+                .get_result(connection)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.guards == 0 && self.cameras == 0 && self.violations == 0 && self.attendance == 0
+    }
+}
+
+#[derive(Debug, Queryable, Serialize)]
+pub struct AreaCamera {
+    pub id: uuid::Uuid,
+    pub label: String,
+    pub deactivated: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AreaGuardCount {
     name: String,
@@ -78,4 +124,44 @@ pub struct CameraInsert {
     pub area_code: String,
     pub camera_url: String,
     pub deactivated: bool,
-}
\ No newline at end of file
+    pub onvif_url: Option<String>,
+    pub onvif_profile: Option<String>,
+    pub recording_quota_mb: Option<i32>,
+}
+
+#[derive(Debug, Queryable, Serialize)]
+pub struct CameraSelect {
+    pub id: uuid::Uuid,
+    pub label: String,
+    #[serde(rename = "area-code")]
+    pub area_code: String,
+    #[serde(rename = "camera-url", serialize_with = "redacted")]
+    pub camera_url: String,
+    pub deactivated: bool,
+    #[serde(rename = "has-credentials")]
+    pub has_credentials: bool,
+    #[serde(rename = "onvif-url")]
+    pub onvif_url: Option<String>,
+    #[serde(rename = "onvif-profile")]
+    pub onvif_profile: Option<String>,
+    #[serde(rename = "recording-quota-mb")]
+    pub recording_quota_mb: Option<i32>,
+}
+
+// Credentials live in the vault, this only catches a url that still carries some
+fn redacted<S: serde::Serializer>(url: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&crate::data::credential_vault::split_credentials(url).0)
+}
+
+// Fields left as None are not touched, Some(None) clears a nullable one
+#[derive(Debug, AsChangeset)]
+#[diesel(table_name = crate::schema::cameras)]
+pub struct CameraUpdate {
+    pub label: Option<String>,
+    pub area_code: Option<String>,
+    pub camera_url: Option<String>,
+    pub deactivated: Option<bool>,
+    pub onvif_url: Option<Option<String>>,
+    pub onvif_profile: Option<Option<String>>,
+    pub recording_quota_mb: Option<Option<i32>>,
+}
@@ -0,0 +1,124 @@
+use std::collections::HashSet;
+
+use chrono::{Duration, NaiveDateTime};
+use diesel::{
+    ExpressionMethods, Insertable, NullableExpressionMethods, PgConnection, QueryDsl, QueryResult,
+    Queryable, RunQueryDsl,
+};
+use serde::Serialize;
+
+use super::GuardAssignment;
+use crate::server_config::AttendancePolicy;
+
+#[derive(Debug, Queryable, Serialize)]
+pub struct AttendanceSelect {
+    pub id: uuid::Uuid,
+    #[serde(rename = "user-id")]
+    pub user_id: uuid::Uuid,
+    #[serde(rename = "area-code")]
+    pub area_code: String,
+    #[serde(rename = "assignment-id")]
+    pub assignment_id: Option<uuid::Uuid>,
+    #[serde(rename = "shift-start")]
+    pub shift_start: Option<NaiveDateTime>,
+    #[serde(rename = "check-in-time")]
+    pub check_in_time: NaiveDateTime,
+    #[serde(rename = "check-out-time")]
+    pub check_out_time: Option<NaiveDateTime>,
+    #[serde(rename = "code-verified")]
+    pub code_verified: bool,
+}
+
+#[derive(Debug, Clone, Queryable, Insertable, Serialize)]
+#[diesel(table_name = crate::schema::missed_shifts)]
+pub struct MissedShift {
+    #[serde(rename = "assignment-id")]
+    pub assignment_id: uuid::Uuid,
+    #[serde(rename = "shift-start")]
+    pub shift_start: NaiveDateTime,
+    #[serde(rename = "user-id")]
+    pub user_id: uuid::Uuid,
+    #[serde(rename = "area-code")]
+    pub area_code: String,
+    #[serde(rename = "reported-time")]
+    pub reported_time: NaiveDateTime,
+}
+
+impl MissedShift {
+    // Shifts of active guards at areas in use that started more than the grace period ago without a
+    // check-in. Each one is returned by the first run that sees it and recorded for later ones
+    pub fn detect(
+        connection: &mut PgConnection,
+        policy: &AttendancePolicy,
+        now: NaiveDateTime,
+    ) -> QueryResult<Vec<MissedShift>> {
+        use crate::schema::{areas, attendance, guard_assignments, missed_shifts, users};
+
+        let until = now - Duration::minutes(policy.grace_minutes);
+        let since = until - Duration::hours(GuardAssignment::MAX_SHIFT_HOURS);
+        let after_until = until + Duration::nanoseconds(1);
+
+        let assignments: Vec<GuardAssignment> = GuardAssignment::overlapping(since, after_until)
+            .filter(
+                guard_assignments::user_id.eq_any(
+                    users::table
+                        .filter(users::deactivated.eq(false))
+                        .select(users::id),
+                ),
+            )
+            .filter(
+                guard_assignments::area_code.eq_any(
+                    areas::table
+                        .filter(areas::archived_time.is_null())
+                        .select(areas::code),
+                ),
+            )
+            .load(connection)?;
+
+        let started: Vec<MissedShift> = assignments
+            .iter()
+            .flat_map(|assignment| {
+                assignment
+                    .shifts(since, after_until)
+                    .filter(|(start, _)| *start > since)
+                    .map(|(start, _)| MissedShift {
+                        assignment_id: assignment.id,
+                        shift_start: start,
+                        user_id: assignment.user_id,
+                        area_code: assignment.area_code.clone(),
+                        reported_time: now,
+                    })
+            })
+            .collect();
+
+        if started.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let attended: HashSet<(uuid::Uuid, NaiveDateTime)> = attendance::table
+            .filter(attendance::assignment_id.eq_any(started.iter().map(|shift| shift.assignment_id)))
+            .filter(attendance::shift_start.gt(since))
+            .select((
+                attendance::assignment_id.assume_not_null(),
+                attendance::shift_start.assume_not_null(),
+            ))
+            .load::<(uuid::Uuid, NaiveDateTime)>(connection)?
+            .into_iter()
+            .collect();
+
+        let missed: Vec<MissedShift> = started
+            .into_iter()
+            .filter(|shift| !attended.contains(&(shift.assignment_id, shift.shift_start)))
+            .collect();
+
+        if missed.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        diesel::insert_into(missed_shifts::table)
+            .values(&missed)
+            .on_conflict_do_nothing()
+            .returning(missed_shifts::all_columns)
+            .get_results(connection)
+    }
+}
@@ -0,0 +1,10 @@
+use diesel::{Insertable, Queryable};
+
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[diesel(table_name = crate::schema::camera_credentials)]
+pub struct CameraCredential {
+    pub camera_id: uuid::Uuid,
+    pub key_id: String,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
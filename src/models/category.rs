@@ -1,4 +1,5 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = SmallInt)]
 pub enum Category {
     Student = 1,
     Visitor = 2,
@@ -10,6 +11,7 @@ use diesel::deserialize::FromSql;
 use diesel::pg::Pg;
 use diesel::serialize::ToSql;
 use diesel::sql_types::SmallInt;
+use diesel::{AsExpression, FromSqlRow};
 use serde::{Deserialize, Serialize};
 
 impl Serialize for Category {
@@ -78,3 +80,28 @@ where
         }
     }
 }
+
+impl std::fmt::Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Student => "Student",
+            Self::Visitor => "Visitor",
+            Self::Faculty => "Faculty",
+            Self::Staff => "Staff",
+        })
+    }
+}
+
+impl std::str::FromStr for Category {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "1" | "student" => Ok(Self::Student),
+            "2" | "visitor" => Ok(Self::Visitor),
+            "3" | "faculty" => Ok(Self::Faculty),
+            "4" | "staff" => Ok(Self::Staff),
+            _ => Err(format!("invalid category: {value}")),
+        }
+    }
+}
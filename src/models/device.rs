@@ -0,0 +1,70 @@
+use chrono::NaiveDateTime;
+use diesel::deserialize::FromSql;
+use diesel::pg::Pg;
+use diesel::serialize::ToSql;
+use diesel::sql_types::SmallInt;
+use diesel::{AsExpression, FromSqlRow, Queryable};
+use serde::{Deserialize, Serialize};
+
+use super::DeviceOs;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[derive(AsExpression, FromSqlRow)]
+#[diesel(sql_type = SmallInt)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeviceStatus {
+    // seen at login but not allowed to open a session yet
+    Pending = 1,
+    Trusted = 2,
+    Blocked = 3,
+}
+
+const NUMERIC_VALUES: [i16; 3] = [1, 2, 3];
+
+impl ToSql<SmallInt, Pg> for DeviceStatus
+where
+    i16: ToSql<SmallInt, Pg>,
+{
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        <i16 as ToSql<SmallInt, Pg>>::to_sql(
+            match self {
+                DeviceStatus::Pending => &NUMERIC_VALUES[0],
+                DeviceStatus::Trusted => &NUMERIC_VALUES[1],
+                DeviceStatus::Blocked => &NUMERIC_VALUES[2],
+            },
+            out,
+        )
+    }
+}
+
+impl FromSql<SmallInt, Pg> for DeviceStatus
+where
+    i16: FromSql<SmallInt, Pg>,
+{
+    fn from_sql(bytes: diesel::backend::RawValue<'_, Pg>) -> diesel::deserialize::Result<Self> {
+        match <i16 as FromSql<SmallInt, Pg>>::from_sql(bytes)? {
+            1 => Ok(Self::Pending),
+            2 => Ok(Self::Trusted),
+            3 => Ok(Self::Blocked),
+            _ => Err("Unrecognized DeviceStatus variant".into()),
+        }
+    }
+}
+
+#[derive(Debug, Queryable, Serialize)]
+pub struct DeviceSelect {
+    pub id: uuid::Uuid,
+    #[serde(rename = "device-os")]
+    pub device_os: DeviceOs,
+    #[serde(rename = "device-name")]
+    pub device_name: String,
+    pub status: DeviceStatus,
+    #[serde(rename = "created-time")]
+    pub created_time: NaiveDateTime,
+    #[serde(rename = "last-seen")]
+    pub last_seen: NaiveDateTime,
+}
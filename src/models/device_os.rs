@@ -1,13 +1,14 @@
 use serde::{Serialize, Deserialize};
 
+use diesel::deserialize::FromSql;
 use diesel::pg::Pg;
-use diesel::AsExpression;
+use diesel::{AsExpression, FromSqlRow};
 use diesel::serialize::ToSql;
 use diesel:: sql_types::SmallInt;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[derive(Serialize, Deserialize)]
-#[derive(AsExpression)]
+#[derive(AsExpression, FromSqlRow)]
 #[diesel(sql_type = SmallInt)]
 pub enum DeviceOs {
     #[serde(alias = "android")]
@@ -29,3 +30,14 @@ impl ToSql<SmallInt, Pg> for DeviceOs where i16: ToSql<SmallInt, Pg> {
         }, out)
     }
 }
+
+impl FromSql<SmallInt, Pg> for DeviceOs where i16: FromSql<SmallInt, Pg> {
+    fn from_sql(bytes: diesel::backend::RawValue<'_, Pg>) -> diesel::deserialize::Result<Self> {
+        match <i16 as FromSql<SmallInt, Pg>>::from_sql(bytes)? {
+            1 => Ok(Self::Android),
+            2 => Ok(Self::Windows),
+            3 => Ok(Self::Linux),
+            _ => Err("Unrecognized DeviceOs variant".into()),
+        }
+    }
+}
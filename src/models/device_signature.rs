@@ -71,6 +71,17 @@ impl<'de> serde::Deserialize<'de> for DeviceSignature where String: serde::Deser
     }
 }
 
+impl std::str::FromStr for DeviceSignature {
+    type Err = crate::traits::HexParseErr;
+
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        use crate::traits::FromHexadecimal;
+        let integer = u128::from_hexadecimal(hex)?;
+
+        Ok(Self(SignatureBits { integer }))
+    }
+}
+
 impl From<DeviceSignature> for [u8; 16] {
     fn from(val: DeviceSignature) -> Self {
         unsafe { val.0.bytes }
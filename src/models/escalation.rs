@@ -0,0 +1,92 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{
+    dsl::count_star, ExpressionMethods, Insertable, OptionalExtension, PgConnection, QueryDsl,
+    QueryResult, Queryable, RunQueryDsl,
+};
+use serde::Serialize;
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::escalation_rules)]
+pub struct EscalationRuleInsert {
+    pub label: String,
+    pub violation_count: i32,
+    pub period_days: i32,
+}
+
+#[derive(Debug, Queryable, Serialize)]
+pub struct EscalationRuleSelect {
+    pub id: uuid::Uuid,
+    pub label: String,
+    #[serde(rename = "violation-count")]
+    pub violation_count: i32,
+    #[serde(rename = "period-days")]
+    pub period_days: i32,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::escalations)]
+pub struct EscalationInsert {
+    pub person_id: uuid::Uuid,
+    pub rule_id: uuid::Uuid,
+    pub violation_count: i32,
+    pub created_time: NaiveDateTime,
+    pub resolved: bool,
+}
+
+#[derive(Debug, Clone, Queryable, Serialize)]
+pub struct EscalationSelect {
+    pub id: uuid::Uuid,
+    #[serde(rename = "person-id")]
+    pub person_id: uuid::Uuid,
+    #[serde(rename = "rule-id")]
+    pub rule_id: uuid::Uuid,
+    #[serde(rename = "violation-count")]
+    pub violation_count: i32,
+    #[serde(rename = "created-time")]
+    pub created_time: NaiveDateTime,
+    pub resolved: bool,
+}
+
+impl EscalationInsert {
+    // A rule is not escalated again while the previous escalation is unresolved
+    pub fn evaluate(
+        connection: &mut PgConnection,
+        person_id: uuid::Uuid,
+    ) -> QueryResult<Vec<EscalationSelect>> {
+        use crate::schema::{escalation_rules, escalations, violations};
+
+        let now = Utc::now().naive_utc();
+        let rules: Vec<EscalationRuleSelect> = escalation_rules::table.load(connection)?;
+        let mut created = Vec::new();
+
+        for rule in rules {
+            let violation_count: i64 = violations::table
+                .filter(violations::person_id.eq(person_id))
+                .filter(violations::date_time.ge(now - Duration::days(rule.period_days as i64)))
+                .select(count_star())
+                .get_result(connection)?;
+
+            if violation_count < rule.violation_count as i64 {
+                continue;
+            }
+
+            // the partial unique index on pending escalations keeps a concurrent evaluation from
+            // raising the same one twice, whichever comes second inserts nothing
+            let escalation = diesel::insert_into(escalations::table)
+                .values(EscalationInsert {
+                    person_id,
+                    rule_id: rule.id,
+                    violation_count: violation_count as i32,
+                    created_time: now,
+                    resolved: false,
+                })
+                .on_conflict_do_nothing()
+                .get_result::<EscalationSelect>(connection)
+                .optional()?;
+
+            created.extend(escalation);
+        }
+
+        Ok(created)
+    }
+}
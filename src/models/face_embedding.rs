@@ -0,0 +1,101 @@
+use chrono::NaiveDateTime;
+use diesel::dsl::{exists, not};
+use diesel::{
+    AsChangeset, BoolExpressionMethods, ExpressionMethods, Insertable, NullableExpressionMethods,
+    OptionalExtension, PgConnection, QueryDsl, QueryResult, RunQueryDsl,
+};
+
+// (violation, blob store key, bytes not yet moved to the store, archive path)
+pub type PendingImage = (uuid::Uuid, Option<String>, Option<Vec<u8>>, Option<String>);
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::face_embeddings)]
+#[diesel(treat_none_as_null = true)]
+pub struct FaceEmbeddingInsert {
+    pub violation_id: uuid::Uuid,
+    pub model: String,
+    pub embedding: Option<Vec<f32>>,
+    pub created_time: NaiveDateTime,
+}
+
+impl FaceEmbeddingInsert {
+    pub fn upsert(&self, connection: &mut PgConnection) -> QueryResult<usize> {
+        use crate::schema::face_embeddings;
+
+        diesel::insert_into(face_embeddings::table)
+            .values(self)
+            .on_conflict(face_embeddings::violation_id)
+            .do_update()
+            .set(self)
+            .execute(connection)
+    }
+
+    // Violations with an image but no vector of the model, newest first
+    pub fn pending(
+        connection: &mut PgConnection,
+        model: &str,
+        limit: i64,
+    ) -> QueryResult<Vec<PendingImage>> {
+        use crate::schema::{face_embeddings, violations};
+
+        violations::table
+            .filter(not(exists(
+                face_embeddings::table
+                    .filter(face_embeddings::violation_id.eq(violations::id))
+                    .filter(face_embeddings::model.eq(model)),
+            )))
+            .filter(
+                violations::image_key
+                    .is_not_null()
+                    .or(violations::image_bytes.is_not_null())
+                    .or(violations::image_archive.is_not_null()),
+            )
+            .order_by(violations::date_time.desc())
+            .limit(limit)
+            .select((
+                violations::id,
+                violations::image_key,
+                violations::image_bytes,
+                violations::image_archive,
+            ))
+            .load(connection)
+    }
+
+    pub fn of_violation(
+        connection: &mut PgConnection,
+        model: &str,
+        violation_id: uuid::Uuid,
+    ) -> QueryResult<Option<Vec<f32>>> {
+        use crate::schema::face_embeddings;
+
+        face_embeddings::table
+            .find(violation_id)
+            .filter(face_embeddings::model.eq(model))
+            .select(face_embeddings::embedding)
+            .first::<Option<Vec<f32>>>(connection)
+            .optional()
+            .map(Option::flatten)
+    }
+
+    // (person, violation, vector) of every identified violation but `except`
+    pub fn identified(
+        connection: &mut PgConnection,
+        model: &str,
+        except: uuid::Uuid,
+    ) -> QueryResult<Vec<(uuid::Uuid, uuid::Uuid, Vec<f32>)>> {
+        use crate::schema::{face_embeddings, violations};
+
+        face_embeddings::table
+            .inner_join(violations::table)
+            .filter(face_embeddings::model.eq(model))
+            .filter(face_embeddings::embedding.is_not_null())
+            .filter(violations::person_id.is_not_null())
+            .filter(violations::id.ne(except))
+            .select((
+                violations::person_id.assume_not_null(),
+                violations::id,
+                face_embeddings::embedding.assume_not_null(),
+            ))
+            .load(connection)
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use diesel::deserialize::FromSql;
+use diesel::dsl::IntoBoxed;
+use diesel::pg::Pg;
+use diesel::serialize::ToSql;
+use diesel::sql_types::SmallInt;
+use diesel::{
+    AsExpression, BoolExpressionMethods, ExpressionMethods, FromSqlRow, PgConnection, QueryDsl,
+    QueryResult, Queryable, RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[derive(AsExpression, FromSqlRow)]
+#[diesel(sql_type = SmallInt)]
+#[serde(rename_all = "kebab-case")]
+pub enum Recurrence {
+    Once = 1,
+    Daily = 2,
+    Weekly = 3,
+}
+
+impl Recurrence {
+    // Times are UTC like the rest of the schema, so a daily shift repeats every 24 hours
+    pub fn period(self) -> Option<Duration> {
+        match self {
+            Recurrence::Once => None,
+            Recurrence::Daily => Some(Duration::days(1)),
+            Recurrence::Weekly => Some(Duration::weeks(1)),
+        }
+    }
+}
+
+const NUMERIC_VALUES: [i16; 3] = [1, 2, 3];
+
+impl ToSql<SmallInt, Pg> for Recurrence
+where
+    i16: ToSql<SmallInt, Pg>,
+{
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        <i16 as ToSql<SmallInt, Pg>>::to_sql(
+            match self {
+                Recurrence::Once => &NUMERIC_VALUES[0],
+                Recurrence::Daily => &NUMERIC_VALUES[1],
+                Recurrence::Weekly => &NUMERIC_VALUES[2],
+            },
+            out,
+        )
+    }
+}
+
+impl FromSql<SmallInt, Pg> for Recurrence
+where
+    i16: FromSql<SmallInt, Pg>,
+{
+    fn from_sql(bytes: diesel::backend::RawValue<'_, Pg>) -> diesel::deserialize::Result<Self> {
+        match <i16 as FromSql<SmallInt, Pg>>::from_sql(bytes)? {
+            1 => Ok(Self::Once),
+            2 => Ok(Self::Daily),
+            3 => Ok(Self::Weekly),
+            _ => Err("Unrecognized Recurrence variant".into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Queryable, Serialize)]
+pub struct GuardAssignment {
+    pub id: uuid::Uuid,
+    #[serde(rename = "user-id")]
+    pub user_id: uuid::Uuid,
+    #[serde(rename = "area-code")]
+    pub area_code: String,
+    #[serde(rename = "start-time")]
+    pub start_time: NaiveDateTime,
+    #[serde(rename = "end-time")]
+    pub end_time: NaiveDateTime,
+    pub recurrence: Recurrence,
+    #[serde(rename = "repeat-until")]
+    pub repeat_until: Option<NaiveDate>,
+}
+
+impl GuardAssignment {
+    // Longest shift, which also keeps the repetitions of a daily shift apart
+    pub const MAX_SHIFT_HOURS: i64 = 24;
+
+    // Shifts of the assignment that overlap the window from..to
+    pub fn shifts(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> impl Iterator<Item = (NaiveDateTime, NaiveDateTime)> + '_ {
+        let period = self.recurrence.period();
+        let length = self.end_time - self.start_time;
+
+        // the first repetition that has not ended before the window opens
+        let first = match period {
+            Some(period) if from >= self.end_time => {
+                (from - self.end_time).num_seconds() / period.num_seconds() + 1
+            }
+            _ => 0,
+        };
+
+        (first..)
+            .map_while(move |index| match (index, period) {
+                (0, _) => Some(self.start_time),
+                (_, Some(period)) => Some(self.start_time + period * index as i32),
+                (_, None) => None,
+            })
+            .take_while(move |start| {
+                *start < to && !matches!(self.repeat_until, Some(until) if start.date() > until)
+            })
+            .map(move |start| (start, start + length))
+            .filter(move |(_, end)| *end > from)
+    }
+
+    pub fn covers(&self, time: NaiveDateTime) -> bool {
+        self.shifts(time, time + Duration::nanoseconds(1)).next().is_some()
+    }
+
+    // Assignments that may have a shift overlapping from..to, `shifts` tells which ones do
+    pub fn overlapping<'a>(
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> IntoBoxed<'a, crate::schema::guard_assignments::table, Pg> {
+        use crate::schema::guard_assignments;
+
+        guard_assignments::table
+            .filter(guard_assignments::start_time.lt(to))
+            .filter(
+                guard_assignments::recurrence
+                    .ne(Recurrence::Once)
+                    .or(guard_assignments::end_time.gt(from)),
+            )
+            .filter(
+                guard_assignments::repeat_until
+                    .is_null()
+                    .or(guard_assignments::repeat_until.ge((from - Duration::hours(Self::MAX_SHIFT_HOURS)).date())),
+            )
+            .into_boxed()
+    }
+
+    // The shift of the user at the area that is on at the time, or starts within `early`
+    pub fn shift_at(
+        connection: &mut PgConnection,
+        user_id: uuid::Uuid,
+        area_code: &str,
+        time: NaiveDateTime,
+        early: Duration,
+    ) -> QueryResult<Option<(uuid::Uuid, NaiveDateTime)>> {
+        use crate::schema::guard_assignments;
+
+        let assignments: Vec<GuardAssignment> = Self::overlapping(time, time + early)
+            .filter(guard_assignments::user_id.eq(user_id))
+            .filter(guard_assignments::area_code.eq(area_code))
+            .load(connection)?;
+
+        Ok(assignments
+            .iter()
+            .flat_map(|assignment| {
+                assignment
+                    .shifts(time, time + early + Duration::nanoseconds(1))
+                    .map(|(start, _)| (assignment.id, start))
+            })
+            .min_by_key(|(_, start)| *start))
+    }
+
+    // Areas the users are posted to at the time. Guards on the roster follow it,
+    // the others keep the single area they were assigned to
+    pub fn current_areas(
+        connection: &mut PgConnection,
+        user_ids: &[uuid::Uuid],
+        time: NaiveDateTime,
+    ) -> QueryResult<HashMap<uuid::Uuid, Vec<String>>> {
+        use crate::schema::{guard_assignments, users};
+
+        let rostered: Vec<uuid::Uuid> = guard_assignments::table
+            .filter(guard_assignments::user_id.eq_any(user_ids))
+            .select(guard_assignments::user_id)
+            .distinct()
+            .load(connection)?;
+
+        let candidates: Vec<GuardAssignment> = Self::overlapping(time, time + Duration::nanoseconds(1))
+            .filter(guard_assignments::user_id.eq_any(&rostered))
+            .load(connection)?;
+
+        let mut areas: HashMap<uuid::Uuid, Vec<String>> =
+            rostered.iter().map(|user_id| (*user_id, Vec::new())).collect();
+
+        for assignment in candidates.into_iter().filter(|assignment| assignment.covers(time)) {
+            let list = areas.entry(assignment.user_id).or_default();
+
+            if !list.contains(&assignment.area_code) {
+                list.push(assignment.area_code);
+            }
+        }
+
+        let standing: Vec<(uuid::Uuid, Option<String>)> = users::table
+            .filter(users::id.eq_any(user_ids))
+            .filter(users::id.ne_all(&rostered))
+            .select((users::id, users::assigned_area))
+            .load(connection)?;
+
+        areas.extend(
+            standing
+                .into_iter()
+                .map(|(user_id, area)| (user_id, area.into_iter().collect())),
+        );
+
+        Ok(areas)
+    }
+}
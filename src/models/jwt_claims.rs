@@ -5,17 +5,24 @@ use serde::{Deserialize, Serialize};
 pub struct JwtClaims {
     #[serde(alias = "session_id")]
     pub session_id: uuid::Uuid,
+    pub iss: String,
+    pub aud: String,
+    pub iat: i64,
     exp: usize,
 }
 
 impl JwtClaims {
-    pub fn new(session_id: uuid::Uuid) -> Self {
-        let exp = (Utc::now() + Duration::days(15))
+    pub fn new(session_id: uuid::Uuid, issuer: &str, audience: &str, ttl: Duration) -> Self {
+        let now = Utc::now();
+        let exp = (now + ttl)
             .timestamp() as usize;
 
         Self {
             session_id,
+            iss: issuer.to_owned(),
+            aud: audience.to_owned(),
+            iat: now.timestamp(),
             exp
         }
     }
-}
\ No newline at end of file
+}
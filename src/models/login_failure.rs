@@ -0,0 +1,113 @@
+use chrono::{Duration, NaiveDateTime};
+use diesel::dsl::{self, count_star};
+use diesel::{ExpressionMethods, Insertable, PgConnection, QueryDsl, QueryResult, Queryable, RunQueryDsl};
+use serde::Serialize;
+
+use crate::server_config::LoginPolicy;
+
+// usernames longer than the column cannot exist anyway
+const USERNAME_LENGTH: usize = 24;
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::login_failures)]
+pub struct LoginFailureInsert {
+    pub username: String,
+    pub user_id: Option<uuid::Uuid>,
+    pub ip_address: Option<String>,
+    pub attempt_time: NaiveDateTime,
+}
+
+#[derive(Debug, Queryable, Serialize)]
+pub struct LoginFailureSelect {
+    pub id: uuid::Uuid,
+    pub username: String,
+    #[serde(rename = "user-id")]
+    pub user_id: Option<uuid::Uuid>,
+    #[serde(rename = "ip-address")]
+    pub ip_address: Option<String>,
+    #[serde(rename = "attempt-time")]
+    pub attempt_time: NaiveDateTime,
+}
+
+pub struct LoginThrottle {
+    // recent failures of the username, drives the progressive delay
+    pub failures: i64,
+    pub locked_until: Option<NaiveDateTime>,
+}
+
+impl LoginFailureInsert {
+    pub fn new(
+        username: &str,
+        user_id: Option<uuid::Uuid>,
+        ip_address: Option<String>,
+        attempt_time: NaiveDateTime,
+    ) -> Self {
+        Self {
+            username: truncate_username(username),
+            user_id,
+            ip_address,
+            attempt_time,
+        }
+    }
+}
+
+impl LoginThrottle {
+    // The same rules apply whether the username exists or not, so a lockout
+    // tells nothing about which accounts are real
+    pub fn check(
+        connection: &mut PgConnection,
+        policy: &LoginPolicy,
+        username: &str,
+        user_id: Option<uuid::Uuid>,
+        ip_address: Option<&str>,
+        now: NaiveDateTime,
+    ) -> QueryResult<Self> {
+        use crate::schema::{login_failures, sessions};
+
+        let mut since = now - Duration::minutes(policy.window_minutes);
+
+        // a successful login starts the count over
+        if let Some(user_id) = user_id {
+            let last_login: Option<NaiveDateTime> = sessions::table
+                .filter(sessions::user_id.eq(user_id))
+                .select(dsl::max(sessions::last_login))
+                .first(connection)?;
+
+            since = last_login.map_or(since, |last_login| since.max(last_login));
+        }
+
+        let (failures, last_failure): (i64, Option<NaiveDateTime>) = login_failures::table
+            .filter(login_failures::username.eq(truncate_username(username)))
+            .filter(login_failures::attempt_time.gt(since))
+            .select((count_star(), dsl::max(login_failures::attempt_time)))
+            .first(connection)?;
+
+        let mut locked_until = last_failure
+            .filter(|_| failures >= policy.max_failures)
+            .map(|last_failure| last_failure + Duration::minutes(policy.lockout_minutes));
+
+        if let Some(ip_address) = ip_address {
+            let (address_failures, last_failure): (i64, Option<NaiveDateTime>) =
+                login_failures::table
+                    .filter(login_failures::ip_address.eq(ip_address))
+                    .filter(login_failures::attempt_time.gt(now - Duration::minutes(policy.window_minutes)))
+                    .select((count_star(), dsl::max(login_failures::attempt_time)))
+                    .first(connection)?;
+
+            let address_locked_until = last_failure
+                .filter(|_| address_failures >= policy.max_address_failures)
+                .map(|last_failure| last_failure + Duration::minutes(policy.lockout_minutes));
+
+            locked_until = locked_until.max(address_locked_until);
+        }
+
+        Ok(Self {
+            failures,
+            locked_until: locked_until.filter(|locked_until| *locked_until > now),
+        })
+    }
+}
+
+fn truncate_username(username: &str) -> String {
+    username.chars().take(USERNAME_LENGTH).collect()
+}
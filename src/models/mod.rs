@@ -1,10 +1,25 @@
+mod access_log;
 mod area;
+mod attendance;
+mod camera_credential;
 mod category;
+mod device;
 mod device_os;
 mod device_signature;
+mod escalation;
+mod face_embedding;
+mod guard_assignment;
 mod jwt_claims;
+mod login_failure;
 mod password_hash;
+mod permission;
+mod person;
+mod person_import;
+mod recording_segment;
+mod refresh_token;
+mod retention_run;
 mod session;
+mod totp_policy;
 mod user;
 mod user_claims;
 mod user_role;
@@ -12,20 +27,43 @@ mod violation;
 mod violation_kind;
 mod into_model;
 
+pub use device::{DeviceSelect, DeviceStatus};
 pub use device_os::DeviceOs;
 pub use device_signature::DeviceSignature;
 pub use password_hash::PasswordHash;
-pub use user_role::UserRole;
+pub use permission::{Access, Permission};
+#[cfg(test)]
+pub(crate) use permission::{POLICY, ROUTES};
+pub use user_role::{numbered_role, numbered_role_option, UserRole};
 
-pub use area::{AreaGuardCount, AreaInsert, AreaSelect, CameraInsert};
+pub use access_log::{AccessKind, AccessLogInsert, AccessLogSelect};
+pub use area::{
+    AreaCamera, AreaDependents, AreaGuardCount, AreaInsert, AreaSelect, CameraInsert, CameraSelect,
+    CameraUpdate,
+};
+pub use attendance::{AttendanceSelect, MissedShift};
+pub use camera_credential::CameraCredential;
 pub use category::Category;
+pub use escalation::{EscalationInsert, EscalationRuleInsert, EscalationRuleSelect, EscalationSelect};
+pub use face_embedding::FaceEmbeddingInsert;
+pub use guard_assignment::{GuardAssignment, Recurrence};
 pub use jwt_claims::JwtClaims;
-pub use session::SessionInsert;
+pub use login_failure::{LoginFailureInsert, LoginFailureSelect, LoginThrottle};
+pub use person::{PersonInsert, PersonSelect};
+pub use person_import::PersonImport;
+pub use recording_segment::{RecordingSegment, RecordingSegmentInsert};
+pub use refresh_token::{random_token, RefreshToken};
+pub use retention_run::{RetentionRunInsert, RetentionRunSelect};
+pub use session::{SessionInsert, SessionSelect};
+pub use totp_policy::TotpPolicy;
 pub use user::UserBasicSelect;
 pub use user::UserInsert;
+pub use user::UserListEntry;
 pub use user::UserSelect;
 pub use user_claims::UserClaims;
+pub(crate) use user_claims::{CSRF_COOKIE, DEVICE_COOKIE, JWT_COOKIE};
 pub use violation::IdentifiedViolation;
+pub use violation::ViolationReportRow;
 pub use violation::ViolationUnknown;
 pub use violation::ViolationUnknownInsert;
 pub use violation_kind::ViolationKind;
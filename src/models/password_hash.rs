@@ -1,50 +1,52 @@
-use base64::engine::general_purpose::STANDARD_NO_PAD;
-use base64::Engine;
 use diesel::deserialize::FromSql;
 use diesel::pg::Pg;
 use diesel::serialize::ToSql;
-use diesel::sql_types::Binary;
+use diesel::sql_types::Text;
 use diesel::{AsExpression, FromSqlRow};
 use std::fmt::Display;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
-#[diesel(sql_type = diesel::sql_types::Binary)]
-pub struct PasswordHash([u8; 64]);
+// Argon2 hash in the PHC string format, e.g. $argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>
+#[derive(Debug, Clone, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = diesel::sql_types::Text)]
+pub struct PasswordHash(String);
 
-impl From<[u8; 64]> for PasswordHash {
+impl PasswordHash {
     #[inline]
-    fn from(arr: [u8; 64]) -> Self {
-        Self(arr)
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for PasswordHash {
+    #[inline]
+    fn from(phc: String) -> Self {
+        Self(phc)
     }
 }
 
 impl Display for PasswordHash {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&STANDARD_NO_PAD.encode(self.0))
+        f.write_str(&self.0)
     }
 }
 
-impl<'a> ToSql<Binary, Pg> for PasswordHash
+impl ToSql<Text, Pg> for PasswordHash
 where
-    &'a [u8]: ToSql<Binary, Pg>,
+    String: ToSql<Text, Pg>,
 {
     fn to_sql<'b>(
         &'b self,
         out: &mut diesel::serialize::Output<'b, '_, Pg>,
     ) -> diesel::serialize::Result {
-        <[u8] as ToSql<Binary, Pg>>::to_sql(self.0.as_ref(), out)
+        <String as ToSql<Text, Pg>>::to_sql(&self.0, out)
     }
 }
 
-impl FromSql<Binary, Pg> for PasswordHash
+impl FromSql<Text, Pg> for PasswordHash
 where
-    Vec<u8>: FromSql<Binary, Pg>,
+    String: FromSql<Text, Pg>,
 {
     fn from_sql(bytes: diesel::backend::RawValue<'_, Pg>) -> diesel::deserialize::Result<Self> {
-        if let Ok(arr) = <Vec<u8> as FromSql<Binary, Pg>>::from_sql(bytes)?.try_into() {
-            return Ok(Self(arr));
-        }
-
-        Err("Cannot convert to PasswordHash".into())
+        Ok(Self(<String as FromSql<Text, Pg>>::from_sql(bytes)?))
     }
 }
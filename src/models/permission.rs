@@ -0,0 +1,185 @@
+use super::UserRole;
+
+use UserRole::{SecurityGuard as Guard, SecurityHead as Head, SystemAdmin as Admin};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    AreasRead,
+    AreasWrite,
+    AttendanceCheckIn,
+    AttendanceCodes,
+    AttendanceRead,
+    CamerasControl,
+    CamerasRead,
+    CamerasWrite,
+    DevicesUnblock,
+    EscalationsManage,
+    LogsRead,
+    PersonsCreate,
+    PersonsImport,
+    PersonsRead,
+    RecordingsRead,
+    RostersRead,
+    RostersWrite,
+    SessionsManage,
+    TotpManage,
+    UsersRead,
+    UsersWrite,
+    ViolationsExport,
+    ViolationsRead,
+    ViolationsReadAllAreas,
+    ViolationsViewUnredacted,
+    ViolationsWrite,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    // login and token refresh, before there is a session
+    Public,
+    // any session, the handler narrows it to the caller's own data
+    SignedIn,
+    Requires(Permission),
+}
+
+// Who may do what, the only place roles are mapped to actions
+pub const POLICY: &[(Permission, &str, &[UserRole])] = &[
+    (Permission::AreasRead, "areas.read", &[Guard, Head, Admin]),
+    (Permission::AreasWrite, "areas.write", &[Head]),
+    (Permission::AttendanceCheckIn, "attendance.check-in", &[Guard]),
+    (Permission::AttendanceCodes, "attendance.codes", &[Head]),
+    (Permission::AttendanceRead, "attendance.read", &[Head]),
+    (Permission::CamerasControl, "cameras.control", &[Guard, Head]),
+    (Permission::CamerasRead, "cameras.read", &[Head, Admin]),
+    (Permission::CamerasWrite, "cameras.write", &[Head, Admin]),
+    (Permission::DevicesUnblock, "devices.unblock", &[Admin]),
+    (Permission::EscalationsManage, "escalations.manage", &[Head]),
+    (Permission::LogsRead, "logs.read", &[Admin]),
+    (Permission::PersonsCreate, "persons.create", &[Guard, Head, Admin]),
+    (Permission::PersonsImport, "persons.import", &[Head, Admin]),
+    (Permission::PersonsRead, "persons.read", &[Guard, Head, Admin]),
+    (Permission::RecordingsRead, "recordings.read", &[Head]),
+    (Permission::RostersRead, "rosters.read", &[Head]),
+    (Permission::RostersWrite, "rosters.write", &[Head]),
+    (Permission::SessionsManage, "sessions.manage", &[Admin]),
+    (Permission::TotpManage, "totp.manage", &[Admin]),
+    (Permission::UsersRead, "users.read", &[Head, Admin]),
+    (Permission::UsersWrite, "users.write", &[Head, Admin]),
+    (Permission::ViolationsExport, "violations.export", &[Head]),
+    (Permission::ViolationsRead, "violations.read", &[Guard, Head]),
+    (Permission::ViolationsReadAllAreas, "violations.read-all-areas", &[Head]),
+    (Permission::ViolationsViewUnredacted, "violations.view-unredacted", &[Head]),
+    (Permission::ViolationsWrite, "violations.write", &[Guard, Head]),
+];
+
+// Every route with what it takes to call it, a route missing here is refused
+pub const ROUTES: &[(&str, &str, Access)] = &[
+    ("POST", "/users/login", Access::Public),
+    ("POST", "/users/refresh", Access::Public),
+    ("GET", "/users/current", Access::SignedIn),
+    ("POST", "/users/register", Access::Requires(Permission::UsersWrite)),
+    ("GET", "/users/unassigned", Access::Requires(Permission::UsersRead)),
+    ("GET", "/users/avatar", Access::SignedIn),
+    ("PATCH", "/users/avatar", Access::SignedIn),
+    ("DELETE", "/users/avatar", Access::SignedIn),
+    ("POST", "/users/logout", Access::SignedIn),
+    ("GET", "/users/sessions", Access::SignedIn),
+    ("DELETE", "/users/sessions", Access::SignedIn),
+    ("DELETE", "/users/sessions/others", Access::SignedIn),
+    ("DELETE", "/users/sessions/all", Access::SignedIn),
+    ("POST", "/users/totp", Access::SignedIn),
+    ("GET", "/users/totp/qr", Access::SignedIn),
+    ("POST", "/users/totp/confirm", Access::SignedIn),
+    ("POST", "/users/totp/recovery-codes", Access::SignedIn),
+    ("DELETE", "/users/totp", Access::SignedIn),
+    ("GET", "/users/totp/policies", Access::Requires(Permission::TotpManage)),
+    ("PUT", "/users/totp/policies", Access::Requires(Permission::TotpManage)),
+    ("GET", "/users/devices", Access::SignedIn),
+    ("PATCH", "/users/devices", Access::SignedIn),
+    ("DELETE", "/users/devices", Access::SignedIn),
+    ("GET", "/users", Access::Requires(Permission::UsersRead)),
+    ("PATCH", "/users", Access::SignedIn),
+    ("PUT", "/users/password", Access::SignedIn),
+    ("POST", "/users/password/reset", Access::Requires(Permission::UsersWrite)),
+    ("POST", "/users/deactivate", Access::Requires(Permission::UsersWrite)),
+    ("POST", "/users/reactivate", Access::Requires(Permission::UsersWrite)),
+    ("PUT", "/users/role", Access::Requires(Permission::UsersWrite)),
+    ("GET", "/logs/entries", Access::Requires(Permission::LogsRead)),
+    ("GET", "/logs/retention", Access::Requires(Permission::LogsRead)),
+    ("GET", "/logs/login-failures", Access::Requires(Permission::LogsRead)),
+    ("GET", "/logs/access", Access::Requires(Permission::LogsRead)),
+    ("GET", "/areas/list", Access::Requires(Permission::AreasRead)),
+    ("GET", "/areas/{code}", Access::Requires(Permission::AreasRead)),
+    ("PATCH", "/areas", Access::Requires(Permission::AreasWrite)),
+    ("POST", "/areas/create", Access::Requires(Permission::AreasWrite)),
+    ("DELETE", "/areas/remove", Access::Requires(Permission::AreasWrite)),
+    ("PATCH", "/areas/assign", Access::Requires(Permission::AreasWrite)),
+    ("GET", "/areas/camera", Access::Requires(Permission::CamerasRead)),
+    ("GET", "/areas/camera/{id}", Access::Requires(Permission::CamerasRead)),
+    ("GET", "/areas/camera/discover", Access::Requires(Permission::CamerasWrite)),
+    ("POST", "/areas/camera/probe", Access::Requires(Permission::CamerasWrite)),
+    ("POST", "/areas/camera/{id}/ptz/move", Access::Requires(Permission::CamerasControl)),
+    ("POST", "/areas/camera/{id}/ptz/stop", Access::Requires(Permission::CamerasControl)),
+    ("POST", "/areas/camera/{id}/ptz/goto", Access::Requires(Permission::CamerasControl)),
+    ("GET", "/areas/camera/{id}/ptz/presets", Access::Requires(Permission::CamerasControl)),
+    ("POST", "/areas/camera/{id}/ptz/presets", Access::Requires(Permission::CamerasWrite)),
+    ("DELETE", "/areas/camera/{id}/ptz/presets", Access::Requires(Permission::CamerasWrite)),
+    ("GET", "/areas/camera/{id}/recordings", Access::Requires(Permission::RecordingsRead)),
+    ("GET", "/areas/camera/{id}/recordings/playlist", Access::Requires(Permission::RecordingsRead)),
+    ("GET", "/areas/camera/{id}/recordings/segments/{segment}", Access::Requires(Permission::RecordingsRead)),
+    ("POST", "/areas/camera", Access::Requires(Permission::CamerasWrite)),
+    ("PATCH", "/areas/camera", Access::Requires(Permission::CamerasWrite)),
+    ("DELETE", "/areas/camera", Access::Requires(Permission::CamerasWrite)),
+    ("GET", "/areas/roster", Access::SignedIn),
+    ("POST", "/areas/roster", Access::Requires(Permission::RostersWrite)),
+    ("DELETE", "/areas/roster", Access::Requires(Permission::RostersWrite)),
+    ("GET", "/violations/unidentified", Access::Requires(Permission::ViolationsRead)),
+    ("GET", "/violations/identified", Access::Requires(Permission::ViolationsRead)),
+    ("GET", "/violations/image", Access::Requires(Permission::ViolationsRead)),
+    ("GET", "/violations/clip", Access::Requires(Permission::ViolationsRead)),
+    ("PATCH", "/violations/record", Access::Requires(Permission::ViolationsWrite)),
+    ("GET", "/violations/suggestions", Access::Requires(Permission::ViolationsWrite)),
+    ("GET", "/violations/export", Access::Requires(Permission::ViolationsExport)),
+    ("GET", "/persons/search", Access::Requires(Permission::PersonsRead)),
+    ("POST", "/persons/create", Access::Requires(Permission::PersonsCreate)),
+    ("POST", "/persons/import", Access::Requires(Permission::PersonsImport)),
+    ("GET", "/persons/history", Access::Requires(Permission::ViolationsRead)),
+    ("GET", "/persons/escalation-rules", Access::Requires(Permission::EscalationsManage)),
+    ("POST", "/persons/escalation-rules", Access::Requires(Permission::EscalationsManage)),
+    ("DELETE", "/persons/escalation-rules", Access::Requires(Permission::EscalationsManage)),
+    ("GET", "/persons/escalations", Access::Requires(Permission::EscalationsManage)),
+    ("PATCH", "/persons/escalations", Access::Requires(Permission::EscalationsManage)),
+    ("POST", "/attendance/check-in", Access::Requires(Permission::AttendanceCheckIn)),
+    ("POST", "/attendance/check-out", Access::Requires(Permission::AttendanceCheckIn)),
+    ("GET", "/attendance", Access::SignedIn),
+    ("GET", "/attendance/missed", Access::Requires(Permission::AttendanceRead)),
+    ("GET", "/attendance/code", Access::Requires(Permission::AttendanceCodes)),
+    ("PUT", "/attendance/code", Access::Requires(Permission::AttendanceCodes)),
+    ("DELETE", "/attendance/code", Access::Requires(Permission::AttendanceCodes)),
+    ("GET", "/socket", Access::SignedIn),
+];
+
+impl Permission {
+    pub fn name(self) -> &'static str {
+        Self::entry(self).1
+    }
+
+    pub fn allows(self, role: UserRole) -> bool {
+        Self::entry(self).2.contains(&role)
+    }
+
+    fn entry(self) -> &'static (Permission, &'static str, &'static [UserRole]) {
+        POLICY
+            .iter()
+            .find(|(permission, _, _)| *permission == self)
+            .expect("every permission has a policy entry")
+    }
+}
+
+impl Access {
+    pub fn of(method: &str, pattern: &str) -> Option<Access> {
+        ROUTES
+            .iter()
+            .find(|(route_method, route_pattern, _)| *route_method == method && *route_pattern == pattern)
+            .map(|(_, _, access)| *access)
+    }
+}
@@ -0,0 +1,68 @@
+use diesel::{
+    ExpressionMethods, Insertable, OptionalExtension, PgConnection, QueryDsl, Queryable,
+    RunQueryDsl,
+};
+use serde::Serialize;
+
+use crate::logging::ResponseError;
+
+use super::Category;
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::persons)]
+pub struct PersonInsert {
+    pub external_id: Option<String>,
+    pub first_name: String,
+    pub last_name: String,
+    pub category: Category,
+    pub department: Option<String>,
+}
+
+#[derive(Debug, Queryable, Serialize)]
+pub struct PersonSelect {
+    pub id: uuid::Uuid,
+    #[serde(rename = "external-id")]
+    pub external_id: Option<String>,
+    #[serde(rename = "first-name")]
+    pub first_name: String,
+    #[serde(rename = "last-name")]
+    pub last_name: String,
+    pub category: Category,
+    pub department: Option<String>,
+}
+
+impl PersonSelect {
+    pub fn select_by_id(
+        connection: &mut PgConnection,
+        person_id: uuid::Uuid,
+    ) -> Result<Self, ResponseError> {
+        use crate::schema::persons;
+
+        match persons::table
+            .filter(persons::id.eq(person_id))
+            .first::<Self>(connection)
+            .optional()
+        {
+            Ok(Some(person)) => Ok(person),
+            Ok(None) => Err(ResponseError::value_do_not_exist("Person")),
+            Err(_) => Err(ResponseError::server_error()),
+        }
+    }
+}
+
+impl PersonInsert {
+    pub fn validate(&self) -> crate::routes::Result<()> {
+        if let Some(external_id) = &self.external_id {
+            ResponseError::length_limit_check("External ID", external_id, 1, 32)?;
+        }
+
+        ResponseError::length_limit_check("First name", &self.first_name, 1, 48)?;
+        ResponseError::length_limit_check("Last name", &self.last_name, 1, 48)?;
+
+        if let Some(department) = &self.department {
+            ResponseError::length_limit_check("Department", department, 1, 128)?;
+        }
+
+        Ok(())
+    }
+}
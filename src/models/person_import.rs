@@ -0,0 +1,227 @@
+use std::collections::{HashMap, HashSet};
+
+use diesel::upsert::excluded;
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, QueryResult, RunQueryDsl};
+use serde::Serialize;
+
+use super::{Category, PersonInsert, PersonSelect};
+
+// Rows are upserted in batches to stay below the bind parameter limit
+const BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Create,
+    Update,
+    Unchanged,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportRowResult {
+    line: u64,
+    #[serde(rename = "external-id")]
+    external_id: String,
+    action: ImportAction,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportRowError {
+    line: u64,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    #[serde(rename = "dry-run")]
+    dry_run: bool,
+    created: usize,
+    updated: usize,
+    unchanged: usize,
+    rows: Vec<ImportRowResult>,
+    errors: Vec<ImportRowError>,
+}
+
+pub struct PersonImport {
+    rows: Vec<(u64, PersonInsert)>,
+    errors: Vec<ImportRowError>,
+}
+
+struct Columns {
+    external_id: usize,
+    first_name: usize,
+    last_name: usize,
+    category: usize,
+    department: Option<usize>,
+}
+
+impl PersonImport {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .flexible(true)
+            .from_reader(bytes);
+
+        let headers = reader.headers().map_err(|error| error.to_string())?.clone();
+
+        let position = |names: &[&str]| {
+            headers.iter().position(|header| {
+                names.contains(&header.to_lowercase().replace(['-', ' '], "_").as_str())
+            })
+        };
+
+        let columns = Columns {
+            external_id: position(&["external_id", "id_number", "id"])
+                .ok_or("Missing external-id column")?,
+            first_name: position(&["first_name"]).ok_or("Missing first-name column")?,
+            last_name: position(&["last_name"]).ok_or("Missing last-name column")?,
+            category: position(&["category"]).ok_or("Missing category column")?,
+            department: position(&["department"]),
+        };
+
+        let mut rows = Vec::new();
+        let mut errors = Vec::new();
+        let mut seen = HashSet::new();
+
+        for (index, record) in reader.records().enumerate() {
+            // the header occupies the first line
+            let mut line = index as u64 + 2;
+
+            let result = record.map_err(|error| error.to_string()).and_then(|record| {
+                if let Some(position) = record.position() {
+                    line = position.line();
+                }
+
+                Self::parse_record(&record, &columns)
+            });
+
+            match result {
+                Ok(person) => {
+                    let external_id = person.external_id.clone().unwrap_or_default();
+
+                    if seen.insert(external_id.clone()) {
+                        rows.push((line, person));
+                    } else {
+                        errors.push(ImportRowError {
+                            line,
+                            message: format!("Duplicate external-id {external_id} in file"),
+                        });
+                    }
+                }
+                Err(message) => errors.push(ImportRowError { line, message }),
+            }
+        }
+
+        Ok(Self { rows, errors })
+    }
+
+    fn parse_record(record: &csv::StringRecord, columns: &Columns) -> Result<PersonInsert, String> {
+        let field = |index: usize| record.get(index).unwrap_or_default().to_owned();
+
+        let external_id = field(columns.external_id);
+
+        if external_id.is_empty() {
+            return Err(String::from("Missing external-id"));
+        }
+
+        let person = PersonInsert {
+            external_id: Some(external_id),
+            first_name: field(columns.first_name),
+            last_name: field(columns.last_name),
+            category: field(columns.category).parse::<Category>()?,
+            department: columns
+                .department
+                .map(field)
+                .filter(|department| !department.is_empty()),
+        };
+
+        person
+            .validate()
+            .map_err(|error| error.response_message().to_owned())?;
+
+        Ok(person)
+    }
+
+    pub fn apply(self, connection: &mut PgConnection, dry_run: bool) -> QueryResult<ImportReport> {
+        use crate::schema::persons;
+
+        let external_ids: Vec<String> = self
+            .rows
+            .iter()
+            .filter_map(|(_, person)| person.external_id.clone())
+            .collect();
+
+        let existing: HashMap<String, PersonSelect> = persons::table
+            .filter(persons::external_id.eq_any(&external_ids))
+            .load::<PersonSelect>(connection)?
+            .into_iter()
+            .filter_map(|person| Some((person.external_id.clone()?, person)))
+            .collect();
+
+        let mut report = ImportReport {
+            dry_run,
+            created: 0,
+            updated: 0,
+            unchanged: 0,
+            rows: Vec::with_capacity(self.rows.len()),
+            errors: self.errors,
+        };
+
+        let mut changes = Vec::new();
+
+        for (line, person) in self.rows {
+            let external_id = person.external_id.clone().unwrap_or_default();
+
+            let action = match existing.get(&external_id) {
+                None => ImportAction::Create,
+                Some(current)
+                    if current.first_name == person.first_name
+                        && current.last_name == person.last_name
+                        && current.category == person.category
+                        && current.department == person.department =>
+                {
+                    ImportAction::Unchanged
+                }
+                Some(_) => ImportAction::Update,
+            };
+
+            match action {
+                ImportAction::Create => report.created += 1,
+                ImportAction::Update => report.updated += 1,
+                ImportAction::Unchanged => report.unchanged += 1,
+            }
+
+            if action != ImportAction::Unchanged {
+                changes.push(person);
+            }
+
+            report.rows.push(ImportRowResult {
+                line,
+                external_id,
+                action,
+            });
+        }
+
+        if !dry_run && !changes.is_empty() {
+            connection.transaction::<_, diesel::result::Error, _>(|connection| {
+                for batch in changes.chunks(BATCH_SIZE) {
+                    diesel::insert_into(persons::table)
+                        .values(batch)
+                        .on_conflict(persons::external_id)
+                        .do_update()
+                        .set((
+                            persons::first_name.eq(excluded(persons::first_name)),
+                            persons::last_name.eq(excluded(persons::last_name)),
+                            persons::category.eq(excluded(persons::category)),
+                            persons::department.eq(excluded(persons::department)),
+                        ))
+                        .execute(connection)?;
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,96 @@
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, Insertable, PgConnection, QueryDsl, QueryResult, Queryable, RunQueryDsl};
+use serde::Serialize;
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::recording_segments)]
+pub struct RecordingSegmentInsert {
+    pub camera_id: uuid::Uuid,
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+    pub file_path: String,
+    pub bytes: i64,
+}
+
+#[derive(Debug, Queryable, Serialize)]
+pub struct RecordingSegment {
+    pub id: uuid::Uuid,
+    #[serde(rename = "camera-id")]
+    pub camera_id: uuid::Uuid,
+    #[serde(rename = "start-time")]
+    pub start_time: NaiveDateTime,
+    #[serde(rename = "end-time")]
+    pub end_time: NaiveDateTime,
+    pub bytes: i64,
+}
+
+impl RecordingSegment {
+    // Segments of the camera overlapping from..to, in the order they were recorded
+    pub fn overlapping(
+        connection: &mut PgConnection,
+        camera_id: uuid::Uuid,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> QueryResult<Vec<Self>> {
+        use crate::schema::recording_segments;
+
+        recording_segments::table
+            .filter(recording_segments::camera_id.eq(camera_id))
+            .filter(recording_segments::start_time.lt(to))
+            .filter(recording_segments::end_time.gt(from))
+            .order_by(recording_segments::start_time)
+            .select((
+                recording_segments::id,
+                recording_segments::camera_id,
+                recording_segments::start_time,
+                recording_segments::end_time,
+                recording_segments::bytes,
+            ))
+            .load(connection)
+    }
+
+    // Drops the segments of the camera that ended before `cutoff`, then the oldest ones until
+    // the rest fit in `quota_bytes`. The files of the dropped ones are given back to delete
+    pub fn prune(
+        connection: &mut PgConnection,
+        camera_id: uuid::Uuid,
+        cutoff: NaiveDateTime,
+        quota_bytes: i64,
+    ) -> QueryResult<Vec<String>> {
+        use crate::schema::recording_segments;
+
+        let mut removed: Vec<String> = diesel::delete(
+            recording_segments::table
+                .filter(recording_segments::camera_id.eq(camera_id))
+                .filter(recording_segments::end_time.lt(cutoff)),
+        )
+        .returning(recording_segments::file_path)
+        .get_results(connection)?;
+
+        let newest_first: Vec<(uuid::Uuid, i64)> = recording_segments::table
+            .filter(recording_segments::camera_id.eq(camera_id))
+            .order_by(recording_segments::start_time.desc())
+            .select((recording_segments::id, recording_segments::bytes))
+            .load(connection)?;
+
+        let mut kept_bytes = 0;
+        let over_quota: Vec<uuid::Uuid> = newest_first
+            .into_iter()
+            .filter(|(_, bytes)| {
+                kept_bytes += bytes;
+                kept_bytes > quota_bytes
+            })
+            .map(|(id, _)| id)
+            .collect();
+
+        if !over_quota.is_empty() {
+            removed.extend(
+                diesel::delete(recording_segments::table.filter(recording_segments::id.eq_any(over_quota)))
+                    .returning(recording_segments::file_path)
+                    .get_results::<String>(connection)?,
+            );
+        }
+
+        Ok(removed)
+    }
+}
@@ -0,0 +1,34 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::rand::{SecureRandom, SystemRandom};
+
+// Opaque token handed to the client, the database only ever sees its hash
+pub struct RefreshToken(String);
+
+impl RefreshToken {
+    pub fn generate() -> Option<Self> {
+        random_token().map(Self)
+    }
+
+    pub fn hash_of(token: &str) -> Vec<u8> {
+        ring::digest::digest(&ring::digest::SHA256, token.as_bytes())
+            .as_ref()
+            .to_vec()
+    }
+
+    pub fn hash(&self) -> Vec<u8> {
+        Self::hash_of(&self.0)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+pub fn random_token() -> Option<String> {
+    let mut bytes = [0u8; 32];
+
+    SystemRandom::new().fill(&mut bytes).ok()?;
+
+    Some(URL_SAFE_NO_PAD.encode(bytes))
+}
@@ -0,0 +1,40 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable};
+use serde::Serialize;
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::retention_runs)]
+pub struct RetentionRunInsert {
+    pub started_time: NaiveDateTime,
+    pub finished_time: NaiveDateTime,
+    pub compacted: i32,
+    pub archived: i32,
+    pub purged: i32,
+    pub failed: i32,
+    pub bytes_reclaimed: i64,
+}
+
+#[derive(Debug, Queryable, Serialize)]
+pub struct RetentionRunSelect {
+    pub id: uuid::Uuid,
+    #[serde(rename = "started-time")]
+    pub started_time: NaiveDateTime,
+    #[serde(rename = "finished-time")]
+    pub finished_time: NaiveDateTime,
+    pub compacted: i32,
+    pub archived: i32,
+    pub purged: i32,
+    pub failed: i32,
+    #[serde(rename = "bytes-reclaimed")]
+    pub bytes_reclaimed: i64,
+}
+
+impl std::fmt::Display for RetentionRunInsert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Retention run: {} compacted, {} archived, {} purged, {} failed, {} bytes reclaimed",
+            self.compacted, self.archived, self.purged, self.failed, self.bytes_reclaimed
+        )
+    }
+}
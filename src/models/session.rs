@@ -1,4 +1,5 @@
-use diesel::Insertable;
+use diesel::{Insertable, Queryable};
+use serde::Serialize;
 use super::DeviceOs;
 
 use chrono::NaiveDateTime;
@@ -12,14 +13,16 @@ pub struct SessionInsert {
     logout_time: Option<NaiveDateTime>,
     device_os: DeviceOs,
     device_name: String,
-    device_hash: Vec<u8>
+    device_hash: Vec<u8>,
+    device_id: Option<uuid::Uuid>,
 }
 
 impl SessionInsert {
     pub fn create(user_id: &uuid::Uuid,
                     device_os: &DeviceOs,
                     device_name: &str,
-                    device_hash: &[u8]) -> Self {
+                    device_hash: &[u8],
+                    device_id: &uuid::Uuid) -> Self {
         let now = chrono::Utc::now().naive_utc();
 
         Self {
@@ -30,6 +33,20 @@ impl SessionInsert {
             device_os: device_os.to_owned(),
             device_name: device_name.to_owned(),
             device_hash: device_hash.to_vec(),
+            device_id: Some(device_id.to_owned()),
         }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Queryable, Serialize)]
+pub struct SessionSelect {
+    pub id: uuid::Uuid,
+    #[serde(rename = "device-os")]
+    pub device_os: DeviceOs,
+    #[serde(rename = "device-name")]
+    pub device_name: String,
+    #[serde(rename = "created-time")]
+    pub created_time: NaiveDateTime,
+    #[serde(rename = "last-login")]
+    pub last_login: NaiveDateTime,
+}
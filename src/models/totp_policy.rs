@@ -0,0 +1,12 @@
+use diesel::Queryable;
+use serde::{Deserialize, Serialize};
+
+use super::UserRole;
+
+#[derive(Debug, Queryable, Serialize, Deserialize)]
+pub struct TotpPolicy {
+    #[serde(rename = "assigned-role", alias = "assigned_role")]
+    #[serde(deserialize_with = "super::numbered_role")]
+    pub assigned_role: UserRole,
+    pub required: bool,
+}
@@ -32,28 +32,39 @@ pub struct UserSelect {
     pub deactivated: bool,
     pub assigned_role: UserRole,
     pub assigned_area: Option<String>,
-    pub avatar: Option<Vec<u8>>
+    pub avatar_key: Option<String>,
+    pub totp_secret: Option<Vec<u8>>,
+    pub totp_enabled: bool,
+    pub totp_last_step: Option<i64>,
 }
 
 impl UserSelect {
     pub fn select_by_username(
         connection: &mut PgConnection,
         username: &str,
-    ) -> Result<Self, ResponseError> {
+    ) -> Result<Option<Self>, ResponseError> {
         use crate::schema::users::dsl;
 
         match dsl::users
             .filter(dsl::username.eq(username))
+            .select((
+                dsl::id,
+                dsl::username,
+                dsl::first_name,
+                dsl::last_name,
+                dsl::password_hash,
+                dsl::deactivated,
+                dsl::assigned_role,
+                dsl::assigned_area,
+                dsl::avatar_key,
+                dsl::totp_secret,
+                dsl::totp_enabled,
+                dsl::totp_last_step,
+            ))
             .first::<Self>(connection)
             .optional()
         {
-            Ok(Some(user)) => Ok(user),
-            Ok(None) => Err(ResponseError::new(
-                "A user entered incorrect username",
-                "Invalid username or password",
-                LogLevel::Error,
-                StatusCode::INTERNAL_SERVER_ERROR,
-            )),
+            Ok(user) => Ok(user),
             Err(err) => Err(ResponseError::new(
                 err.to_string().as_str(),
                 "Failed to retrieved data",
@@ -64,6 +75,23 @@ impl UserSelect {
     }
 }
 
+#[derive(Debug, Queryable, Serialize)]
+pub struct UserListEntry {
+    pub id: uuid::Uuid,
+    pub username: String,
+    #[serde(rename = "first-name")]
+    pub first_name: String,
+    #[serde(rename = "last-name")]
+    pub last_name: String,
+    #[serde(rename = "assigned-role")]
+    pub assigned_role: UserRole,
+    #[serde(rename = "assigned-area")]
+    pub assigned_area: Option<String>,
+    pub deactivated: bool,
+    #[serde(rename = "totp-enabled")]
+    pub totp_enabled: bool,
+}
+
 #[derive(Debug, Serialize)]
 pub struct UserBasicSelect {
     pub id: uuid::Uuid,
@@ -2,15 +2,22 @@ use std::future::Future;
 use std::pin::Pin;
 
 use actix_web::dev::Payload;
-use actix_web::http::StatusCode;
+use actix_web::http::{header, Method, StatusCode};
 use actix_web::web::Data;
 use actix_web::HttpRequest;
 
-use diesel::{AsChangeset, ExpressionMethods, QueryDsl, RunQueryDsl};
+use chrono::NaiveDateTime;
+use diesel::{AsChangeset, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
 
 use crate::data::AppData;
 use crate::logging::{LogLevel, ResponseError};
-use crate::models::UserRole;
+use crate::models::{Access, DeviceSignature, Permission, UserRole};
+
+pub(crate) const JWT_COOKIE: &str = "jwt";
+pub(crate) const CSRF_COOKIE: &str = "csrf-token";
+pub(crate) const CSRF_HEADER: &str = "X-CSRF-Token";
+pub(crate) const DEVICE_COOKIE: &str = "device-signature";
+pub(crate) const DEVICE_HEADER: &str = "X-Device-Signature";
 
 #[derive(Debug, Copy, Clone)]
 pub struct UserClaims {
@@ -28,16 +35,31 @@ impl actix_web::FromRequest for UserClaims {
         let req = req.clone();
 
         Box::pin(async move {
-            let token = req
-                .cookie("jwt")
+            // routes are closed unless the policy table says who may call them
+            let access = req
+                .match_pattern()
+                .and_then(|pattern| Access::of(req.method().as_str(), &pattern))
                 .ok_or(ResponseError::new(
-                    "JSON Web token not found",
-                    "Invalid Session",
-                    LogLevel::Information,
-                    StatusCode::UNAUTHORIZED,
-                ))?
-                .value()
-                .to_owned();
+                    format!("No access policy for {} {}", req.method(), req.path()),
+                    "Internal Server Error",
+                    LogLevel::Error,
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                ))?;
+
+            let token = match Self::bearer_token(&req) {
+                Some(token) => token,
+                None => {
+                    let cookie = req.cookie(JWT_COOKIE).ok_or(ResponseError::new(
+                        "JSON Web token not found",
+                        "Invalid Session",
+                        LogLevel::Information,
+                        StatusCode::UNAUTHORIZED,
+                    ))?;
+
+                    Self::verify_csrf(&req)?;
+                    cookie.value().to_owned()
+                }
+            };
 
             let state = req.app_data::<Data<AppData>>().unwrap();
             let jwtc = state.jwt_decode(&token)?;
@@ -47,11 +69,26 @@ impl actix_web::FromRequest for UserClaims {
 
             let mut database = state.connect_database();
 
-            let (user_id, assigned_role): (uuid::Uuid, UserRole) = users::table
+            let (user_id, assigned_role, last_login, totp_enabled, device_hash, password_change): (
+                uuid::Uuid,
+                UserRole,
+                NaiveDateTime,
+                bool,
+                Vec<u8>,
+                bool,
+            ) = users::table
                 .inner_join(sessions::table)
                 .filter(users::deactivated.eq(false))
                 .filter(sessions::id.eq(jwtc.session_id))
-                .select((users::id, users::assigned_role))
+                .filter(sessions::logout_time.is_null())
+                .select((
+                    users::id,
+                    users::assigned_role,
+                    sessions::last_login,
+                    users::totp_enabled,
+                    sessions::device_hash,
+                    users::password_change_required,
+                ))
                 .get_result(&mut database)
                 .or(Err(ResponseError::new(
                     "User do not found",
@@ -60,11 +97,146 @@ impl actix_web::FromRequest for UserClaims {
                     StatusCode::UNAUTHORIZED,
                 )))?;
 
-            Ok(Self {
+            // a device that logs in again reuses its session, tokens from before that are stale
+            if jwtc.iat < last_login.timestamp() {
+                return Err(ResponseError::new(
+                    "Token issued before the session was renewed",
+                    "Invalid Session",
+                    LogLevel::Information,
+                    StatusCode::UNAUTHORIZED,
+                ));
+            }
+
+            // a token only works from the device it was issued to
+            let signature = Self::device_signature(&req)?;
+            let request_hash = state.xxh3_128bits(signature.into()).await.to_ne_bytes();
+
+            if ring::constant_time::verify_slices_are_equal(&request_hash, &device_hash).is_err() {
+                return Err(ResponseError::new(
+                    format!("Token of session {} used from another device", jwtc.session_id),
+                    "Invalid Session",
+                    LogLevel::Warning,
+                    StatusCode::UNAUTHORIZED,
+                ));
+            }
+
+            let claims = Self {
                 session_id: jwtc.session_id,
                 user_id,
                 assigned_role,
-            })
+            };
+
+            if let Access::Requires(permission) = access {
+                if !claims.can(permission) {
+                    return Err(ResponseError::unauthorized(claims));
+                }
+            }
+
+            // a reset password only opens the route that replaces it
+            if password_change && !Self::allowed_before_password_change(req.path()) {
+                return Err(ResponseError::new(
+                    format!("User {user_id} has to change the reset password"),
+                    "Password must be changed first",
+                    LogLevel::Information,
+                    StatusCode::FORBIDDEN,
+                ));
+            }
+
+            // until enrolment is done, only the routes needed to enrol are open
+            if !totp_enabled && !Self::allowed_without_totp(req.path()) {
+                use crate::schema::totp_policies;
+
+                let required = totp_policies::table
+                    .filter(totp_policies::assigned_role.eq(assigned_role))
+                    .select(totp_policies::required)
+                    .first::<bool>(&mut database)
+                    .optional()
+                    .or(Err(ResponseError::server_error()))?
+                    .unwrap_or(false);
+
+                if required {
+                    return Err(ResponseError::new(
+                        format!("User {user_id} has not set up two-factor authentication"),
+                        "Two-factor authentication must be set up first",
+                        LogLevel::Information,
+                        StatusCode::FORBIDDEN,
+                    ));
+                }
+            }
+
+            Ok(claims)
         })
     }
 }
+
+impl UserClaims {
+    pub fn can(&self, permission: Permission) -> bool {
+        permission.allows(self.assigned_role)
+    }
+
+    fn allowed_before_password_change(path: &str) -> bool {
+        path == "/users/password" || path == "/users/logout" || path == "/users/current"
+    }
+
+    fn allowed_without_totp(path: &str) -> bool {
+        path.starts_with("/users/totp") || path == "/users/logout" || path == "/users/current"
+    }
+
+    // Browsers cannot add headers to image requests, so cookie sessions fall back to a cookie
+    pub(crate) fn device_signature(req: &HttpRequest) -> Result<DeviceSignature, ResponseError> {
+        req.headers()
+            .get(DEVICE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+            .or_else(|| req.cookie(DEVICE_COOKIE).map(|cookie| cookie.value().to_owned()))
+            .and_then(|signature| signature.parse().ok())
+            .ok_or(ResponseError::new(
+                "Device signature missing or malformed",
+                "Invalid Session",
+                LogLevel::Information,
+                StatusCode::UNAUTHORIZED,
+            ))
+    }
+
+    fn bearer_token(req: &HttpRequest) -> Option<String> {
+        let value = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+        let (scheme, token) = value.split_once(' ')?;
+
+        scheme
+            .eq_ignore_ascii_case("bearer")
+            .then(|| token.trim().to_owned())
+    }
+
+    // Browsers attach cookies to cross site requests too, so requests that change
+    // something must echo the csrf cookie in a header that other sites cannot set
+    pub(crate) fn verify_csrf(req: &HttpRequest) -> Result<(), ResponseError> {
+        if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+            return Ok(());
+        }
+
+        let cookie = req.cookie(CSRF_COOKIE);
+        let header = req
+            .headers()
+            .get(CSRF_HEADER)
+            .and_then(|value| value.to_str().ok());
+
+        match (cookie, header) {
+            (Some(cookie), Some(header))
+                if !header.is_empty()
+                    && ring::constant_time::verify_slices_are_equal(
+                        cookie.value().as_bytes(),
+                        header.as_bytes(),
+                    )
+                    .is_ok() =>
+            {
+                Ok(())
+            }
+            _ => Err(ResponseError::new(
+                "CSRF token missing or mismatched",
+                "Invalid Session",
+                LogLevel::Warning,
+                StatusCode::FORBIDDEN,
+            )),
+        }
+    }
+}
@@ -13,6 +13,39 @@ pub enum UserRole {
     SystemAdmin = 3,
 }
 
+impl UserRole {
+    // A system administrator manages everyone, other roles only the roles below them
+    pub fn can_manage(self, other: UserRole) -> bool {
+        self == UserRole::SystemAdmin || self as i16 > other as i16
+    }
+}
+
+// Requests that send back a role the way responses show it, 1 to 3, unlike the 0 to 2 of signup
+pub fn numbered_role<'de, D>(deserializer: D) -> Result<UserRole, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    role_of_number(u32::deserialize(deserializer)?)
+}
+
+pub fn numbered_role_option<'de, D>(deserializer: D) -> Result<Option<UserRole>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<u32>::deserialize(deserializer)?
+        .map(role_of_number)
+        .transpose()
+}
+
+fn role_of_number<E: serde::de::Error>(value: u32) -> Result<UserRole, E> {
+    match value {
+        1 => Ok(UserRole::SecurityGuard),
+        2 => Ok(UserRole::SecurityHead),
+        3 => Ok(UserRole::SystemAdmin),
+        _ => Err(E::custom(format!("invalid value for User role: {value}"))),
+    }
+}
+
 impl Serialize for UserRole {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -2,7 +2,7 @@ use chrono::NaiveDateTime;
 use diesel::{
     deserialize::FromSqlRow,
     sql_types::{Nullable, SmallInt, Text, Timestamp},
-    Insertable,
+    Insertable, Queryable,
 };
 use serde::Serialize;
 
@@ -12,7 +12,7 @@ pub struct ViolationUnknownInsert {
     pub area_code: String,
     pub violation_kind: super::ViolationKind,
     pub date_time: chrono::NaiveDateTime,
-    pub image_bytes: Vec<u8>,
+    pub image_key: String,
     pub identified: bool,
 }
 
@@ -64,6 +64,8 @@ pub struct IdentifiedViolation {
     date_time: NaiveDateTime,
     #[serde(rename = "personnel-id")]
     personnel_id: uuid::Uuid,
+    #[serde(rename = "person-id")]
+    person_id: Option<uuid::Uuid>,
     #[serde(rename = "first-name")]
     first_name: String,
     #[serde(rename = "last-name")]
@@ -79,6 +81,7 @@ impl
             SmallInt,
             Timestamp,
             Nullable<diesel::sql_types::Uuid>,
+            Nullable<diesel::sql_types::Uuid>,
             Nullable<Text>,
             Nullable<Text>,
             Nullable<SmallInt>,
@@ -100,6 +103,10 @@ impl
                     "personnel_id",
                 )?
                 .unwrap(),
+            person_id: row
+                .get_value::<Nullable<diesel::sql_types::Uuid>, Option<uuid::Uuid>, &str>(
+                    "person_id",
+                )?,
             first_name: row
                 .get_value::<Nullable<Text>, Option<String>, &str>("first_name")?
                 .unwrap(),
@@ -112,3 +119,15 @@ impl
         })
     }
 }
+
+#[derive(Debug, Queryable)]
+pub struct ViolationReportRow {
+    pub id: uuid::Uuid,
+    pub area_code: String,
+    pub violation_kind: super::ViolationKind,
+    pub date_time: NaiveDateTime,
+    pub identified: bool,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub category: Option<super::Category>,
+}
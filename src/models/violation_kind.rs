@@ -72,3 +72,12 @@ where
         }
     }
 }
+
+impl std::fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ViolationKind::FacemaskProtocol => "Facemask Protocol",
+            ViolationKind::FootTraffic => "Foot Traffic",
+        })
+    }
+}
@@ -8,7 +8,7 @@ use actix_web::{dev::Payload, web, FromRequest, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
 use serde::ser::SerializeMap;
 
-use crate::models::UserClaims;
+use crate::models::{EscalationSelect, MissedShift, Permission, UserClaims, UserRole};
 
 #[derive(Default)]
 pub struct Notifier {
@@ -27,6 +27,8 @@ impl Notifier {
             claims,
             listen_notification: true,
             listen_activation: true,
+            listen_escalation: claims.assigned_role != UserRole::SecurityGuard,
+            listen_missed_shift: claims.can(Permission::AttendanceRead),
         };
 
         let (addr, response) =
@@ -41,12 +43,53 @@ impl Notifier {
             client.addr.do_send(notification.clone());
         }
     }
+
+    pub(crate) fn notify_permitted(&self, permission: Permission, notification: Notification) {
+        for client in self.clients.values().filter(|client| client.claims.can(permission)) {
+            client.addr.do_send(notification.clone());
+        }
+    }
+
+    // Users who read violations only of the areas they are posted to
+    pub(crate) fn scoped_users(&self) -> Vec<uuid::Uuid> {
+        self.clients
+            .values()
+            .filter(|client| !client.claims.can(Permission::ViolationsReadAllAreas))
+            .map(|client| client.claims.user_id)
+            .collect()
+    }
+
+    // Violations reach those who read every area, and guards posted to the area right now
+    pub(crate) fn notify_area(
+        &self,
+        area_code: &str,
+        areas: &HashMap<uuid::Uuid, Vec<String>>,
+        notification: Notification,
+    ) {
+        for client in self.clients.values() {
+            let claims = client.claims;
+
+            let posted = areas
+                .get(&claims.user_id)
+                .into_iter()
+                .flatten()
+                .any(|area| area == area_code);
+
+            if claims.can(Permission::ViolationsReadAllAreas)
+                || (claims.can(Permission::ViolationsRead) && posted)
+            {
+                client.addr.do_send(notification.clone());
+            }
+        }
+    }
 }
 
 pub(crate) struct Actor {
     pub(super) claims: UserClaims,
     pub(super) listen_notification: bool,
     pub(super) listen_activation: bool,
+    pub(super) listen_escalation: bool,
+    pub(super) listen_missed_shift: bool,
 }
 
 pub(crate) enum Response {
@@ -66,6 +109,8 @@ pub(crate) struct ClientRequest {
 pub(crate) enum Notification {
     NewViolations(Vec<uuid::Uuid>),
     NewActivation(Vec<ActiveEntry>),
+    NewEscalations(Vec<EscalationSelect>),
+    MissedShifts(Vec<MissedShift>),
 }
 
 #[derive(Clone, serde::Serialize)]
@@ -97,6 +142,14 @@ impl actix::StreamHandler<Result<ws::Message, ws::ProtocolError>> for Actor {
                         2 => {
                             self.listen_activation = request.listen;
                         }
+                        3 => {
+                            self.listen_escalation = request.listen
+                                && self.claims.assigned_role != UserRole::SecurityGuard;
+                        }
+                        4 => {
+                            self.listen_missed_shift =
+                                request.listen && self.claims.can(Permission::AttendanceRead);
+                        }
                         _ => (),
                     }
                 }
@@ -122,6 +175,16 @@ impl actix::Handler<Notification> for Actor {
                     return Response::NotificationSkipped;
                 }
             }
+            Notification::NewEscalations(_) => {
+                if !self.listen_escalation {
+                    return Response::NotificationSkipped;
+                }
+            }
+            Notification::MissedShifts(_) => {
+                if !self.listen_missed_shift {
+                    return Response::NotificationSkipped;
+                }
+            }
         }
 
         if let Ok(bytes) = serde_cbor::to_vec(&notification) {
@@ -153,6 +216,22 @@ impl serde::ser::Serialize for Notification {
                 map.serialize_entry("event", &2u8)?;
                 map.serialize_entry("activities", activities)?;
 
+                map.end()
+            }
+            Notification::NewEscalations(escalations) => {
+                let mut map = serializer.serialize_map(Some(2))?;
+
+                map.serialize_entry("event", &3u8)?;
+                map.serialize_entry("escalations", escalations)?;
+
+                map.end()
+            }
+            Notification::MissedShifts(shifts) => {
+                let mut map = serializer.serialize_map(Some(2))?;
+
+                map.serialize_entry("event", &4u8)?;
+                map.serialize_entry("shifts", shifts)?;
+
                 map.end()
             }
         }
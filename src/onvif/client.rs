@@ -0,0 +1,368 @@
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+use ring::rand::{SecureRandom, SystemRandom};
+use roxmltree::{Document, Node};
+use serde::Serialize;
+
+use super::{escape, OnvifError, OnvifResult};
+
+const DEVICE_NAMESPACE: &str = "http://www.onvif.org/ver10/device/wsdl";
+const MEDIA_NAMESPACE: &str = "http://www.onvif.org/ver10/media/wsdl";
+const PTZ_NAMESPACE: &str = "http://www.onvif.org/ver20/ptz/wsdl";
+
+// Where the device offers media and PTZ, they are not always on the device url
+#[derive(Debug, Clone, Serialize)]
+pub struct Services {
+    pub media: Option<String>,
+    pub ptz: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MediaProfile {
+    pub token: String,
+    pub name: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub ptz: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PtzPreset {
+    pub token: String,
+    pub name: Option<String>,
+}
+
+pub struct OnvifClient {
+    agent: ureq::Agent,
+    device_url: String,
+    credentials: Option<(String, String)>,
+}
+
+fn find<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.descendants().find(|child| child.has_tag_name(name))
+}
+
+fn find_text(node: Node, name: &str) -> Option<String> {
+    find(node, name)
+        .and_then(|child| child.text())
+        .map(|text| text.trim().to_owned())
+}
+
+// Host of the url without userinfo and port, lowercased so that it can be compared
+fn host(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let authority = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    let host = match authority.strip_prefix('[') {
+        Some(address) => address.split_once(']')?.0,
+        None => authority.split(':').next()?,
+    };
+
+    Some(host.to_ascii_lowercase()).filter(|host| !host.is_empty())
+}
+
+fn parse(xml: &str) -> OnvifResult<Document<'_>> {
+    Document::parse(xml).map_err(|error| OnvifError::Response(error.to_string()))
+}
+
+fn fault(xml: &str) -> Option<OnvifError> {
+    let document = Document::parse(xml).ok()?;
+    let fault = find(document.root(), "Fault")?;
+
+    if fault
+        .descendants()
+        .any(|node| node.text().is_some_and(|text| text.trim().ends_with("NotAuthorized")))
+    {
+        return Some(OnvifError::Unauthorized);
+    }
+
+    Some(OnvifError::Fault(
+        find_text(fault, "Text")
+            .or_else(|| find_text(fault, "faultstring"))
+            .unwrap_or_else(|| "unknown".into()),
+    ))
+}
+
+impl OnvifClient {
+    // Credentials are the username and password, without any url encoding
+    pub fn new(device_url: &str, credentials: Option<(String, String)>, timeout: Duration) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(timeout).build(),
+            device_url: device_url.to_owned(),
+            credentials,
+        }
+    }
+
+    // WS-Security UsernameToken with a password digest, what ONVIF asks of every client
+    fn security_header(&self) -> OnvifResult<String> {
+        let (username, password) = match &self.credentials {
+            Some(credentials) => credentials,
+            None => return Ok(String::new()),
+        };
+
+        let mut nonce = [0u8; 16];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| OnvifError::Transport("no randomness for the nonce".into()))?;
+
+        let created = Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+        let password_digest = digest(
+            &SHA1_FOR_LEGACY_USE_ONLY,
+            &[&nonce[..], created.as_bytes(), password.as_bytes()].concat(),
+        );
+
+        Ok(format!(
+            concat!(
+                "<s:Header>",
+                r#"<wsse:Security s:mustUnderstand="1""#,
+                r#" xmlns:wsse="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-secext-1.0.xsd""#,
+                r#" xmlns:wsu="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-utility-1.0.xsd">"#,
+                "<wsse:UsernameToken>",
+                "<wsse:Username>{}</wsse:Username>",
+                r#"<wsse:Password Type="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-username-token-profile-1.0#PasswordDigest">{}</wsse:Password>"#,
+                r#"<wsse:Nonce EncodingType="http://docs.oasis-open.org/wss/2004/01/oasis-200401-soap-message-security-1.0#Base64Binary">{}</wsse:Nonce>"#,
+                "<wsu:Created>{}</wsu:Created>",
+                "</wsse:UsernameToken>",
+                "</wsse:Security>",
+                "</s:Header>",
+            ),
+            escape(username),
+            STANDARD.encode(password_digest.as_ref()),
+            STANDARD.encode(nonce),
+            created,
+        ))
+    }
+
+    // Posts the body in an envelope and gives back the answer, a fault is turned into an error
+    fn call(&self, url: &str, body: &str) -> OnvifResult<String> {
+        let envelope = format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                r#"<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope""#,
+                r#" xmlns:tds="{}" xmlns:trt="{}" xmlns:tptz="{}" xmlns:tt="http://www.onvif.org/ver10/schema">"#,
+                "{}<s:Body>{}</s:Body></s:Envelope>",
+            ),
+            DEVICE_NAMESPACE,
+            MEDIA_NAMESPACE,
+            PTZ_NAMESPACE,
+            self.security_header()?,
+            body,
+        );
+
+        match self
+            .agent
+            .post(url)
+            .set("Content-Type", "application/soap+xml; charset=utf-8")
+            .send_string(&envelope)
+        {
+            Ok(response) => response
+                .into_string()
+                .map_err(|error| OnvifError::Transport(error.to_string())),
+            Err(ureq::Error::Status(401, _)) => Err(OnvifError::Unauthorized),
+            Err(ureq::Error::Status(status, response)) => {
+                let answer = response.into_string().unwrap_or_default();
+                Err(fault(&answer).unwrap_or_else(|| OnvifError::Response(format!("status {status}"))))
+            }
+            Err(error) => Err(OnvifError::Transport(error.to_string())),
+        }
+    }
+
+    pub fn services(&self) -> OnvifResult<Services> {
+        let answer = self.call(
+            &self.device_url,
+            "<tds:GetCapabilities><tds:Category>All</tds:Category></tds:GetCapabilities>",
+        )?;
+        let document = parse(&answer)?;
+        let capabilities = find(document.root(), "Capabilities")
+            .ok_or_else(|| OnvifError::Response("no capabilities".into()))?;
+
+        // the device picks these urls, one pointing elsewhere would have the server call it instead
+        let device_host = host(&self.device_url);
+        let service = |name: &str| {
+            let url = capabilities
+                .children()
+                .find(|node| node.has_tag_name(name))
+                .and_then(|node| find_text(node, "XAddr"));
+
+            match url {
+                Some(url) if device_host.is_none() || host(&url) != device_host => Err(OnvifError::Response(
+                    format!("{name} service is not on the device host"),
+                )),
+                url => Ok(url),
+            }
+        };
+
+        Ok(Services {
+            media: service("Media")?,
+            ptz: service("PTZ")?,
+        })
+    }
+
+    pub fn profiles(&self, media_url: &str) -> OnvifResult<Vec<MediaProfile>> {
+        let answer = self.call(media_url, "<trt:GetProfiles/>")?;
+        let document = parse(&answer)?;
+
+        Ok(document
+            .descendants()
+            .filter(|node| node.has_tag_name("Profiles"))
+            .filter_map(|node| {
+                let resolution = find(node, "VideoEncoderConfiguration").and_then(|node| find(node, "Resolution"));
+                let dimension = |name: &str| {
+                    resolution
+                        .and_then(|resolution| find_text(resolution, name))
+                        .and_then(|value| value.parse().ok())
+                };
+
+                Some(MediaProfile {
+                    token: node.attribute("token")?.to_owned(),
+                    name: node
+                        .children()
+                        .find(|child| child.has_tag_name("Name"))
+                        .and_then(|child| child.text())
+                        .map(String::from),
+                    width: dimension("Width"),
+                    height: dimension("Height"),
+                    ptz: find(node, "PTZConfiguration").is_some(),
+                })
+            })
+            .collect())
+    }
+
+    pub fn stream_url(&self, media_url: &str, profile: &str) -> OnvifResult<String> {
+        let answer = self.call(
+            media_url,
+            &format!(
+                concat!(
+                    "<trt:GetStreamUri><trt:StreamSetup>",
+                    "<tt:Stream>RTP-Unicast</tt:Stream>",
+                    "<tt:Transport><tt:Protocol>RTSP</tt:Protocol></tt:Transport>",
+                    "</trt:StreamSetup><trt:ProfileToken>{}</trt:ProfileToken></trt:GetStreamUri>",
+                ),
+                escape(profile)
+            ),
+        )?;
+        let document = parse(&answer)?;
+
+        find(document.root(), "MediaUri")
+            .and_then(|node| find_text(node, "Uri"))
+            .ok_or_else(|| OnvifError::Response("no stream uri".into()))
+    }
+
+    // Velocities go from -1 to 1, the camera keeps moving until stopped or the timeout runs out
+    pub fn continuous_move(
+        &self,
+        ptz_url: &str,
+        profile: &str,
+        (pan, tilt, zoom): (f32, f32, f32),
+        timeout: Option<Duration>,
+    ) -> OnvifResult<()> {
+        let timeout = timeout
+            .map(|timeout| format!("<tptz:Timeout>PT{}S</tptz:Timeout>", timeout.as_secs_f32()))
+            .unwrap_or_default();
+
+        self.call(
+            ptz_url,
+            &format!(
+                concat!(
+                    "<tptz:ContinuousMove><tptz:ProfileToken>{}</tptz:ProfileToken>",
+                    r#"<tptz:Velocity><tt:PanTilt x="{}" y="{}"/><tt:Zoom x="{}"/></tptz:Velocity>"#,
+                    "{}</tptz:ContinuousMove>",
+                ),
+                escape(profile),
+                pan,
+                tilt,
+                zoom,
+                timeout
+            ),
+        )
+        .map(|_| ())
+    }
+
+    pub fn stop(&self, ptz_url: &str, profile: &str) -> OnvifResult<()> {
+        self.call(
+            ptz_url,
+            &format!(
+                concat!(
+                    "<tptz:Stop><tptz:ProfileToken>{}</tptz:ProfileToken>",
+                    "<tptz:PanTilt>true</tptz:PanTilt><tptz:Zoom>true</tptz:Zoom></tptz:Stop>",
+                ),
+                escape(profile)
+            ),
+        )
+        .map(|_| ())
+    }
+
+    pub fn presets(&self, ptz_url: &str, profile: &str) -> OnvifResult<Vec<PtzPreset>> {
+        let answer = self.call(
+            ptz_url,
+            &format!(
+                "<tptz:GetPresets><tptz:ProfileToken>{}</tptz:ProfileToken></tptz:GetPresets>",
+                escape(profile)
+            ),
+        )?;
+        let document = parse(&answer)?;
+
+        Ok(document
+            .descendants()
+            .filter(|node| node.has_tag_name("Preset"))
+            .filter_map(|node| {
+                Some(PtzPreset {
+                    token: node.attribute("token")?.to_owned(),
+                    name: find_text(node, "Name"),
+                })
+            })
+            .collect())
+    }
+
+    // Saves where the camera points now, the device picks the token
+    pub fn set_preset(&self, ptz_url: &str, profile: &str, name: &str) -> OnvifResult<String> {
+        let answer = self.call(
+            ptz_url,
+            &format!(
+                concat!(
+                    "<tptz:SetPreset><tptz:ProfileToken>{}</tptz:ProfileToken>",
+                    "<tptz:PresetName>{}</tptz:PresetName></tptz:SetPreset>",
+                ),
+                escape(profile),
+                escape(name)
+            ),
+        )?;
+        let document = parse(&answer)?;
+
+        find_text(document.root(), "PresetToken").ok_or_else(|| OnvifError::Response("no preset token".into()))
+    }
+
+    pub fn goto_preset(&self, ptz_url: &str, profile: &str, preset: &str) -> OnvifResult<()> {
+        self.call(
+            ptz_url,
+            &format!(
+                concat!(
+                    "<tptz:GotoPreset><tptz:ProfileToken>{}</tptz:ProfileToken>",
+                    "<tptz:PresetToken>{}</tptz:PresetToken></tptz:GotoPreset>",
+                ),
+                escape(profile),
+                escape(preset)
+            ),
+        )
+        .map(|_| ())
+    }
+
+    pub fn remove_preset(&self, ptz_url: &str, profile: &str, preset: &str) -> OnvifResult<()> {
+        self.call(
+            ptz_url,
+            &format!(
+                concat!(
+                    "<tptz:RemovePreset><tptz:ProfileToken>{}</tptz:ProfileToken>",
+                    "<tptz:PresetToken>{}</tptz:PresetToken></tptz:RemovePreset>",
+                ),
+                escape(profile),
+                escape(preset)
+            ),
+        )
+        .map(|_| ())
+    }
+}
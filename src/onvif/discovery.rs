@@ -0,0 +1,121 @@
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use super::percent_decode;
+
+// WS-Discovery group every ONVIF device listens on
+pub const MULTICAST_ADDRESS: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(239, 255, 255, 250), 3702);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveredDevice {
+    pub endpoint: String,
+    #[serde(rename = "device-urls")]
+    pub device_urls: Vec<String>,
+    pub name: Option<String>,
+    pub hardware: Option<String>,
+    pub scopes: Vec<String>,
+}
+
+fn probe_message(message_id: uuid::Uuid) -> String {
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            r#"<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope""#,
+            r#" xmlns:a="http://schemas.xmlsoap.org/ws/2004/08/addressing""#,
+            r#" xmlns:d="http://schemas.xmlsoap.org/ws/2005/04/discovery""#,
+            r#" xmlns:dn="http://www.onvif.org/ver10/network/wsdl">"#,
+            "<s:Header>",
+            "<a:MessageID>uuid:{}</a:MessageID>",
+            "<a:To>urn:schemas-xmlsoap-org:ws:2005:04:discovery</a:To>",
+            "<a:Action>http://schemas.xmlsoap.org/ws/2005/04/discovery/Probe</a:Action>",
+            "</s:Header>",
+            "<s:Body><d:Probe><d:Types>dn:NetworkVideoTransmitter</d:Types></d:Probe></s:Body>",
+            "</s:Envelope>",
+        ),
+        message_id
+    )
+}
+
+// Sends one probe to the target, the multicast group or a single device, and collects
+// the answers until the timeout. A device answering more than once is listed once
+pub fn probe(target: SocketAddr, timeout: Duration) -> io::Result<Vec<DiscoveredDevice>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_multicast_ttl_v4(2)?;
+    socket.send_to(probe_message(uuid::Uuid::new_v4()).as_bytes(), target)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut buffer = vec![0u8; 65535];
+    let mut devices: Vec<DiscoveredDevice> = Vec::new();
+
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+
+        if left.is_zero() {
+            break;
+        }
+
+        socket.set_read_timeout(Some(left))?;
+
+        let length = match socket.recv_from(&mut buffer) {
+            Ok((length, _)) => length,
+            Err(error) if matches!(error.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                break
+            }
+            Err(error) => return Err(error),
+        };
+
+        for device in parse_probe_matches(&String::from_utf8_lossy(&buffer[..length])) {
+            if !devices.iter().any(|known| known.endpoint == device.endpoint) {
+                devices.push(device);
+            }
+        }
+    }
+
+    Ok(devices)
+}
+
+// Anything that is not a well formed ProbeMatches message gives nothing
+fn parse_probe_matches(message: &str) -> Vec<DiscoveredDevice> {
+    let document = match roxmltree::Document::parse(message) {
+        Ok(document) => document,
+        Err(_) => return Vec::new(),
+    };
+
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name("ProbeMatch"))
+        .filter_map(|node| {
+            let child_text = |name: &str| {
+                node.descendants()
+                    .find(|child| child.has_tag_name(name))
+                    .and_then(|child| child.text())
+                    .unwrap_or_default()
+            };
+
+            let endpoint = child_text("Address").trim().to_owned();
+            let device_urls: Vec<String> = child_text("XAddrs").split_whitespace().map(String::from).collect();
+            let scopes: Vec<String> = child_text("Scopes").split_whitespace().map(String::from).collect();
+
+            let scope = |prefix: &str| {
+                scopes
+                    .iter()
+                    .find_map(|scope| scope.strip_prefix(prefix))
+                    .map(percent_decode)
+            };
+
+            let name = scope("onvif://www.onvif.org/name/");
+            let hardware = scope("onvif://www.onvif.org/hardware/");
+
+            (!device_urls.is_empty()).then_some(DiscoveredDevice {
+                endpoint,
+                device_urls,
+                name,
+                hardware,
+                scopes,
+            })
+        })
+        .collect()
+}
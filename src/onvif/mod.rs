@@ -0,0 +1,91 @@
+mod client;
+mod discovery;
+
+pub use client::OnvifClient;
+pub use discovery::{probe, MULTICAST_ADDRESS};
+
+#[derive(Debug)]
+pub enum OnvifError {
+    // the device could not be reached or did not answer in time
+    Transport(String),
+    // the device refused the credentials
+    Unauthorized,
+    // the device answered with a SOAP fault
+    Fault(String),
+    // the answer was not what was asked for
+    Response(String),
+    // the device does not offer the service, e.g. PTZ on a fixed camera
+    Unsupported(&'static str),
+}
+
+impl std::fmt::Display for OnvifError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OnvifError::Transport(message) => write!(f, "device unreachable: {message}"),
+            OnvifError::Unauthorized => write!(f, "device refused the credentials"),
+            OnvifError::Fault(message) => write!(f, "device fault: {message}"),
+            OnvifError::Response(message) => write!(f, "unexpected answer: {message}"),
+            OnvifError::Unsupported(service) => write!(f, "device has no {service} service"),
+        }
+    }
+}
+
+pub type OnvifResult<T> = Result<T, OnvifError>;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// Username and password out of the `user:password` in a url
+pub fn credentials(userinfo: &str) -> (String, String) {
+    match userinfo.split_once(':') {
+        Some((username, password)) => (percent_decode(username), percent_decode(password)),
+        None => (percent_decode(userinfo), String::new()),
+    }
+}
+
+pub fn userinfo(username: &str, password: &str) -> String {
+    format!("{}:{}", percent_encode(username), percent_encode(password))
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+// Scopes and url userinfo are percent-encoded, anything malformed is kept as it is
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escaped = match bytes.get(index + 1..index + 3) {
+            Some(hex) if bytes[index] == b'%' => std::str::from_utf8(hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
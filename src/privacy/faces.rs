@@ -0,0 +1,71 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+use image::RgbImage;
+use opencv::core::{Mat, Rect, Scalar, Size, Vector, CV_8UC1};
+use opencv::objdetect::CascadeClassifier;
+use opencv::prelude::*;
+
+use super::Region;
+
+thread_local! {
+    // loading the cascade takes longer than running it, so each blocking thread keeps its own
+    static CASCADE: RefCell<Option<(PathBuf, CascadeClassifier)>> = RefCell::new(None);
+}
+
+pub fn detect(image: &RgbImage, cascade: &Path) -> opencv::Result<Vec<Region>> {
+    let gray = image::imageops::grayscale(image);
+    let mut frame = Mat::new_rows_cols_with_default(
+        gray.height() as i32,
+        gray.width() as i32,
+        CV_8UC1,
+        Scalar::all(0.0),
+    )?;
+    frame.data_bytes_mut()?.copy_from_slice(gray.as_raw());
+
+    let mut equalized = Mat::default();
+    opencv::imgproc::equalize_hist(&frame, &mut equalized)?;
+
+    // faces under a tenth of the image are too small to recognise anyway
+    let smallest = (gray.width().min(gray.height()) / 10).max(12) as i32;
+
+    CASCADE.with(|loaded| {
+        let mut loaded = loaded.borrow_mut();
+
+        if !matches!(&*loaded, Some((path, _)) if path == cascade) {
+            let classifier = CascadeClassifier::new(&cascade.to_string_lossy())?;
+
+            if classifier.empty()? {
+                return Err(opencv::Error::new(
+                    opencv::core::StsError,
+                    format!("No face cascade at {}", cascade.display()),
+                ));
+            }
+
+            *loaded = Some((cascade.to_owned(), classifier));
+        }
+
+        let (_, classifier) = loaded.as_mut().expect("cascade loaded above");
+        let mut faces = Vector::<Rect>::new();
+
+        classifier.detect_multi_scale(
+            &equalized,
+            &mut faces,
+            1.1,
+            4,
+            0,
+            Size::new(smallest, smallest),
+            Size::default(),
+        )?;
+
+        Ok(faces
+            .iter()
+            .map(|face| Region {
+                x: face.x.max(0) as u32,
+                y: face.y.max(0) as u32,
+                width: face.width.max(0) as u32,
+                height: face.height.max(0) as u32,
+            })
+            .collect())
+    })
+}
@@ -0,0 +1,146 @@
+mod faces;
+
+use std::io::Cursor;
+
+use actix_web::http::header::{HeaderValue, CACHE_CONTROL};
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use diesel::{PgConnection, RunQueryDsl};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageOutputFormat, RgbImage};
+
+use crate::logging::{LogLevel, ResponseError};
+use crate::models::{AccessKind, AccessLogInsert, Permission, UserClaims};
+use crate::server_config::{PrivacyMode, PrivacyPolicy};
+
+// A blurred region is scaled down to this many blocks across and back up
+const BLUR_BLOCKS: u32 = 8;
+
+// Faces are padded by a quarter on every side, the cascade fits them tightly
+const FACE_PADDING: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// What is hidden of footage shown to the user
+pub fn mode_for(policy: &PrivacyPolicy, user: &UserClaims) -> PrivacyMode {
+    if user.can(Permission::ViolationsViewUnredacted) {
+        PrivacyMode::Off
+    } else {
+        policy.mode
+    }
+}
+
+// Redacts a frame in place. When no face is found the whole frame is blurred, a face the
+// cascade missed is far likelier in a crop of a person than a crop without one
+pub fn redact(image: &mut RgbImage, policy: &PrivacyPolicy, mode: PrivacyMode) {
+    let (width, height) = image.dimensions();
+    let whole = Region { x: 0, y: 0, width, height };
+
+    let regions = match mode {
+        PrivacyMode::Off => return,
+        PrivacyMode::Bodies => vec![whole],
+        PrivacyMode::Faces => {
+            let faces = faces::detect(image, &policy.face_cascade).unwrap_or_default();
+
+            if faces.is_empty() {
+                vec![whole]
+            } else {
+                faces.into_iter().map(|face| pad(face, width, height)).collect()
+            }
+        }
+    };
+
+    blur_regions(image, &regions);
+}
+
+pub fn redact_jpeg(
+    bytes: &[u8],
+    policy: &PrivacyPolicy,
+    mode: PrivacyMode,
+) -> image::ImageResult<Vec<u8>> {
+    let mut image = image::load_from_memory(bytes)?.to_rgb8();
+    redact(&mut image, policy, mode);
+
+    let mut jpeg = Cursor::new(Vec::new());
+    DynamicImage::ImageRgb8(image).write_to(&mut jpeg, ImageOutputFormat::Jpeg(85))?;
+
+    Ok(jpeg.into_inner())
+}
+
+pub fn blur_regions(image: &mut RgbImage, regions: &[Region]) {
+    for region in regions {
+        if region.width == 0 || region.height == 0 {
+            continue;
+        }
+
+        let patch = imageops::crop_imm(image, region.x, region.y, region.width, region.height).to_image();
+        let blocks = imageops::resize(
+            &patch,
+            BLUR_BLOCKS.min(region.width),
+            BLUR_BLOCKS.min(region.height),
+            FilterType::Triangle,
+        );
+        let blurred = imageops::resize(&blocks, region.width, region.height, FilterType::Triangle);
+
+        imageops::replace(image, &blurred, region.x as i64, region.y as i64);
+    }
+}
+
+fn pad(face: Region, width: u32, height: u32) -> Region {
+    let x = face.x.saturating_sub(face.width / FACE_PADDING);
+    let y = face.y.saturating_sub(face.height / FACE_PADDING);
+    let right = (face.x + face.width + face.width / FACE_PADDING).min(width);
+    let bottom = (face.y + face.height + face.height / FACE_PADDING).min(height);
+
+    Region {
+        x,
+        y,
+        width: right.saturating_sub(x),
+        height: bottom.saturating_sub(y),
+    }
+}
+
+// For footage that cannot be redacted
+pub(crate) fn unredacted_only() -> ResponseError {
+    ResponseError::new(
+        "Footage requires unredacted access",
+        "Not allowed to view this footage",
+        LogLevel::Information,
+        StatusCode::FORBIDDEN,
+    )
+}
+
+// Unredacted footage is only shown once the view is on record
+pub(crate) fn record_access(
+    connection: &mut PgConnection,
+    user: &UserClaims,
+    access_kind: AccessKind,
+    subject_id: Option<uuid::Uuid>,
+    ip_address: Option<String>,
+) -> crate::routes::Result<()> {
+    diesel::insert_into(crate::schema::access_logs::table)
+        .values(AccessLogInsert {
+            user_id: Some(user.user_id),
+            access_kind,
+            subject_id,
+            ip_address,
+            access_time: chrono::Utc::now().naive_utc(),
+        })
+        .execute(connection)
+        .map(|_| ())
+        .or(Err(ResponseError::server_error()))
+}
+
+// Footage is kept out of the browser cache so that every view is redacted or recorded again
+pub(crate) fn uncached(mut response: HttpResponse) -> HttpResponse {
+    response
+        .headers_mut()
+        .insert(CACHE_CONTROL, HeaderValue::from_static("private, no-store"));
+    response
+}
@@ -0,0 +1,405 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration as StdDuration, Instant};
+
+use actix_web::web::Data;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, QueryResult, RunQueryDsl};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::data::{AppData, DatabasePool};
+use crate::logging::{LogLevel, LogRecorder, LoggableError};
+use crate::models::{CameraCredential, RecordingSegment, RecordingSegmentInsert};
+use crate::server_config::RecordingPolicy;
+
+// How often recordings are started, stopped or restarted to match the cameras, and old segments dropped
+const SUPERVISE_INTERVAL: StdDuration = StdDuration::from_secs(30);
+// How often the recording directory is checked for files the index does not know of
+const SWEEP_INTERVAL: StdDuration = StdDuration::from_secs(3600);
+// Segments further apart than this were not recorded by the same ffmpeg
+const GAP_MS: i64 = 1000;
+// What the url read from the input list may use, the concat demuxer allows only files otherwise
+const INPUT_PROTOCOLS: &str = "file,crypto,data,rtsp,rtsps,rtp,srtp,udp,tcp,tls,http,https";
+
+// id, camera url, deactivated and recording quota of a camera
+type CameraRow = (Uuid, String, bool, Option<i32>);
+
+// The ffmpeg recording one camera, killed when the task is aborted
+struct Recording {
+    url: String,
+    task: JoinHandle<()>,
+}
+
+pub fn spawn(data: Data<AppData<'static>>, logger: Data<Mutex<LogRecorder>>, policy: RecordingPolicy) {
+    let root = match policy.dir.clone() {
+        Some(root) => root,
+        None => return,
+    };
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SUPERVISE_INTERVAL);
+        let mut recordings: HashMap<Uuid, Recording> = HashMap::new();
+        let mut last_sweep: Option<Instant> = None;
+
+        loop {
+            interval.tick().await;
+
+            let sweep = last_sweep.is_none_or(|time| time.elapsed() >= SWEEP_INTERVAL);
+            let run_data = data.clone();
+            let run_root = root.clone();
+            let run_policy = policy.clone();
+
+            let cameras = match tokio::task::spawn_blocking(move || {
+                maintain(&run_data, &run_root, &run_policy, sweep)
+            })
+            .await
+            {
+                Ok(Ok((cameras, removed))) => {
+                    if sweep {
+                        last_sweep = Some(Instant::now());
+                    }
+
+                    if removed > 0 {
+                        let log = LoggableError::new(
+                            format!("Dropped {removed} recording segments"),
+                            LogLevel::Information,
+                        );
+                        logger.lock().await.record(&log, None);
+                    }
+
+                    cameras
+                }
+                Ok(Err(error)) => {
+                    let log = LoggableError::new(format!("Recording upkeep failed: {error}"), LogLevel::Error);
+                    logger.lock().await.record(&log, None);
+                    continue;
+                }
+                Err(error) => {
+                    let log = LoggableError::new(format!("Recording upkeep aborted: {error}"), LogLevel::Error);
+                    logger.lock().await.record(&log, None);
+                    continue;
+                }
+            };
+
+            // a recording that stopped, or whose camera moved to another url, starts over
+            recordings.retain(|id, recording| {
+                let current = cameras.get(id) == Some(&recording.url) && !recording.task.is_finished();
+
+                if !current {
+                    recording.task.abort();
+                }
+
+                current
+            });
+
+            for (id, url) in cameras {
+                if recordings.contains_key(&id) {
+                    continue;
+                }
+
+                let task = tokio::spawn(record(
+                    data.database_pool(),
+                    logger.clone(),
+                    root.clone(),
+                    policy.clone(),
+                    id,
+                    url.clone(),
+                ));
+
+                recordings.insert(id, Recording { url, task });
+            }
+        }
+    });
+}
+
+// Drops what retention no longer keeps and gives back the url of each camera to record, with its
+// credentials. A camera whose credentials cannot be opened is not recorded
+fn maintain(
+    data: &AppData<'_>,
+    root: &Path,
+    policy: &RecordingPolicy,
+    sweep: bool,
+) -> QueryResult<(HashMap<Uuid, String>, usize)> {
+    use crate::schema::{camera_credentials, cameras};
+
+    let mut connection = data.connect_database();
+
+    let rows: Vec<CameraRow> = cameras::table
+        .select((
+            cameras::id,
+            cameras::camera_url,
+            cameras::deactivated,
+            cameras::recording_quota_mb,
+        ))
+        .load(&mut connection)?;
+
+    let credentials: HashMap<Uuid, CameraCredential> = camera_credentials::table
+        .load::<CameraCredential>(&mut connection)?
+        .into_iter()
+        .map(|credential| (credential.camera_id, credential))
+        .collect();
+
+    let cutoff = Utc::now().naive_utc() - Duration::days(policy.retention_days as i64);
+    let mut removed = 0;
+
+    // deactivated cameras are not recorded, but what they recorded before still ages out
+    for (id, _, _, quota_mb) in &rows {
+        let quota_bytes = quota_mb.map_or(policy.quota_mb as i64, |quota_mb| quota_mb as i64) * 1024 * 1024;
+
+        for file_path in RecordingSegment::prune(&mut connection, *id, cutoff, quota_bytes)? {
+            std::fs::remove_file(root.join(file_path)).ok();
+            removed += 1;
+        }
+    }
+
+    if sweep {
+        let known: HashSet<Uuid> = rows.iter().map(|(id, ..)| *id).collect();
+        removed += sweep_unindexed(&mut connection, root, policy, &known)?;
+    }
+
+    let cameras = rows
+        .into_iter()
+        .filter(|(_, _, deactivated, _)| !deactivated)
+        .filter_map(|(id, url, _, _)| {
+            let url = data.credential_vault().camera_url(&url, credentials.get(&id))?;
+            Some((id, url))
+        })
+        .collect();
+
+    Ok((cameras, removed))
+}
+
+// Files a recording left behind when it was stopped mid segment, and the folders of removed
+// cameras. Only files older than a few segments are taken, so none that is being written
+fn sweep_unindexed(
+    connection: &mut PgConnection,
+    root: &Path,
+    policy: &RecordingPolicy,
+    known: &HashSet<Uuid>,
+) -> QueryResult<usize> {
+    use crate::schema::recording_segments;
+
+    let grace = StdDuration::from_secs(policy.segment_seconds.max(1) as u64 * 3);
+    let mut removed = 0;
+
+    for entry in std::fs::read_dir(root).into_iter().flatten().flatten() {
+        let camera_id = match entry.file_name().to_str().and_then(|name| Uuid::parse_str(name).ok()) {
+            Some(camera_id) => camera_id,
+            None => continue,
+        };
+
+        if !known.contains(&camera_id) {
+            let files = std::fs::read_dir(entry.path()).map_or(0, |files| files.count());
+
+            if std::fs::remove_dir_all(entry.path()).is_ok() {
+                removed += files;
+            }
+
+            continue;
+        }
+
+        let indexed: HashSet<String> = recording_segments::table
+            .filter(recording_segments::camera_id.eq(camera_id))
+            .select(recording_segments::file_path)
+            .load::<String>(connection)?
+            .into_iter()
+            .collect();
+
+        for file in std::fs::read_dir(entry.path()).into_iter().flatten().flatten() {
+            let file_path = format!("{camera_id}/{}", file.file_name().to_string_lossy());
+            let stale = file
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age > grace));
+
+            if stale && !indexed.contains(&file_path) && std::fs::remove_file(file.path()).is_ok() {
+                removed += 1;
+            }
+        }
+    }
+
+    Ok(removed)
+}
+
+// The url with its credentials goes to ffmpeg in a file only this user can read, never as an
+// argument, since the command line of a process is open to every user of the host. The concat
+// demuxer is what reads an input url from a file. It passes no options on to the stream, so
+// RTSP is asked over TCP in the url, as over UDP a busy network loses whole seconds of recording
+fn input_list(url: &str) -> std::io::Result<tempfile::NamedTempFile> {
+    let url = match url.starts_with("rtsp") {
+        true if url.contains('?') => format!("{url}&tcp"),
+        true => format!("{url}?tcp"),
+        false => url.to_owned(),
+    };
+
+    // tempfile creates it with 0600
+    let mut file = tempfile::Builder::new().suffix(".ffconcat").tempfile()?;
+    writeln!(file, "ffconcat version 1.0")?;
+    writeln!(file, "file '{}'", url.replace('\'', "'\\''"))?;
+    file.flush()?;
+
+    Ok(file)
+}
+
+// Runs ffmpeg until it stops, indexing each segment as it is finished. The stream is copied as
+// it comes so recording costs next to nothing, only the video is kept since cameras often send
+// audio MPEG-TS cannot carry
+async fn record(
+    pool: DatabasePool,
+    logger: Data<Mutex<LogRecorder>>,
+    root: PathBuf,
+    policy: RecordingPolicy,
+    camera_id: Uuid,
+    url: String,
+) {
+    let dir = root.join(camera_id.to_string());
+
+    if let Err(error) = std::fs::create_dir_all(&dir) {
+        let log = LoggableError::new(
+            format!("Recording of camera {camera_id} failed to start: {error}"),
+            LogLevel::Error,
+        );
+        logger.lock().await.record(&log, None);
+        return;
+    }
+
+    // kept until ffmpeg is done, the file goes with it when the recording stops
+    let input = match input_list(&url) {
+        Ok(input) => input,
+        Err(error) => {
+            let log = LoggableError::new(
+                format!("Recording of camera {camera_id} failed to start: {error}"),
+                LogLevel::Error,
+            );
+            logger.lock().await.record(&log, None);
+            return;
+        }
+    };
+
+    let mut command = Command::new(&policy.ffmpeg);
+    command
+        .args(["-nostdin", "-loglevel", "error"])
+        .args(["-f", "concat", "-safe", "0", "-protocol_whitelist", INPUT_PROTOCOLS])
+        .arg("-i")
+        .arg(input.path())
+        .args(["-map", "0:v:0", "-c", "copy", "-f", "segment", "-segment_format", "mpegts"])
+        .arg("-segment_time")
+        .arg(policy.segment_seconds.max(1).to_string())
+        .args(["-strftime", "1", "-segment_list", "pipe:1", "-segment_list_type", "csv"])
+        .arg(dir.join("%Y%m%d-%H%M%S.ts"))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(error) => {
+            let log = LoggableError::new(
+                format!("Recording of camera {camera_id} failed to start: {error}"),
+                LogLevel::Error,
+            );
+            logger.lock().await.record(&log, None);
+            return;
+        }
+    };
+
+    let mut lines = match child.stdout.take() {
+        Some(stdout) => BufReader::new(stdout).lines(),
+        None => return,
+    };
+    let mut indexed = 0;
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let (name, seconds) = match parse_segment(&line) {
+            Some(segment) => segment,
+            None => continue,
+        };
+
+        // the line comes as the file is closed, so that is when the segment ended
+        let end_time = Utc::now().naive_utc();
+        let segment = RecordingSegmentInsert {
+            camera_id,
+            start_time: end_time - Duration::microseconds((seconds * 1e6) as i64),
+            end_time,
+            file_path: format!("{camera_id}/{name}"),
+            bytes: std::fs::metadata(dir.join(&name)).map_or(0, |metadata| metadata.len() as i64),
+        };
+
+        let pool = pool.clone();
+
+        if let Ok(Ok(_)) = tokio::task::spawn_blocking(move || {
+            let mut connection = pool.get().map_err(|error| error.to_string())?;
+
+            diesel::insert_into(crate::schema::recording_segments::table)
+                .values(&segment)
+                .execute(&mut connection)
+                .map_err(|error| error.to_string())
+        })
+        .await
+        {
+            indexed += 1;
+        }
+    }
+
+    let status = child.wait().await;
+
+    // a camera that never answers is retried quietly, one that was recording is worth a note
+    if indexed > 0 {
+        let status = status.map_or_else(|error| error.to_string(), |status| status.to_string());
+        let log = LoggableError::new(
+            format!("Recording of camera {camera_id} stopped after {indexed} segments: {status}"),
+            LogLevel::Warning,
+        );
+        logger.lock().await.record(&log, None);
+    }
+}
+
+// ffmpeg lists each finished segment as `file,start,end` with the times in seconds of the stream
+pub(crate) fn parse_segment(line: &str) -> Option<(String, f64)> {
+    let mut fields = line.trim().rsplitn(3, ',');
+    let end: f64 = fields.next()?.parse().ok()?;
+    let start: f64 = fields.next()?.parse().ok()?;
+    let name = Path::new(fields.next()?).file_name()?.to_str()?.to_owned();
+
+    (end >= start).then_some((name, end - start))
+}
+
+// A VOD playlist of the segments, the URIs are relative so they resolve next to the playlist.
+// Where the recording was interrupted the timestamps start over, which players are told of
+pub(crate) fn playlist(segments: &[RecordingSegment]) -> String {
+    let seconds = |segment: &RecordingSegment| {
+        (segment.end_time - segment.start_time).num_milliseconds() as f64 / 1000.0
+    };
+
+    let target_duration = segments.iter().map(seconds).fold(1.0, f64::max).ceil();
+
+    let mut playlist = format!(
+        "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-TARGETDURATION:{target_duration}\n#EXT-X-MEDIA-SEQUENCE:0\n"
+    );
+    let mut previous_end: Option<NaiveDateTime> = None;
+
+    for segment in segments {
+        if matches!(previous_end, Some(end) if (segment.start_time - end).num_milliseconds().abs() > GAP_MS) {
+            playlist.push_str("#EXT-X-DISCONTINUITY\n");
+        }
+
+        playlist.push_str(&format!(
+            "#EXT-X-PROGRAM-DATE-TIME:{}Z\n#EXTINF:{:.3},\nsegments/{}\n",
+            segment.start_time.format("%Y-%m-%dT%H:%M:%S%.3f"),
+            seconds(segment),
+            segment.id
+        ));
+
+        previous_end = Some(segment.end_time);
+    }
+
+    playlist.push_str("#EXT-X-ENDLIST\n");
+    playlist
+}
@@ -0,0 +1,67 @@
+use std::io::{Error, ErrorKind};
+
+use actix_web::web::{self, Bytes};
+use futures_util::Stream;
+
+use crate::data::DatabasePool;
+use crate::models::ViolationReportRow;
+
+use super::{ReportFilter, COLUMNS, PAGE_SIZE};
+
+pub(crate) fn csv_stream(
+    pool: DatabasePool,
+    filter: ReportFilter,
+) -> impl Stream<Item = Result<Bytes, Error>> {
+    futures_util::stream::unfold(Some(0), move |offset| {
+        let pool = pool.clone();
+        let filter = filter.clone();
+
+        async move {
+            let offset = offset?;
+
+            match web::block(move || encode_page(&pool, &filter, offset)).await {
+                Ok(Ok((bytes, row_count))) => Some((
+                    Ok(bytes),
+                    (row_count == PAGE_SIZE as usize).then_some(offset + PAGE_SIZE),
+                )),
+                Ok(Err(error)) => Some((Err(error), None)),
+                Err(error) => Some((Err(Error::new(ErrorKind::Other, error)), None)),
+            }
+        }
+    })
+}
+
+fn encode_page(
+    pool: &DatabasePool,
+    filter: &ReportFilter,
+    offset: i64,
+) -> Result<(Bytes, usize), Error> {
+    let mut connection = pool
+        .get()
+        .map_err(|error| Error::new(ErrorKind::Other, error))?;
+
+    let rows = filter
+        .load_page(&mut connection, offset)
+        .map_err(|error| Error::new(ErrorKind::Other, error))?;
+
+    let bytes = encode_rows(&rows, offset == 0)?;
+
+    Ok((Bytes::from(bytes), rows.len()))
+}
+
+// Only the first page starts with the column names
+pub(crate) fn encode_rows(rows: &[ViolationReportRow], header: bool) -> Result<Vec<u8>, Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::<u8>::new());
+
+    if header {
+        writer.write_record(COLUMNS)?;
+    }
+
+    for row in rows {
+        writer.write_record(super::row_columns(row))?;
+    }
+
+    writer.into_inner().map_err(|error| error.into_error())
+}
@@ -0,0 +1,165 @@
+mod csv_report;
+mod pdf_report;
+mod xlsx_report;
+
+use actix_web::http::StatusCode;
+use chrono::{Duration, NaiveDate};
+use diesel::pg::Pg;
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, QueryResult, RunQueryDsl};
+use serde::Deserialize;
+
+use crate::logging::{LogLevel, ResponseError};
+use crate::models::ViolationReportRow;
+use crate::schema::violations;
+
+pub(crate) use csv_report::csv_stream;
+#[cfg(test)]
+pub(crate) use csv_report::encode_rows;
+pub(crate) use pdf_report::write_pdf;
+pub(crate) use xlsx_report::write_xlsx;
+
+// Rows are fetched page by page so that a report never holds
+// the whole violations table (and its images) in memory
+const PAGE_SIZE: i64 = 200;
+
+// (blob store key, bytes not yet moved to the store, archive path)
+type ImageSource = (Option<String>, Option<Vec<u8>>, Option<String>);
+
+const COLUMNS: [&str; 8] = [
+    "Violation ID",
+    "Date Time (UTC)",
+    "Area Code",
+    "Violation Kind",
+    "Identified",
+    "First Name",
+    "Last Name",
+    "Category",
+];
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ReportFormat {
+    Csv,
+    Xlsx,
+    Pdf,
+}
+
+impl ReportFormat {
+    pub(crate) fn file_name(&self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "violations.csv",
+            ReportFormat::Xlsx => "violations.xlsx",
+            ReportFormat::Pdf => "violations.pdf",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ReportFilter {
+    pub(crate) area_code: Option<String>,
+    pub(crate) identified: Option<bool>,
+    pub(crate) from: Option<NaiveDate>,
+    pub(crate) to: Option<NaiveDate>,
+}
+
+impl ReportFilter {
+    pub(crate) fn query(&self) -> violations::BoxedQuery<'static, Pg> {
+        let mut query = violations::table.into_boxed();
+
+        if let Some(area_code) = &self.area_code {
+            query = query.filter(violations::area_code.eq(area_code.clone()));
+        }
+
+        if let Some(identified) = self.identified {
+            query = query.filter(violations::identified.eq(identified));
+        }
+
+        if let Some(from) = self.from.and_then(|date| date.and_hms_opt(0, 0, 0)) {
+            query = query.filter(violations::date_time.ge(from));
+        }
+
+        // the end date is inclusive
+        if let Some(to) = self
+            .to
+            .and_then(|date| (date + Duration::days(1)).and_hms_opt(0, 0, 0))
+        {
+            query = query.filter(violations::date_time.lt(to));
+        }
+
+        query.order_by((violations::date_time, violations::id))
+    }
+
+    fn load_page(
+        &self,
+        connection: &mut PgConnection,
+        offset: i64,
+    ) -> QueryResult<Vec<ViolationReportRow>> {
+        self.query()
+            .select((
+                violations::id,
+                violations::area_code,
+                violations::violation_kind,
+                violations::date_time,
+                violations::identified,
+                violations::first_name,
+                violations::last_name,
+                violations::category,
+            ))
+            .limit(PAGE_SIZE)
+            .offset(offset)
+            .load(connection)
+    }
+
+    fn load_page_with_images(
+        &self,
+        connection: &mut PgConnection,
+        offset: i64,
+    ) -> QueryResult<Vec<(ViolationReportRow, ImageSource)>> {
+        self.query()
+            .select((
+                (
+                    violations::id,
+                    violations::area_code,
+                    violations::violation_kind,
+                    violations::date_time,
+                    violations::identified,
+                    violations::first_name,
+                    violations::last_name,
+                    violations::category,
+                ),
+                (
+                    violations::image_key,
+                    violations::image_bytes,
+                    violations::image_archive,
+                ),
+            ))
+            .limit(PAGE_SIZE)
+            .offset(offset)
+            .load(connection)
+    }
+}
+
+fn row_columns(row: &ViolationReportRow) -> [String; 8] {
+    [
+        row.id.to_string(),
+        row.date_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        row.area_code.clone(),
+        row.violation_kind.to_string(),
+        if row.identified { "Yes" } else { "No" }.to_owned(),
+        row.first_name.clone().unwrap_or_default(),
+        row.last_name.clone().unwrap_or_default(),
+        row.category
+            .as_ref()
+            .map(|category| category.to_string())
+            .unwrap_or_default(),
+    ]
+}
+
+fn report_error(format: &str, error: impl std::fmt::Display) -> ResponseError {
+    ResponseError::new(
+        format!("Failed to generate {format} report: {error}"),
+        "Failed to generate report",
+        LogLevel::Error,
+        StatusCode::INTERNAL_SERVER_ERROR,
+    )
+}
@@ -0,0 +1,170 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use image::DynamicImage;
+use printpdf::{Image, ImageTransform, Mm, PdfDocument};
+use tokio::runtime::Handle;
+
+use crate::blob_store::BlobStore;
+use crate::data::DatabasePool;
+use crate::models::ViolationReportRow;
+use crate::server_config::{PrivacyMode, PrivacyPolicy};
+
+use super::{report_error, ReportFilter, PAGE_SIZE};
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 15.0;
+const ENTRY_HEIGHT: f32 = 32.0;
+
+// 112px at 101.6 DPI is printed as a 28mm thumbnail
+const THUMBNAIL_PIXELS: u32 = 112;
+const THUMBNAIL_DPI: f32 = 101.6;
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn write_pdf(
+    pool: &DatabasePool,
+    filter: &ReportFilter,
+    font_bytes: &[u8],
+    runtime: &Handle,
+    blob_store: &dyn BlobStore,
+    archive_dir: &Path,
+    privacy: &PrivacyPolicy,
+    mode: PrivacyMode,
+) -> crate::routes::Result<File> {
+    let mut connection = pool.get().map_err(|error| report_error("PDF", error))?;
+
+    let (document, page, layer) = PdfDocument::new(
+        "Violation Report",
+        Mm(PAGE_WIDTH),
+        Mm(PAGE_HEIGHT),
+        "Layer 1",
+    );
+
+    let font = document
+        .add_external_font(font_bytes)
+        .map_err(|error| report_error("PDF", error))?;
+
+    let mut layer = document.get_page(page).get_layer(layer);
+
+    layer.use_text(
+        "Violation Report",
+        18.0,
+        Mm(MARGIN),
+        Mm(PAGE_HEIGHT - MARGIN - 6.0),
+        &font,
+    );
+    layer.use_text(
+        format!(
+            "Generated {} UTC",
+            chrono::Utc::now().format("%Y-%m-%d %H:%M")
+        ),
+        9.0,
+        Mm(MARGIN),
+        Mm(PAGE_HEIGHT - MARGIN - 12.0),
+        &font,
+    );
+
+    let mut top = PAGE_HEIGHT - MARGIN - 20.0;
+    let mut offset = 0;
+
+    loop {
+        let rows = filter
+            .load_page_with_images(&mut connection, offset)
+            .map_err(|error| report_error("PDF", error))?;
+
+        for (row, image) in rows.iter() {
+            if top - ENTRY_HEIGHT < MARGIN {
+                let (page, layer_index) =
+                    document.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+
+                layer = document.get_page(page).get_layer(layer_index);
+                top = PAGE_HEIGHT - MARGIN;
+            }
+
+            let image_bytes = match image {
+                (Some(key), _, _) => crate::blob_store::get_blocking(runtime, blob_store, key).ok(),
+                (None, Some(bytes), _) => Some(bytes.clone()),
+                (None, None, Some(archive)) => {
+                    crate::retention::read_archived(archive_dir, archive).ok()
+                }
+                (None, None, None) => None,
+            };
+
+            // the full resolution crop is dropped as soon as the thumbnail is made,
+            // faces are found in it first since they are lost in a thumbnail
+            if let Some(Ok(image)) = image_bytes.map(|bytes| image::load_from_memory(&bytes)) {
+                let mut image = image.to_rgb8();
+                crate::privacy::redact(&mut image, privacy, mode);
+
+                let thumbnail = DynamicImage::ImageRgb8(
+                    DynamicImage::ImageRgb8(image)
+                        .thumbnail(THUMBNAIL_PIXELS, THUMBNAIL_PIXELS)
+                        .to_rgb8(),
+                );
+
+                Image::from_dynamic_image(&thumbnail).add_to_layer(
+                    layer.clone(),
+                    ImageTransform {
+                        translate_x: Some(Mm(MARGIN)),
+                        translate_y: Some(Mm(top - ENTRY_HEIGHT + 2.0)),
+                        dpi: Some(THUMBNAIL_DPI),
+                        ..Default::default()
+                    },
+                );
+            }
+
+            for (index, line) in entry_lines(row).into_iter().enumerate() {
+                layer.use_text(
+                    line,
+                    10.0,
+                    Mm(MARGIN + 34.0),
+                    Mm(top - 6.0 - index as f32 * 5.0),
+                    &font,
+                );
+            }
+
+            top -= ENTRY_HEIGHT;
+        }
+
+        if rows.len() < PAGE_SIZE as usize {
+            break;
+        }
+
+        offset += PAGE_SIZE;
+    }
+
+    let mut file = tempfile::tempfile().map_err(|error| report_error("PDF", error))?;
+
+    document
+        .save(&mut BufWriter::new(&mut file))
+        .map_err(|error| report_error("PDF", error))?;
+
+    Ok(file)
+}
+
+fn entry_lines(row: &ViolationReportRow) -> Vec<String> {
+    let mut lines = vec![
+        format!("{} - {}", row.violation_kind, row.area_code),
+        format!("{} UTC", row.date_time.format("%Y-%m-%d %H:%M:%S")),
+    ];
+
+    if row.identified {
+        lines.push(format!(
+            "{} {}",
+            row.first_name.as_deref().unwrap_or_default(),
+            row.last_name.as_deref().unwrap_or_default()
+        ));
+
+        if let Some(category) = &row.category {
+            lines.push(category.to_string());
+        }
+    } else {
+        lines.push(String::from("Unidentified"));
+    }
+
+    lines.push(row.id.to_string());
+
+    lines
+}
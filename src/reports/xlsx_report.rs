@@ -0,0 +1,56 @@
+use std::fs::File;
+
+use rust_xlsxwriter::{Format, Workbook};
+
+use crate::data::DatabasePool;
+
+use super::{report_error, ReportFilter, COLUMNS, PAGE_SIZE};
+
+pub(crate) fn write_xlsx(pool: &DatabasePool, filter: &ReportFilter) -> crate::routes::Result<File> {
+    let mut connection = pool.get().map_err(|error| report_error("XLSX", error))?;
+
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    let header = Format::new().set_bold();
+
+    for (column, title) in COLUMNS.iter().enumerate() {
+        worksheet
+            .write_string_with_format(0, column as u16, *title, &header)
+            .map_err(|error| report_error("XLSX", error))?;
+    }
+
+    let mut offset = 0;
+    let mut line = 1u32;
+
+    loop {
+        let rows = filter
+            .load_page(&mut connection, offset)
+            .map_err(|error| report_error("XLSX", error))?;
+
+        for row in &rows {
+            for (column, value) in super::row_columns(row).into_iter().enumerate() {
+                worksheet
+                    .write_string(line, column as u16, value)
+                    .map_err(|error| report_error("XLSX", error))?;
+            }
+
+            line += 1;
+        }
+
+        if rows.len() < PAGE_SIZE as usize {
+            break;
+        }
+
+        offset += PAGE_SIZE;
+    }
+
+    worksheet.autofit();
+
+    let mut file = tempfile::tempfile().map_err(|error| report_error("XLSX", error))?;
+
+    workbook
+        .save_to_writer(&mut file)
+        .map_err(|error| report_error("XLSX", error))?;
+
+    Ok(file)
+}
@@ -0,0 +1,348 @@
+use std::io::{Cursor, Error, ErrorKind};
+use std::path::{Component, Path};
+use std::time::Duration as StdDuration;
+
+use actix_web::web::Data;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, QueryResult, RunQueryDsl};
+use image::ImageOutputFormat;
+use tokio::runtime::Handle;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::blob_store::{self, BlobStore, SharedBlobStore};
+use crate::data::DatabasePool;
+use crate::logging::{LogLevel, LogRecorder, LoggableError};
+use crate::models::RetentionRunInsert;
+use crate::schema::{retention_runs, violations};
+use crate::server_config::RetentionPolicy;
+
+const BATCH_SIZE: i64 = 100;
+
+// id, image key, archive path and clip key of a violation to purge
+type PurgeRow = (Uuid, Option<String>, Option<String>, Option<String>);
+
+pub fn spawn(
+    pool: DatabasePool,
+    blob_store: SharedBlobStore,
+    logger: Data<Mutex<LogRecorder>>,
+    policy: RetentionPolicy,
+) {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(StdDuration::from_secs(policy.interval_hours.max(1) * 3600));
+
+        loop {
+            interval.tick().await;
+
+            let pool = pool.clone();
+            let blob_store = blob_store.clone();
+            let run_policy = policy.clone();
+            let runtime = Handle::current();
+
+            let log = match tokio::task::spawn_blocking(move || {
+                run(&pool, &runtime, blob_store.as_ref(), &run_policy)
+            })
+            .await
+            {
+                Ok(Ok(summary)) => LoggableError::new(summary.to_string(), LogLevel::Information),
+                Ok(Err(error)) => {
+                    LoggableError::new(format!("Retention run failed: {error}"), LogLevel::Error)
+                }
+                Err(error) => {
+                    LoggableError::new(format!("Retention run aborted: {error}"), LogLevel::Error)
+                }
+            };
+
+            logger.lock().await.record(&log, None);
+        }
+    });
+}
+
+pub fn run(
+    pool: &DatabasePool,
+    runtime: &Handle,
+    blob_store: &dyn BlobStore,
+    policy: &RetentionPolicy,
+) -> Result<RetentionRunInsert, String> {
+    let mut connection = pool.get().map_err(|error| error.to_string())?;
+    let started_time = Utc::now().naive_utc();
+
+    let mut summary = RetentionRunInsert {
+        started_time,
+        finished_time: started_time,
+        compacted: 0,
+        archived: 0,
+        purged: 0,
+        failed: 0,
+        bytes_reclaimed: 0,
+    };
+
+    // purge first so nothing gets compacted or archived only to be deleted right after
+    if let Some(days) = policy.purge_after_days {
+        purge(
+            &mut connection,
+            runtime,
+            blob_store,
+            policy,
+            cutoff(started_time, days),
+            &mut summary,
+        )
+        .map_err(|error| error.to_string())?;
+    }
+
+    if let Some(days) = policy.archive_after_days {
+        archive(
+            &mut connection,
+            runtime,
+            blob_store,
+            policy,
+            cutoff(started_time, days),
+            &mut summary,
+        )
+        .map_err(|error| error.to_string())?;
+    }
+
+    if let Some(days) = policy.compact_after_days {
+        compact(
+            &mut connection,
+            runtime,
+            blob_store,
+            policy,
+            cutoff(started_time, days),
+            &mut summary,
+        )
+        .map_err(|error| error.to_string())?;
+    }
+
+    summary.finished_time = Utc::now().naive_utc();
+
+    diesel::insert_into(retention_runs::table)
+        .values(&summary)
+        .execute(&mut connection)
+        .map_err(|error| error.to_string())?;
+
+    Ok(summary)
+}
+
+pub(crate) fn read_archived(archive_dir: &Path, relative: &str) -> std::io::Result<Vec<u8>> {
+    let relative = Path::new(relative);
+
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(Error::new(ErrorKind::InvalidInput, "Invalid archive path"));
+    }
+
+    std::fs::read(archive_dir.join(relative))
+}
+
+pub(crate) fn cutoff(now: NaiveDateTime, days: u32) -> NaiveDateTime {
+    now - Duration::days(days as i64)
+}
+
+fn compact(
+    connection: &mut PgConnection,
+    runtime: &Handle,
+    blob_store: &dyn BlobStore,
+    policy: &RetentionPolicy,
+    cutoff: NaiveDateTime,
+    summary: &mut RetentionRunInsert,
+) -> QueryResult<()> {
+    loop {
+        let batch: Vec<(Uuid, Option<String>)> = violations::table
+            .filter(violations::date_time.lt(cutoff))
+            .filter(violations::image_compacted.eq(false))
+            .filter(violations::image_key.is_not_null())
+            .select((violations::id, violations::image_key))
+            .limit(BATCH_SIZE)
+            .load(connection)?;
+
+        for (id, key) in &batch {
+            let key = key.as_deref().unwrap_or_default();
+
+            // rows that fail are still flagged so they are not retried every run
+            let compacted = blob_store::get_blocking(runtime, blob_store, key)
+                .ok()
+                .and_then(|bytes| {
+                    let smaller = recompress(&bytes, policy)?;
+                    Some((bytes.len(), smaller))
+                });
+
+            // the smaller image takes the place of the original as it is stored
+            let replaced = match compacted {
+                Some((original_len, Some(smaller))) => {
+                    let smaller_len = smaller.len();
+                    let stored = blob_store::store_blocking(
+                        runtime,
+                        blob_store,
+                        connection,
+                        smaller,
+                        "image/jpeg",
+                        |connection, new_key| {
+                            diesel::update(violations::table.filter(violations::id.eq(id)))
+                                .set((
+                                    violations::image_key.eq(new_key),
+                                    violations::image_compacted.eq(true),
+                                ))
+                                .execute(connection)
+                        },
+                    );
+
+                    match stored {
+                        Ok(_) => {
+                            summary.compacted += 1;
+                            summary.bytes_reclaimed += (original_len - smaller_len) as i64;
+                            true
+                        }
+                        Err(_) => {
+                            summary.failed += 1;
+                            false
+                        }
+                    }
+                }
+                Some((_, None)) => {
+                    summary.compacted += 1;
+                    false
+                }
+                None => {
+                    summary.failed += 1;
+                    false
+                }
+            };
+
+            if replaced {
+                blob_store::release_blocking(runtime, blob_store, connection, key).ok();
+            } else {
+                diesel::update(violations::table.filter(violations::id.eq(id)))
+                    .set(violations::image_compacted.eq(true))
+                    .execute(connection)?;
+            }
+        }
+
+        if batch.len() < BATCH_SIZE as usize {
+            return Ok(());
+        }
+    }
+}
+
+// None when the image cannot be decoded, Some(None) when re-encoding does not make it smaller
+pub(crate) fn recompress(bytes: &[u8], policy: &RetentionPolicy) -> Option<Option<Vec<u8>>> {
+    let mut image = image::load_from_memory(bytes).ok()?;
+
+    if image.width() > policy.max_dimension || image.height() > policy.max_dimension {
+        image = image.thumbnail(policy.max_dimension, policy.max_dimension);
+    }
+
+    let mut buffer = Cursor::new(Vec::new());
+
+    image::DynamicImage::ImageRgb8(image.to_rgb8())
+        .write_to(&mut buffer, ImageOutputFormat::Jpeg(policy.jpeg_quality))
+        .ok()?;
+
+    let smaller = buffer.into_inner();
+
+    Some((smaller.len() < bytes.len()).then_some(smaller))
+}
+
+fn archive(
+    connection: &mut PgConnection,
+    runtime: &Handle,
+    blob_store: &dyn BlobStore,
+    policy: &RetentionPolicy,
+    cutoff: NaiveDateTime,
+    summary: &mut RetentionRunInsert,
+) -> QueryResult<()> {
+    loop {
+        let batch: Vec<(Uuid, NaiveDateTime, Option<String>)> = violations::table
+            .filter(violations::date_time.lt(cutoff))
+            .filter(violations::image_key.is_not_null())
+            .select((violations::id, violations::date_time, violations::image_key))
+            .limit(BATCH_SIZE)
+            .load(connection)?;
+
+        for (id, date_time, key) in &batch {
+            let key = key.as_deref().unwrap_or_default();
+            let relative = format!("{}/{}.jpg", date_time.format("%Y/%m"), id);
+            let path = policy.archive_dir.join(&relative);
+
+            let written = blob_store::get_blocking(runtime, blob_store, key)
+                .map_err(|error| Error::new(ErrorKind::Other, error.to_string()))
+                .and_then(|bytes| {
+                    path.parent()
+                        .map_or(Ok(()), std::fs::create_dir_all)
+                        .and_then(|_| std::fs::write(&path, &bytes))
+                        .map(|_| bytes.len())
+                });
+
+            // the store or the archive directory is unusable, the rest is left for the next run
+            let written = match written {
+                Ok(written) => written,
+                Err(_) => {
+                    summary.failed += 1;
+                    return Ok(());
+                }
+            };
+
+            diesel::update(violations::table.filter(violations::id.eq(id)))
+                .set((
+                    violations::image_key.eq(None::<String>),
+                    violations::image_archive.eq(Some(relative)),
+                ))
+                .execute(connection)?;
+
+            blob_store::release_blocking(runtime, blob_store, connection, key).ok();
+
+            summary.archived += 1;
+            summary.bytes_reclaimed += written as i64;
+        }
+
+        if batch.len() < BATCH_SIZE as usize {
+            return Ok(());
+        }
+    }
+}
+
+fn purge(
+    connection: &mut PgConnection,
+    runtime: &Handle,
+    blob_store: &dyn BlobStore,
+    policy: &RetentionPolicy,
+    cutoff: NaiveDateTime,
+    summary: &mut RetentionRunInsert,
+) -> QueryResult<()> {
+    loop {
+        let batch: Vec<PurgeRow> = violations::table
+            .filter(violations::date_time.lt(cutoff))
+            .select((
+                violations::id,
+                violations::image_key,
+                violations::image_archive,
+                violations::clip_key,
+            ))
+            .limit(BATCH_SIZE)
+            .load(connection)?;
+
+        let ids: Vec<Uuid> = batch.iter().map(|(id, _, _, _)| *id).collect();
+
+        summary.purged += diesel::delete(violations::table.filter(violations::id.eq_any(ids)))
+            .execute(connection)? as i32;
+
+        // blobs are released after the rows are gone so the reference check sees the deletion
+        for (_, key, archive, clip) in &batch {
+            // a clip shared with a violation that is kept is not released
+            for key in [key, clip].into_iter().flatten() {
+                blob_store::release_blocking(runtime, blob_store, connection, key).ok();
+            }
+
+            if let Some(archive) = archive {
+                std::fs::remove_file(policy.archive_dir.join(archive)).ok();
+            }
+        }
+
+        if batch.len() < BATCH_SIZE as usize {
+            return Ok(());
+        }
+    }
+}
@@ -2,22 +2,36 @@ use actix_web::http::StatusCode;
 use actix_web::web;
 use actix_web::HttpResponse;
 use actix_web::Responder;
+use chrono::{Duration, NaiveDateTime, Utc};
 use diesel::dsl::count;
+use diesel::BoolExpressionMethods;
+use diesel::Connection;
 use diesel::result::DatabaseErrorKind;
 use diesel::ExpressionMethods;
 use diesel::JoinOnDsl;
 use diesel::NullableExpressionMethods;
+use diesel::OptionalExtension;
+use diesel::PgConnection;
 use diesel::QueryDsl;
 use diesel::RunQueryDsl;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::data::credential_vault::{self, split_credentials};
 use crate::logging::LogLevel;
+use crate::logging::ResponseError;
+use crate::models::AreaCamera;
+use crate::models::AreaDependents;
 use crate::models::AreaGuardCount;
 use crate::models::AreaInsert;
 use crate::models::AreaSelect;
 use crate::models::CameraInsert;
+use crate::models::CameraSelect;
+use crate::models::CameraUpdate;
+use crate::models::GuardAssignment;
 use crate::models::IntoModel;
+use crate::models::Recurrence;
+use crate::models::UserBasicSelect;
 
 use crate::{
     data::AppData,
@@ -30,6 +44,12 @@ pub(crate) struct CreateAreaRequest {
     pub(crate) name: String,
 }
 
+#[derive(Deserialize)]
+struct UpdateAreaRequest {
+    code: String,
+    name: String,
+}
+
 #[derive(Deserialize)]
 pub(crate) struct AssignRequest {
     #[serde(alias = "user-id")]
@@ -41,6 +61,7 @@ pub(crate) struct AssignRequest {
 struct AreaRemoveQuery {
     #[serde(alias = "area-code")]
     pub(crate) code: String,
+    archive: Option<bool>,
 }
 
 #[derive(Serialize)]
@@ -67,6 +88,24 @@ struct CameraModifyRequest {
     label: Option<String>,
     #[serde(alias = "camera-url")]
     camera_url: Option<String>,
+    #[serde(alias = "area-code")]
+    area_code: Option<String>,
+    deactivated: Option<bool>,
+    #[serde(alias = "clear-credentials")]
+    clear_credentials: Option<bool>,
+    #[serde(alias = "onvif-url")]
+    onvif_url: Option<String>,
+    #[serde(alias = "onvif-profile")]
+    onvif_profile: Option<String>,
+    #[serde(alias = "recording-quota-mb")]
+    recording_quota_mb: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CameraListQuery {
+    #[serde(alias = "area-code")]
+    area_code: Option<String>,
+    deactivated: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -76,7 +115,16 @@ struct CameraAddRequest {
     area_code: String,
     #[serde(alias = "camera-url")]
     camera_url: String,
+    // given apart from the url, e.g. after a probe that does not send them back
+    username: Option<String>,
+    password: Option<String>,
     enable: bool,
+    #[serde(alias = "onvif-url")]
+    onvif_url: Option<String>,
+    #[serde(alias = "onvif-profile")]
+    onvif_profile: Option<String>,
+    #[serde(alias = "recording-quota-mb")]
+    recording_quota_mb: Option<i32>,
 }
 
 impl IntoModel<CameraInsert> for CameraAddRequest {
@@ -85,15 +133,55 @@ impl IntoModel<CameraInsert> for CameraAddRequest {
         crate::logging::ResponseError::length_limit_check("Area code", &self.area_code, 3, 10)?;
         crate::logging::ResponseError::length_limit_check("Camera URL", &self.camera_url, 10, 512)?;
 
+        if let Some(onvif_url) = &self.onvif_url {
+            crate::logging::ResponseError::length_limit_check("ONVIF URL", onvif_url, 10, 512)?;
+        }
+
+        if let Some(onvif_profile) = &self.onvif_profile {
+            crate::logging::ResponseError::length_limit_check("ONVIF profile", onvif_profile, 1, 64)?;
+        }
+
+        if matches!(self.recording_quota_mb, Some(quota) if quota <= 0) {
+            return Err(invalid_quota());
+        }
+
         Ok(CameraInsert {
             label: self.label.clone(),
             area_code: self.area_code.clone(),
-            camera_url: self.camera_url.clone(),
+            camera_url: split_credentials(&self.camera_url).0,
             deactivated: !self.enable,
+            // the device signs in with the camera's credentials, none are kept here
+            onvif_url: self.onvif_url.as_deref().map(|url| split_credentials(url).0),
+            onvif_profile: self.onvif_profile.clone(),
+            recording_quota_mb: self.recording_quota_mb,
         })
     }
 }
 
+fn invalid_quota() -> ResponseError {
+    ResponseError::new(
+        "Invalid recording quota",
+        "The recording quota must be a positive number of megabytes",
+        LogLevel::Information,
+        StatusCode::UNPROCESSABLE_ENTITY,
+    )
+}
+
+// Guards, shifts and cameras can only be added to an area that is still in use
+pub(crate) fn active_area(connection: &mut PgConnection, area_code: &str) -> super::Result<()> {
+    use crate::schema::areas;
+
+    areas::table
+        .filter(areas::code.eq(area_code))
+        .filter(areas::archived_time.is_null())
+        .select(areas::code)
+        .first::<String>(connection)
+        .optional()
+        .or(Err(ResponseError::server_error()))?
+        .map(|_| ())
+        .ok_or_else(|| ResponseError::value_do_not_exist("Area"))
+}
+
 #[actix_web::get("/list")]
 async fn get_list(
     (state, _user, query): (web::Data<AppData<'_>>, UserClaims, web::Query<ListQuery>),
@@ -107,6 +195,7 @@ async fn get_list(
         let list = areas::table
             .left_join(users::table.on(areas::code.nullable().eq(users::assigned_area)))
             .filter(users::deactivated.eq(false))
+            .filter(areas::archived_time.is_null())
             .group_by((areas::code, areas::name))
             .select((
                 areas::dsl::code,
@@ -120,7 +209,11 @@ async fn get_list(
             .customize()
             .with_status(StatusCode::OK))
     } else {
-        let list: Vec<AreaSelect> = areas::table.get_results(&mut connection).unwrap();
+        let list: Vec<AreaSelect> = areas::table
+            .filter(areas::archived_time.is_null())
+            .select((areas::name, areas::code))
+            .get_results(&mut connection)
+            .unwrap();
         Ok(serde_json::to_string(&list)
             .unwrap()
             .customize()
@@ -130,14 +223,10 @@ async fn get_list(
 
 #[actix_web::post("/create")]
 async fn post_create(
-    (state, request, user): (web::Data<AppData<'_>>, web::Json<CreateAreaRequest>, UserClaims),
+    (state, request, _user): (web::Data<AppData<'_>>, web::Json<CreateAreaRequest>, UserClaims),
 ) -> super::Result<impl Responder> {
     use crate::schema::areas::dsl::*;
 
-    if user.assigned_role != UserRole::SecurityHead {
-        return Err(crate::logging::ResponseError::unauthorized(user));
-    }
-
     let mut connection = state.connect_database();
 
     let model: AreaInsert = request.0.try_into()?;
@@ -151,33 +240,140 @@ async fn post_create(
     Ok(web::Json(CreateAreaOk { code: return_code }))
 }
 
+#[actix_web::patch("")]
+async fn patch_area(
+    (state, request, _user): (web::Data<AppData<'_>>, web::Json<UpdateAreaRequest>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::areas;
+
+    ResponseError::length_limit_check("Name", &request.name, 3, 128)?;
+
+    let mut connection = state.connect_database();
+
+    match diesel::update(areas::table.filter(areas::code.eq(&request.code)))
+        .set(areas::name.eq(&request.name))
+        .execute(&mut connection)
+    {
+        Err(_) => Err(ResponseError::server_error()),
+        Ok(0) => Err(ResponseError::value_do_not_exist("Area")),
+        Ok(_) => Ok(HttpResponse::NoContent()),
+    }
+}
+
 #[actix_web::delete("/remove")]
 async fn delete_areas(
-    (state, query, user): (web::Data<AppData<'_>>, web::Query<AreaRemoveQuery>, UserClaims),
+    (state, query, _user): (web::Data<AppData<'_>>, web::Query<AreaRemoveQuery>, UserClaims),
 ) -> super::Result<impl Responder> {
     use crate::schema::areas;
 
-    if user.assigned_role != UserRole::SecurityHead {
-        return Err(crate::logging::ResponseError::unauthorized(user));
-    }
     let mut connection = state.connect_database();
 
-    diesel::delete(areas::table.filter(areas::code.eq(&query.code)))
-        .execute(&mut connection)
-        .unwrap();
+    if query.archive == Some(true) {
+        return match archive_area(&mut connection, &query.code, Utc::now().naive_utc()) {
+            Err(_) => Err(ResponseError::server_error()),
+            Ok(0) => Err(ResponseError::value_do_not_exist("Area")),
+            Ok(_) => Ok(HttpResponse::NoContent().finish()),
+        };
+    }
+
+    let dependents = AreaDependents::count(&mut connection, &query.code)
+        .or(Err(ResponseError::server_error()))?;
+
+    if !dependents.is_empty() {
+        return Ok(area_in_use(&dependents));
+    }
+
+    // the roster and missed shifts of the area go with it
+    match diesel::delete(areas::table.filter(areas::code.eq(&query.code))).execute(&mut connection) {
+        Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _)) => {
+            let dependents = AreaDependents::count(&mut connection, &query.code)
+                .or(Err(ResponseError::server_error()))?;
+
+            Ok(area_in_use(&dependents))
+        }
+        Err(_) => Err(ResponseError::server_error()),
+        Ok(0) => Err(ResponseError::value_do_not_exist("Area")),
+        Ok(_) => Ok(HttpResponse::NoContent().finish()),
+    }
+}
+
+fn area_in_use(dependents: &AreaDependents) -> HttpResponse {
+    HttpResponse::Conflict()
+        .content_type("application/json")
+        .body(
+            serde_json::json!({
+                "message": "Area is still in use, archive it instead",
+                "dependents": dependents,
+            })
+            .to_string(),
+        )
+}
 
-    Ok(HttpResponse::NoContent())
+// Keeps the area and its history but takes it out of service. Gives 0 when there is no such area
+fn archive_area(
+    connection: &mut PgConnection,
+    area_code: &str,
+    now: NaiveDateTime,
+) -> diesel::QueryResult<usize> {
+    use crate::schema::{areas, cameras, guard_assignments, users};
+
+    connection.transaction(|connection| {
+        let archived = diesel::update(
+            areas::table
+                .filter(areas::code.eq(area_code))
+                .filter(areas::archived_time.is_null()),
+        )
+        .set(areas::archived_time.eq(now))
+        .execute(connection)?;
+
+        // an area archived before is left as it is, so archiving is safe to repeat
+        if archived == 0 {
+            return areas::table
+                .filter(areas::code.eq(area_code))
+                .count()
+                .get_result::<i64>(connection)
+                .map(|areas| areas as usize);
+        }
+
+        diesel::update(users::table.filter(users::assigned_area.eq(area_code)))
+            .set(users::assigned_area.eq(None::<String>))
+            .execute(connection)?;
+
+        diesel::update(cameras::table.filter(cameras::area_code.eq(area_code)))
+            .set(cameras::deactivated.eq(true))
+            .execute(connection)?;
+
+        // shifts already worked keep their attendance, the rest of the roster is dropped
+        diesel::delete(
+            guard_assignments::table
+                .filter(guard_assignments::area_code.eq(area_code))
+                .filter(guard_assignments::start_time.gt(now)),
+        )
+        .execute(connection)?;
+
+        diesel::update(
+            guard_assignments::table
+                .filter(guard_assignments::area_code.eq(area_code))
+                .filter(guard_assignments::recurrence.ne(Recurrence::Once))
+                .filter(
+                    guard_assignments::repeat_until
+                        .is_null()
+                        .or(guard_assignments::repeat_until.gt(now.date())),
+                ),
+        )
+        .set(guard_assignments::repeat_until.eq(now.date()))
+        .execute(connection)?;
+
+        Ok(archived)
+    })
 }
 
 #[actix_web::patch("/assign")]
 async fn patch_assign(
-    (state, request, user): (web::Data<AppData<'_>>, web::Json<AssignRequest>, UserClaims),
+    (state, request, _user): (web::Data<AppData<'_>>, web::Json<AssignRequest>, UserClaims),
 ) -> super::Result<impl Responder> {
     use crate::schema::users::dsl::*;
 
-    if user.assigned_role != UserRole::SecurityHead {
-        return Err(crate::logging::ResponseError::unauthorized(user));
-    }
     let mut connection = state.connect_database();
 
     let role: UserRole = users
@@ -201,6 +397,10 @@ async fn patch_assign(
         ));
     }
 
+    if let Some(area_code) = &request.area_code {
+        active_area(&mut connection, area_code)?;
+    }
+
     diesel::update(users.filter(id.eq(request.user_id)))
         .set(assigned_area.eq(&request.area_code))
         .execute(&mut connection)
@@ -216,21 +416,39 @@ async fn patch_assign(
 
 #[actix_web::post("/camera")]
 async fn post_camera(
-    (state, request, user): (web::Data<AppData<'_>>, web::Json<CameraAddRequest>, UserClaims),
+    (state, request, _user): (web::Data<AppData<'_>>, web::Json<CameraAddRequest>, UserClaims),
 ) -> super::Result<impl Responder> {
     use crate::schema::cameras;
 
-    if user.assigned_role == UserRole::SecurityGuard {
-        return Err(crate::logging::ResponseError::unauthorized(user));
-    }
-
+    let model = request.model()?;
+    let userinfo = match &request.username {
+        Some(username) => Some(crate::onvif::userinfo(
+            username,
+            request.password.as_deref().unwrap_or_default(),
+        )),
+        None => split_credentials(&request.camera_url).1,
+    };
     let mut connection = state.connect_database();
 
-    match diesel::insert_into(cameras::table)
-        .values(request.model()?)
-        .returning(cameras::id)
-        .get_result::<uuid::Uuid>(&mut connection)
-    {
+    active_area(&mut connection, &request.area_code)?;
+
+    match connection.transaction(|connection| {
+        let id = diesel::insert_into(cameras::table)
+            .values(model)
+            .returning(cameras::id)
+            .get_result::<uuid::Uuid>(connection)?;
+
+        if let Some(userinfo) = &userinfo {
+            let credential = state
+                .credential_vault()
+                .seal(id, userinfo)
+                .ok_or(diesel::result::Error::RollbackTransaction)?;
+
+            credential_vault::store(connection, &credential)?;
+        }
+
+        Ok(id)
+    }) {
         Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
             Err(crate::logging::ResponseError::conflict_field("Name"))
         }
@@ -247,44 +465,219 @@ async fn post_camera(
     }
 }
 
+#[actix_web::get("/camera")]
+async fn get_cameras(
+    (state, query, _user): (web::Data<AppData<'_>>, web::Query<CameraListQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::{camera_credentials, cameras};
+
+    let mut filtered = cameras::table
+        .left_join(camera_credentials::table)
+        .select((
+            cameras::id,
+            cameras::label,
+            cameras::area_code,
+            cameras::camera_url,
+            cameras::deactivated,
+            camera_credentials::camera_id.nullable().is_not_null(),
+            cameras::onvif_url,
+            cameras::onvif_profile,
+            cameras::recording_quota_mb,
+        ))
+        .into_boxed();
+
+    if let Some(area_code) = &query.area_code {
+        filtered = filtered.filter(cameras::area_code.eq(area_code));
+    }
+
+    if let Some(deactivated) = query.deactivated {
+        filtered = filtered.filter(cameras::deactivated.eq(deactivated));
+    }
+
+    let mut connection = state.connect_database();
+
+    let list: Vec<CameraSelect> = filtered
+        .order_by((cameras::area_code, cameras::label))
+        .load(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(serde_json::to_string(&list)
+        .or(Err(ResponseError::server_error()))?
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
+#[actix_web::get("/camera/{id}")]
+async fn get_camera(
+    (state, id, _user): (web::Data<AppData<'_>>, web::Path<uuid::Uuid>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::{areas, camera_credentials, cameras};
+
+    let mut connection = state.connect_database();
+
+    let (camera, area_name): (CameraSelect, String) = cameras::table
+        .inner_join(areas::table)
+        .left_join(camera_credentials::table)
+        .filter(cameras::id.eq(id.into_inner()))
+        .select((
+            (
+                cameras::id,
+                cameras::label,
+                cameras::area_code,
+                cameras::camera_url,
+                cameras::deactivated,
+                camera_credentials::camera_id.nullable().is_not_null(),
+                cameras::onvif_url,
+                cameras::onvif_profile,
+                cameras::recording_quota_mb,
+            ),
+            areas::name,
+        ))
+        .first(&mut connection)
+        .optional()
+        .or(Err(ResponseError::server_error()))?
+        .ok_or_else(|| ResponseError::value_do_not_exist("Camera"))?;
+
+    let mut camera = serde_json::to_value(&camera).or(Err(ResponseError::server_error()))?;
+    camera["area-name"] = area_name.into();
+
+    Ok(camera
+        .to_string()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
 #[actix_web::patch("/camera")]
 async fn patch_camera(
-    (state, request, user): (
+    (state, request, _user): (
         web::Data<AppData<'_>>,
         web::Json<CameraModifyRequest>,
         UserClaims,
     ),
 ) -> super::Result<impl Responder> {
-    use crate::schema::cameras;
+    use crate::schema::{camera_credentials, cameras};
+
+    let request = request.into_inner();
 
-    if user.assigned_role == UserRole::SecurityGuard {
-        return Err(crate::logging::ResponseError::unauthorized(user));
+    if let Some(label) = &request.label {
+        ResponseError::length_limit_check("Label", label, 3, 15)?;
     }
 
-    let mut connection = state.connect_database();
+    if let Some(camera_url) = &request.camera_url {
+        ResponseError::length_limit_check("Camera URL", camera_url, 10, 512)?;
+    }
 
-    let result = if let (Some(label), Some(camera_url)) = (&request.label, &request.camera_url) {
-        diesel::update(cameras::table.filter(cameras::id.eq(request.camera_id)))
-            .set((cameras::label.eq(label), cameras::camera_url.eq(camera_url)))
-            .execute(&mut connection)
-    } else if let Some(label) = &request.label {
-        diesel::update(cameras::table.filter(cameras::id.eq(request.camera_id)))
-            .set(cameras::label.eq(label))
-            .execute(&mut connection)
-    } else if let Some(camera_url) = &request.camera_url {
-        diesel::update(cameras::table.filter(cameras::id.eq(request.camera_id)))
-            .set(cameras::camera_url.eq(camera_url))
-            .execute(&mut connection)
-    } else {
+    if let Some(onvif_url) = request.onvif_url.as_deref().filter(|url| !url.is_empty()) {
+        ResponseError::length_limit_check("ONVIF URL", onvif_url, 10, 512)?;
+    }
+
+    if let Some(onvif_profile) = request.onvif_profile.as_deref().filter(|profile| !profile.is_empty()) {
+        ResponseError::length_limit_check("ONVIF profile", onvif_profile, 1, 64)?;
+    }
+
+    if matches!(request.recording_quota_mb, Some(quota) if quota < 0) {
+        return Err(invalid_quota());
+    }
+
+    // a url without credentials keeps the stored ones, since responses never show them
+    let (camera_url, userinfo) = match request.camera_url.as_deref().map(split_credentials) {
+        Some((camera_url, userinfo)) => (Some(camera_url), userinfo),
+        None => (None, None),
+    };
+    let clear_credentials = request.clear_credentials == Some(true);
+
+    let changes = CameraUpdate {
+        label: request.label,
+        area_code: request.area_code,
+        camera_url,
+        deactivated: request.deactivated,
+        // an empty value takes the camera off ONVIF
+        onvif_url: request
+            .onvif_url
+            .map(|url| Some(split_credentials(&url).0).filter(|url| !url.is_empty())),
+        onvif_profile: request
+            .onvif_profile
+            .map(|profile| Some(profile).filter(|profile| !profile.is_empty())),
+        // zero goes back to RECORDING_QUOTA_MB
+        recording_quota_mb: request
+            .recording_quota_mb
+            .map(|quota| Some(quota).filter(|quota| *quota > 0)),
+    };
+
+    let camera_changed = changes.label.is_some()
+        || changes.area_code.is_some()
+        || changes.camera_url.is_some()
+        || changes.deactivated.is_some()
+        || changes.onvif_url.is_some()
+        || changes.onvif_profile.is_some()
+        || changes.recording_quota_mb.is_some();
+
+    if !camera_changed && !clear_credentials {
         return Err(crate::logging::ResponseError::new(
             "Nothing to do",
             "Nothing to do",
             LogLevel::Information,
             StatusCode::NOT_ACCEPTABLE,
         ));
-    };
+    }
+
+    let mut connection = state.connect_database();
+
+    // a camera that ends up running has to be in an area still in use
+    if changes.area_code.is_some() || changes.deactivated == Some(false) {
+        let area_code = match &changes.area_code {
+            Some(area_code) => area_code.clone(),
+            None => cameras::table
+                .filter(cameras::id.eq(request.camera_id))
+                .select(cameras::area_code)
+                .first(&mut connection)
+                .optional()
+                .or(Err(ResponseError::server_error()))?
+                .ok_or_else(|| ResponseError::value_do_not_exist("Camera"))?,
+        };
+
+        active_area(&mut connection, &area_code)?;
+    }
+
+    match connection.transaction(|connection| {
+        let row_count = if camera_changed {
+            diesel::update(cameras::table.filter(cameras::id.eq(request.camera_id)))
+                .set(&changes)
+                .execute(connection)?
+        } else {
+            cameras::table
+                .filter(cameras::id.eq(request.camera_id))
+                .count()
+                .get_result::<i64>(connection)? as usize
+        };
+
+        if row_count == 0 {
+            return Ok(0);
+        }
+
+        if clear_credentials {
+            diesel::delete(
+                camera_credentials::table.filter(camera_credentials::camera_id.eq(request.camera_id)),
+            )
+            .execute(connection)?;
+        }
+
+        if let Some(userinfo) = &userinfo {
+            let credential = state
+                .credential_vault()
+                .seal(request.camera_id, userinfo)
+                .ok_or(diesel::result::Error::RollbackTransaction)?;
 
-    match result {
+            credential_vault::store(connection, &credential)?;
+        }
+
+        Ok(row_count)
+    }) {
+        Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            Err(ResponseError::conflict_field("Label"))
+        }
         Err(_) => Err(crate::logging::ResponseError::server_error()),
         Ok(row_count) => {
             if row_count == 0 {
@@ -298,7 +691,7 @@ async fn patch_camera(
 
 #[actix_web::delete("/camera")]
 async fn delete_camera(
-    (state, query, user): (
+    (state, query, _user): (
         web::Data<AppData<'_>>,
         web::Query<CameraRemoveQuery>,
         UserClaims,
@@ -306,10 +699,6 @@ async fn delete_camera(
 ) -> super::Result<impl Responder> {
     use crate::schema::cameras;
 
-    if user.assigned_role == UserRole::SecurityGuard {
-        return Err(crate::logging::ResponseError::unauthorized(user));
-    }
-
     let mut connection = state.connect_database();
 
     match diesel::delete(cameras::table.filter(cameras::id.eq(query.camera_id)))
@@ -326,13 +715,95 @@ async fn delete_camera(
     }
 }
 
+#[actix_web::get("/{code}")]
+async fn get_area(
+    (state, code, _user): (web::Data<AppData<'_>>, web::Path<String>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::{areas, cameras, guard_assignments, users, violations};
+
+    let now = Utc::now().naive_utc();
+    let mut connection = state.connect_database();
+
+    let (name, archived_time): (String, Option<NaiveDateTime>) = areas::table
+        .filter(areas::code.eq(code.as_str()))
+        .select((areas::name, areas::archived_time))
+        .first(&mut connection)
+        .optional()
+        .or(Err(ResponseError::server_error()))?
+        .ok_or_else(|| ResponseError::value_do_not_exist("Area"))?;
+
+    let area_cameras: Vec<AreaCamera> = cameras::table
+        .filter(cameras::area_code.eq(code.as_str()))
+        .select((cameras::id, cameras::label, cameras::deactivated))
+        .order_by(cameras::label)
+        .load(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    let guards: Vec<UserBasicSelect> = users::table
+        .filter(users::assigned_area.eq(code.as_str()))
+        .filter(users::deactivated.eq(false))
+        .select((users::id, users::first_name, users::last_name))
+        .load(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    let on_shift: Vec<uuid::Uuid> = GuardAssignment::overlapping(now, now + Duration::nanoseconds(1))
+        .filter(guard_assignments::area_code.eq(code.as_str()))
+        .load::<GuardAssignment>(&mut connection)
+        .or(Err(ResponseError::server_error()))?
+        .into_iter()
+        .filter(|assignment| assignment.covers(now))
+        .map(|assignment| assignment.user_id)
+        .collect();
+
+    let on_shift: Vec<UserBasicSelect> = users::table
+        .filter(users::id.eq_any(on_shift))
+        .select((users::id, users::first_name, users::last_name))
+        .load(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    let mut recent = serde_json::Map::new();
+
+    for (window, days) in [("day", 1), ("week", 7), ("month", 30)] {
+        let count: i64 = violations::table
+            .filter(violations::area_code.eq(code.as_str()))
+            .filter(violations::date_time.ge(now - Duration::days(days)))
+            .count()
+            .get_result(&mut connection)
+            .or(Err(ResponseError::server_error()))?;
+
+        recent.insert(window.into(), count.into());
+    }
+
+    Ok(serde_json::json!({
+        "code": code.as_str(),
+        "name": name,
+        "archived-time": archived_time,
+        "cameras": area_cameras,
+        "guards": guards,
+        "on-shift": on_shift,
+        "violations": recent,
+    })
+    .to_string()
+    .customize()
+    .insert_header(("Content-Type", "application/json"))
+    .with_status(StatusCode::OK))
+}
+
 pub fn scope() -> actix_web::Scope {
     web::scope("/areas")
         .service(post_create)
         .service(get_list)
         .service(patch_assign)
         .service(delete_areas)
+        .service(get_cameras)
+        .configure(super::onvif::configure)
+        .configure(super::recordings::configure)
+        .service(get_camera)
         .service(post_camera)
         .service(patch_camera)
         .service(delete_camera)
+        .service(patch_area)
+        .configure(super::roster::configure)
+        // last, so the fixed paths above are not taken for an area code
+        .service(get_area)
 }
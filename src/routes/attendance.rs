@@ -0,0 +1,315 @@
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, put, web};
+use actix_web::{HttpResponse, Responder};
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::result::DatabaseErrorKind;
+use diesel::{ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::data::{totp, AppData};
+use crate::logging::{LogLevel, ResponseError};
+use crate::models::{AttendanceSelect, GuardAssignment, MissedShift, Permission, UserClaims};
+
+#[derive(Deserialize)]
+struct CheckInRequest {
+    #[serde(alias = "area-code")]
+    area_code: String,
+    code: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CheckOutRequest {
+    code: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AttendanceQuery {
+    #[serde(alias = "user-id")]
+    user_id: Option<uuid::Uuid>,
+    #[serde(alias = "area-code")]
+    area_code: Option<String>,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize)]
+struct MissedQuery {
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize)]
+struct AreaQuery {
+    #[serde(alias = "area-code")]
+    area_code: String,
+}
+
+// None when the area shows no code, then any check-in there is taken on trust
+fn area_secret(connection: &mut PgConnection, area_code: &str) -> super::Result<Option<Vec<u8>>> {
+    use crate::schema::areas;
+
+    areas::table
+        .filter(areas::code.eq(area_code))
+        .select(areas::checkin_secret)
+        .first(connection)
+        .optional()
+        .or(Err(ResponseError::server_error()))?
+        .ok_or_else(|| ResponseError::value_do_not_exist("Area"))
+}
+
+fn verify_code(secret: Option<&[u8]>, code: Option<&str>) -> super::Result<bool> {
+    let secret = match secret {
+        Some(secret) => secret,
+        None => return Ok(false),
+    };
+
+    match code.and_then(|code| totp::verify(secret, code, Utc::now().timestamp(), None)) {
+        Some(_) => Ok(true),
+        None => Err(ResponseError::new(
+            "Invalid check-in code",
+            "Invalid check-in code",
+            LogLevel::Information,
+            StatusCode::UNAUTHORIZED,
+        )),
+    }
+}
+
+#[post("/check-in")]
+async fn post_check_in(
+    (state, request, user): (web::Data<AppData<'_>>, web::Json<CheckInRequest>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::attendance;
+
+    let now = Utc::now().naive_utc();
+    let mut connection = state.connect_database();
+
+    let secret = area_secret(&mut connection, &request.area_code)?;
+    let code_verified = verify_code(secret.as_deref(), request.code.as_deref())?;
+
+    // a check-in off the roster is still kept, it just matches no shift
+    let early = Duration::minutes(state.attendance_policy().early_minutes);
+    let shift = GuardAssignment::shift_at(&mut connection, user.user_id, &request.area_code, now, early)
+        .or(Err(ResponseError::server_error()))?;
+
+    match diesel::insert_into(attendance::table)
+        .values((
+            attendance::user_id.eq(user.user_id),
+            attendance::area_code.eq(&request.area_code),
+            attendance::assignment_id.eq(shift.map(|(id, _)| id)),
+            attendance::shift_start.eq(shift.map(|(_, start)| start)),
+            attendance::check_in_time.eq(now),
+            attendance::code_verified.eq(code_verified),
+        ))
+        .returning(attendance::id)
+        .get_result::<uuid::Uuid>(&mut connection)
+    {
+        Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            Err(ResponseError::new(
+                "Already checked in",
+                "Already checked in",
+                LogLevel::Information,
+                StatusCode::CONFLICT,
+            ))
+        }
+        Err(_) => Err(ResponseError::server_error()),
+        Ok(id) => Ok(json!({
+            "id": id,
+            "assignment-id": shift.map(|(id, _)| id),
+            "shift-start": shift.map(|(_, start)| start),
+        })
+        .to_string()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK)),
+    }
+}
+
+#[post("/check-out")]
+async fn post_check_out(
+    (state, request, user): (web::Data<AppData<'_>>, web::Json<CheckOutRequest>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::attendance;
+
+    let now = Utc::now().naive_utc();
+    let mut connection = state.connect_database();
+
+    let (id, area_code): (uuid::Uuid, String) = attendance::table
+        .filter(attendance::user_id.eq(user.user_id))
+        .filter(attendance::check_out_time.is_null())
+        .select((attendance::id, attendance::area_code))
+        .first(&mut connection)
+        .optional()
+        .or(Err(ResponseError::server_error()))?
+        .ok_or_else(|| ResponseError::value_do_not_exist("Check-in"))?;
+
+    let secret = area_secret(&mut connection, &area_code)?;
+    verify_code(secret.as_deref(), request.code.as_deref())?;
+
+    diesel::update(attendance::table.filter(attendance::id.eq(id)))
+        .set(attendance::check_out_time.eq(now))
+        .execute(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+#[get("")]
+async fn get_attendance(
+    (state, query, user): (web::Data<AppData<'_>>, web::Query<AttendanceQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::attendance;
+
+    // guards only see their own records
+    let user_id = match query.user_id {
+        _ if user.can(Permission::AttendanceRead) => query.user_id,
+        Some(user_id) if user_id != user.user_id => return Err(ResponseError::unauthorized(user)),
+        _ => Some(user.user_id),
+    };
+
+    let mut filtered = attendance::table.into_boxed();
+
+    if let Some(user_id) = user_id {
+        filtered = filtered.filter(attendance::user_id.eq(user_id));
+    }
+
+    if let Some(area_code) = &query.area_code {
+        filtered = filtered.filter(attendance::area_code.eq(area_code));
+    }
+
+    if let Some(from) = query.from {
+        filtered = filtered.filter(attendance::check_in_time.ge(from));
+    }
+
+    if let Some(to) = query.to {
+        filtered = filtered.filter(attendance::check_in_time.lt(to));
+    }
+
+    let mut connection = state.connect_database();
+
+    let records: Vec<AttendanceSelect> = filtered
+        .order_by(attendance::check_in_time.desc())
+        .load(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(json!({ "records": records })
+        .to_string()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
+#[get("/missed")]
+async fn get_missed(
+    (state, query, _user): (web::Data<AppData<'_>>, web::Query<MissedQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::missed_shifts;
+
+    let mut filtered = missed_shifts::table.into_boxed();
+
+    if let Some(from) = query.from {
+        filtered = filtered.filter(missed_shifts::shift_start.ge(from));
+    }
+
+    if let Some(to) = query.to {
+        filtered = filtered.filter(missed_shifts::shift_start.lt(to));
+    }
+
+    let mut connection = state.connect_database();
+
+    let shifts: Vec<MissedShift> = filtered
+        .order_by(missed_shifts::shift_start.desc())
+        .load(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(json!({ "shifts": shifts })
+        .to_string()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
+// The code to put up at the post, it changes with every step
+#[get("/code")]
+async fn get_code(
+    (state, query, _user): (web::Data<AppData<'_>>, web::Query<AreaQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    let mut connection = state.connect_database();
+
+    let secret = area_secret(&mut connection, &query.area_code)?
+        .ok_or_else(|| ResponseError::value_do_not_exist("Check-in code"))?;
+
+    let (code, expires_in) = totp::current_code(&secret, Utc::now().timestamp());
+
+    Ok(json!({
+        "code": code,
+        "expires-in": expires_in,
+    })
+    .to_string()
+    .customize()
+    .insert_header(("Content-Type", "application/json"))
+    .insert_header(("Cache-Control", "no-store"))
+    .with_status(StatusCode::OK))
+}
+
+// Replaces the secret, so a display set up with the old one stops working
+#[put("/code")]
+async fn put_code(
+    (state, query, _user): (web::Data<AppData<'_>>, web::Query<AreaQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::areas;
+
+    let secret = totp::generate_secret().ok_or_else(ResponseError::server_error)?;
+    let mut connection = state.connect_database();
+
+    match diesel::update(areas::table.filter(areas::code.eq(&query.area_code)))
+        .set(areas::checkin_secret.eq(Some(&secret)))
+        .execute(&mut connection)
+    {
+        Err(_) => Err(ResponseError::server_error()),
+        Ok(0) => Err(ResponseError::value_do_not_exist("Area")),
+        Ok(_) => Ok(json!({
+            "secret": totp::encode_secret(&secret),
+            "uri": totp::otpauth_uri(
+                &secret,
+                &state.login_policy().totp_issuer,
+                &format!("Area {}", query.area_code),
+            ),
+        })
+        .to_string()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .insert_header(("Cache-Control", "no-store"))
+        .with_status(StatusCode::OK)),
+    }
+}
+
+#[delete("/code")]
+async fn delete_code(
+    (state, query, _user): (web::Data<AppData<'_>>, web::Query<AreaQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::areas;
+
+    let mut connection = state.connect_database();
+
+    match diesel::update(areas::table.filter(areas::code.eq(&query.area_code)))
+        .set(areas::checkin_secret.eq(None::<Vec<u8>>))
+        .execute(&mut connection)
+    {
+        Err(_) => Err(ResponseError::server_error()),
+        Ok(0) => Err(ResponseError::value_do_not_exist("Area")),
+        Ok(_) => Ok(HttpResponse::NoContent()),
+    }
+}
+
+pub fn scope() -> actix_web::Scope {
+    web::scope("/attendance")
+        .service(post_check_in)
+        .service(post_check_out)
+        .service(get_attendance)
+        .service(get_missed)
+        .service(get_code)
+        .service(put_code)
+        .service(delete_code)
+}
@@ -0,0 +1,226 @@
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, patch, web};
+use actix_web::{HttpResponse, Responder};
+
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl};
+use serde::Deserialize;
+
+use crate::data::AppData;
+use crate::logging::{LogLevel, ResponseError};
+use crate::models::{DeviceOs, DeviceSelect, DeviceStatus, Permission, UserClaims, UserSelect};
+
+use super::users::{end_sessions, session_owner};
+
+#[derive(Deserialize)]
+struct DevicesQuery {
+    #[serde(alias = "user-id")]
+    user_id: Option<uuid::Uuid>,
+}
+
+#[derive(Deserialize)]
+struct DeviceQuery {
+    id: uuid::Uuid,
+}
+
+#[derive(Deserialize)]
+struct DeviceStatusRequest {
+    status: DeviceStatus,
+}
+
+// Registers the device on first sight and returns its id once it may open a session
+pub(crate) fn admit_device(
+    connection: &mut PgConnection,
+    user: &UserSelect,
+    device_os: DeviceOs,
+    device_name: &str,
+    device_hash: &[u8],
+    now: NaiveDateTime,
+) -> super::Result<uuid::Uuid> {
+    use crate::schema::devices;
+
+    let (id, status): (uuid::Uuid, DeviceStatus) = diesel::insert_into(devices::table)
+        .values((
+            devices::user_id.eq(user.id),
+            devices::device_hash.eq(device_hash),
+            devices::device_os.eq(device_os),
+            devices::device_name.eq(device_name),
+            devices::device_status.eq(DeviceStatus::Pending),
+            devices::created_time.eq(now),
+            devices::last_seen.eq(now),
+        ))
+        .on_conflict((devices::user_id, devices::device_hash))
+        .do_update()
+        .set((
+            devices::device_os.eq(device_os),
+            devices::device_name.eq(device_name),
+            devices::last_seen.eq(now),
+        ))
+        .returning((devices::id, devices::device_status))
+        .get_result(connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    match status {
+        DeviceStatus::Trusted => Ok(id),
+        DeviceStatus::Blocked => Err(ResponseError::new(
+            format!("Login of user {} from blocked device {id}", user.id),
+            "This device is blocked",
+            LogLevel::Warning,
+            StatusCode::FORBIDDEN,
+        )),
+        DeviceStatus::Pending => {
+            use crate::schema::sessions;
+
+            let other_devices: i64 = devices::table
+                .filter(devices::user_id.eq(user.id))
+                .filter(devices::id.ne(id))
+                .count()
+                .get_result(connection)
+                .or(Err(ResponseError::server_error()))?;
+
+            // sessions from before devices were registered count too, otherwise whoever
+            // logged in first after the upgrade would own the account
+            let sessions: i64 = sessions::table
+                .filter(sessions::user_id.eq(user.id))
+                .count()
+                .get_result(connection)
+                .or(Err(ResponseError::server_error()))?;
+
+            // a second factor confirmed in this login vouches for the device, and the
+            // first device of an account never used before has nobody who could approve it
+            if !user.totp_enabled && (other_devices > 0 || sessions > 0) {
+                return Err(ResponseError::new(
+                    format!("Login of user {} from unapproved device {id}", user.id),
+                    "This device awaits approval from a trusted device or an administrator",
+                    LogLevel::Information,
+                    StatusCode::FORBIDDEN,
+                ));
+            }
+
+            diesel::update(devices::table.filter(devices::id.eq(id)))
+                .set(devices::device_status.eq(DeviceStatus::Trusted))
+                .execute(connection)
+                .or(Err(ResponseError::server_error()))?;
+
+            Ok(id)
+        }
+    }
+}
+
+fn device_owner(connection: &mut PgConnection, id: uuid::Uuid) -> super::Result<(uuid::Uuid, DeviceStatus)> {
+    use crate::schema::devices;
+
+    devices::table
+        .filter(devices::id.eq(id))
+        .select((devices::user_id, devices::device_status))
+        .first(connection)
+        .optional()
+        .or(Err(ResponseError::server_error()))?
+        .ok_or_else(|| ResponseError::value_do_not_exist("Device"))
+}
+
+fn device_sessions(connection: &mut PgConnection, id: uuid::Uuid) -> super::Result<Vec<uuid::Uuid>> {
+    use crate::schema::sessions;
+
+    sessions::table
+        .filter(sessions::device_id.eq(id))
+        .filter(sessions::logout_time.is_null())
+        .select(sessions::id)
+        .load(connection)
+        .or(Err(ResponseError::server_error()))
+}
+
+#[get("/devices")]
+async fn get_devices(
+    (state, query, user): (web::Data<AppData<'_>>, web::Query<DevicesQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::devices;
+
+    let user_id = session_owner(&user, query.user_id)?;
+    let mut connection = state.connect_database();
+
+    let list: Vec<DeviceSelect> = devices::table
+        .filter(devices::user_id.eq(user_id))
+        .order_by(devices::last_seen.desc())
+        .select((
+            devices::id,
+            devices::device_os,
+            devices::device_name,
+            devices::device_status,
+            devices::created_time,
+            devices::last_seen,
+        ))
+        .load(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(serde_json::to_string(&list)
+        .unwrap()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
+#[patch("/devices")]
+async fn patch_device(
+    (state, query, body, user): (
+        web::Data<AppData<'_>>,
+        web::Query<DeviceQuery>,
+        web::Json<DeviceStatusRequest>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    use crate::schema::devices;
+
+    let mut connection = state.connect_database();
+    let (owner, current) = device_owner(&mut connection, query.id)?;
+
+    session_owner(&user, Some(owner))?;
+
+    // owners approve and block their devices, lifting a block is left to administrators
+    if current == DeviceStatus::Blocked && !user.can(Permission::DevicesUnblock) {
+        return Err(ResponseError::unauthorized(user));
+    }
+
+    diesel::update(devices::table.filter(devices::id.eq(query.id)))
+        .set(devices::device_status.eq(body.status))
+        .execute(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    if body.status != DeviceStatus::Trusted {
+        let sessions = device_sessions(&mut connection, query.id)?;
+        end_sessions(&mut connection, &sessions)?;
+    }
+
+    Ok(HttpResponse::NoContent())
+}
+
+#[delete("/devices")]
+async fn delete_device(
+    (state, query, user): (web::Data<AppData<'_>>, web::Query<DeviceQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::devices;
+
+    let mut connection = state.connect_database();
+    let (owner, current) = device_owner(&mut connection, query.id)?;
+
+    session_owner(&user, Some(owner))?;
+
+    // forgetting a blocked device would let it come back as pending
+    if current == DeviceStatus::Blocked && !user.can(Permission::DevicesUnblock) {
+        return Err(ResponseError::unauthorized(user));
+    }
+
+    // its sessions go with it
+    diesel::delete(devices::table.filter(devices::id.eq(query.id)))
+        .execute(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+pub(crate) fn configure(config: &mut web::ServiceConfig) {
+    config
+        .service(get_devices)
+        .service(patch_device)
+        .service(delete_device);
+}
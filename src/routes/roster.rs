@@ -0,0 +1,228 @@
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, web};
+use actix_web::{HttpResponse, Responder};
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use diesel::result::DatabaseErrorKind;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::data::AppData;
+use crate::logging::{LogLevel, ResponseError};
+use crate::models::{GuardAssignment, Permission, Recurrence, UserClaims, UserRole};
+
+// Longest window a roster is laid out for in one request
+const MAX_WINDOW_DAYS: i64 = 31;
+
+#[derive(Deserialize)]
+struct RosterQuery {
+    #[serde(alias = "user-id")]
+    user_id: Option<uuid::Uuid>,
+    #[serde(alias = "area-code")]
+    area_code: Option<String>,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize)]
+struct AssignmentRequest {
+    #[serde(alias = "user-id")]
+    user_id: uuid::Uuid,
+    #[serde(alias = "area-code")]
+    area_code: String,
+    #[serde(alias = "start-time")]
+    start_time: NaiveDateTime,
+    #[serde(alias = "end-time")]
+    end_time: NaiveDateTime,
+    #[serde(default = "once")]
+    recurrence: Recurrence,
+    #[serde(alias = "repeat-until")]
+    repeat_until: Option<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+struct AssignmentQuery {
+    id: uuid::Uuid,
+}
+
+#[derive(Serialize)]
+struct Shift<'a> {
+    #[serde(rename = "assignment-id")]
+    assignment_id: uuid::Uuid,
+    #[serde(rename = "user-id")]
+    user_id: uuid::Uuid,
+    #[serde(rename = "area-code")]
+    area_code: &'a str,
+    #[serde(rename = "start-time")]
+    start_time: NaiveDateTime,
+    #[serde(rename = "end-time")]
+    end_time: NaiveDateTime,
+}
+
+fn once() -> Recurrence {
+    Recurrence::Once
+}
+
+fn invalid_roster(message: &str) -> ResponseError {
+    ResponseError::new(message, message, LogLevel::Information, StatusCode::BAD_REQUEST)
+}
+
+#[get("/roster")]
+async fn get_roster(
+    (state, query, user): (web::Data<AppData<'_>>, web::Query<RosterQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::guard_assignments;
+
+    // guards only see their own shifts
+    let user_id = match query.user_id {
+        _ if user.can(Permission::RostersRead) => query.user_id,
+        Some(user_id) if user_id != user.user_id => return Err(ResponseError::unauthorized(user)),
+        _ => Some(user.user_id),
+    };
+
+    let from = query.from.unwrap_or_else(|| Utc::now().naive_utc());
+    let to = query.to.unwrap_or(from + Duration::days(7));
+
+    if to <= from || to - from > Duration::days(MAX_WINDOW_DAYS) {
+        return Err(invalid_roster("The roster window must be between 0 and 31 days long"));
+    }
+
+    let mut filtered = GuardAssignment::overlapping(from, to);
+
+    if let Some(user_id) = user_id {
+        filtered = filtered.filter(guard_assignments::user_id.eq(user_id));
+    }
+
+    if let Some(area_code) = &query.area_code {
+        filtered = filtered.filter(guard_assignments::area_code.eq(area_code));
+    }
+
+    let mut connection = state.connect_database();
+
+    let assignments: Vec<GuardAssignment> = filtered
+        .order_by(guard_assignments::start_time)
+        .load(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    let mut shifts: Vec<Shift> = assignments
+        .iter()
+        .flat_map(|assignment| {
+            assignment.shifts(from, to).map(|(start_time, end_time)| Shift {
+                assignment_id: assignment.id,
+                user_id: assignment.user_id,
+                area_code: &assignment.area_code,
+                start_time,
+                end_time,
+            })
+        })
+        .collect();
+
+    shifts.sort_by_key(|shift| shift.start_time);
+
+    let assignments: Vec<&GuardAssignment> = assignments
+        .iter()
+        .filter(|assignment| shifts.iter().any(|shift| shift.assignment_id == assignment.id))
+        .collect();
+
+    Ok(json!({
+        "assignments": assignments,
+        "shifts": shifts,
+    })
+    .to_string()
+    .customize()
+    .insert_header(("Content-Type", "application/json"))
+    .with_status(StatusCode::OK))
+}
+
+#[post("/roster")]
+async fn post_roster(
+    (state, request, _user): (web::Data<AppData<'_>>, web::Json<AssignmentRequest>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::{guard_assignments, users};
+
+    if request.end_time <= request.start_time
+        || request.end_time - request.start_time > Duration::hours(GuardAssignment::MAX_SHIFT_HOURS)
+    {
+        return Err(invalid_roster("A shift must end after it starts and last at most 24 hours"));
+    }
+
+    match (request.recurrence, request.repeat_until) {
+        (Recurrence::Once, Some(_)) => {
+            return Err(invalid_roster("Only a repeating shift can have an end date"));
+        }
+        (_, Some(until)) if until < request.start_time.date() => {
+            return Err(invalid_roster("A shift cannot stop repeating before it starts"));
+        }
+        _ => (),
+    }
+
+    let mut connection = state.connect_database();
+
+    let role: UserRole = users::table
+        .filter(users::id.eq(request.user_id))
+        .filter(users::deactivated.eq(false))
+        .select(users::assigned_role)
+        .first(&mut connection)
+        .optional()
+        .or(Err(ResponseError::server_error()))?
+        .ok_or_else(|| ResponseError::value_do_not_exist("User"))?;
+
+    if role != UserRole::SecurityGuard {
+        return Err(ResponseError::new(
+            "Invalid user role",
+            "User must be a security guard",
+            LogLevel::Information,
+            StatusCode::NOT_ACCEPTABLE,
+        ));
+    }
+
+    super::areas::active_area(&mut connection, &request.area_code)?;
+
+    match diesel::insert_into(guard_assignments::table)
+        .values((
+            guard_assignments::user_id.eq(request.user_id),
+            guard_assignments::area_code.eq(&request.area_code),
+            guard_assignments::start_time.eq(request.start_time),
+            guard_assignments::end_time.eq(request.end_time),
+            guard_assignments::recurrence.eq(request.recurrence),
+            guard_assignments::repeat_until.eq(request.repeat_until),
+        ))
+        .returning(guard_assignments::id)
+        .get_result::<uuid::Uuid>(&mut connection)
+    {
+        Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _)) => {
+            Err(ResponseError::value_do_not_exist("Area"))
+        }
+        Err(_) => Err(ResponseError::server_error()),
+        Ok(id) => Ok(json!({ "id": id })
+            .to_string()
+            .customize()
+            .insert_header(("Content-Type", "application/json"))
+            .with_status(StatusCode::OK)),
+    }
+}
+
+#[delete("/roster")]
+async fn delete_roster(
+    (state, query, _user): (web::Data<AppData<'_>>, web::Query<AssignmentQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::guard_assignments;
+
+    let mut connection = state.connect_database();
+
+    match diesel::delete(guard_assignments::table.filter(guard_assignments::id.eq(query.id)))
+        .execute(&mut connection)
+    {
+        Err(_) => Err(ResponseError::server_error()),
+        Ok(0) => Err(ResponseError::value_do_not_exist("Assignment")),
+        Ok(_) => Ok(HttpResponse::NoContent()),
+    }
+}
+
+pub(crate) fn configure(config: &mut web::ServiceConfig) {
+    config
+        .service(get_roster)
+        .service(post_roster)
+        .service(delete_roster);
+}
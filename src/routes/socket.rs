@@ -6,14 +6,13 @@ async fn index(
     request: HttpRequest,
     stream: web::Payload,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let app_data = web::Data::<AppData>::from_request(&request, &mut Payload::None)
-        .await
-        .unwrap();
+    let app_data = web::Data::<AppData>::from_request(&request, &mut Payload::None).await?;
 
     let mut notifier = app_data.notifier_mut().await;
     notifier.add_socket(&request, stream).await
 }
 
 pub fn resource() -> actix_web::Resource {
-    web::resource("/socket").to(index)
+    // websocket upgrades are GET requests, nothing else is served here
+    web::resource("/socket").route(web::get().to(index))
 }
@@ -0,0 +1,378 @@
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, put, web};
+use actix_web::{HttpResponse, Responder};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, PgConnection,
+    QueryDsl, RunQueryDsl,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::data::totp;
+use crate::data::AppData;
+use crate::logging::{LogLevel, ResponseError};
+use crate::models::{Permission, TotpPolicy, UserClaims, UserSelect};
+
+#[derive(Deserialize)]
+struct CodeRequest {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct TotpQuery {
+    #[serde(alias = "user-id")]
+    user_id: Option<uuid::Uuid>,
+}
+
+// Accepts a time based code once, or an unused recovery code
+pub(crate) fn verify_login_code(
+    connection: &mut PgConnection,
+    user: &UserSelect,
+    code: &str,
+    now: NaiveDateTime,
+) -> super::Result<bool> {
+    if let Some(secret) = &user.totp_secret {
+        if let Some(step) = totp::verify(secret, code, now.timestamp(), user.totp_last_step) {
+            return accept_step(connection, user.id, step);
+        }
+    }
+
+    use crate::schema::totp_recovery_codes;
+
+    let used = diesel::update(
+        totp_recovery_codes::table
+            .filter(totp_recovery_codes::user_id.eq(user.id))
+            .filter(totp_recovery_codes::code_hash.eq(totp::hash_recovery_code(code)))
+            .filter(totp_recovery_codes::used_time.is_null()),
+    )
+    .set(totp_recovery_codes::used_time.eq(Some(now)))
+    .execute(connection)
+    .or(Err(ResponseError::server_error()))?;
+
+    Ok(used > 0)
+}
+
+// Two requests with the same code race on the step, only one of them gets it
+fn accept_step(connection: &mut PgConnection, user_id: uuid::Uuid, step: i64) -> super::Result<bool> {
+    use crate::schema::users;
+
+    let updated = diesel::update(
+        users::table.filter(users::id.eq(user_id)).filter(
+            users::totp_last_step
+                .is_null()
+                .or(users::totp_last_step.lt(step)),
+        ),
+    )
+    .set(users::totp_last_step.eq(Some(step)))
+    .execute(connection)
+    .or(Err(ResponseError::server_error()))?;
+
+    Ok(updated > 0)
+}
+
+fn select_user(connection: &mut PgConnection, user_id: uuid::Uuid) -> super::Result<UserSelect> {
+    use crate::schema::users;
+
+    users::table
+        .filter(users::id.eq(user_id))
+        .select((
+            users::id,
+            users::username,
+            users::first_name,
+            users::last_name,
+            users::password_hash,
+            users::deactivated,
+            users::assigned_role,
+            users::assigned_area,
+            users::avatar_key,
+            users::totp_secret,
+            users::totp_enabled,
+            users::totp_last_step,
+        ))
+        .first::<UserSelect>(connection)
+        .optional()
+        .or(Err(ResponseError::server_error()))?
+        .ok_or_else(|| ResponseError::value_do_not_exist("User"))
+}
+
+fn replace_recovery_codes(
+    connection: &mut PgConnection,
+    user_id: uuid::Uuid,
+) -> super::Result<Vec<String>> {
+    use crate::schema::totp_recovery_codes;
+
+    let codes = totp::generate_recovery_codes().ok_or_else(ResponseError::server_error)?;
+
+    connection
+        .transaction::<_, diesel::result::Error, _>(|connection| {
+            diesel::delete(totp_recovery_codes::table.filter(totp_recovery_codes::user_id.eq(user_id)))
+                .execute(connection)?;
+
+            diesel::insert_into(totp_recovery_codes::table)
+                .values(
+                    codes
+                        .iter()
+                        .map(|code| {
+                            (
+                                totp_recovery_codes::user_id.eq(user_id),
+                                totp_recovery_codes::code_hash.eq(totp::hash_recovery_code(code)),
+                            )
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .execute(connection)
+        })
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(codes)
+}
+
+fn already_enabled() -> ResponseError {
+    ResponseError::new(
+        "Two-factor authentication is already enabled",
+        "Two-factor authentication is already enabled",
+        LogLevel::Information,
+        StatusCode::CONFLICT,
+    )
+}
+
+fn invalid_code() -> ResponseError {
+    ResponseError::new(
+        "Invalid two-factor code",
+        "Invalid two-factor code",
+        LogLevel::Information,
+        StatusCode::BAD_REQUEST,
+    )
+}
+
+#[post("/totp")]
+async fn post_totp(
+    (state, user): (web::Data<AppData<'_>>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::users;
+
+    let mut connection = state.connect_database();
+    let account = select_user(&mut connection, user.user_id)?;
+
+    if account.totp_enabled {
+        return Err(already_enabled());
+    }
+
+    // starting over replaces a secret that was never confirmed
+    let secret = totp::generate_secret().ok_or_else(ResponseError::server_error)?;
+
+    diesel::update(users::table.filter(users::id.eq(user.user_id)))
+        .set((
+            users::totp_secret.eq(Some(&secret)),
+            users::totp_last_step.eq(None::<i64>),
+        ))
+        .execute(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(json!({
+        "secret": totp::encode_secret(&secret),
+        "uri": totp::otpauth_uri(&secret, &state.login_policy().totp_issuer, &account.username),
+    })
+    .to_string()
+    .customize()
+    .insert_header(("Content-Type", "application/json"))
+    .insert_header(("Cache-Control", "no-store"))
+    .with_status(StatusCode::OK))
+}
+
+#[get("/totp/qr")]
+async fn get_totp_qr(
+    (state, user): (web::Data<AppData<'_>>, UserClaims),
+) -> super::Result<impl Responder> {
+    let mut connection = state.connect_database();
+    let account = select_user(&mut connection, user.user_id)?;
+
+    // the secret is only shown while enrolment is pending
+    let secret = match (&account.totp_secret, account.totp_enabled) {
+        (Some(secret), false) => secret,
+        _ => return Err(ResponseError::value_do_not_exist("Two-factor enrolment")),
+    };
+
+    let uri = totp::otpauth_uri(secret, &state.login_policy().totp_issuer, &account.username);
+    let png = web::block(move || totp::qr_png(&uri))
+        .await
+        .or(Err(ResponseError::server_error()))?
+        .ok_or_else(ResponseError::server_error)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("image/png")
+        .insert_header(("Cache-Control", "no-store"))
+        .body(png))
+}
+
+#[post("/totp/confirm")]
+async fn post_totp_confirm(
+    (state, body, user): (web::Data<AppData<'_>>, web::Json<CodeRequest>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::users;
+
+    let mut connection = state.connect_database();
+    let account = select_user(&mut connection, user.user_id)?;
+
+    if account.totp_enabled {
+        return Err(already_enabled());
+    }
+
+    let secret = account
+        .totp_secret
+        .as_ref()
+        .ok_or_else(|| ResponseError::value_do_not_exist("Two-factor enrolment"))?;
+
+    let step = totp::verify(secret, &body.code, Utc::now().timestamp(), None).ok_or_else(invalid_code)?;
+
+    diesel::update(users::table.filter(users::id.eq(user.user_id)))
+        .set((
+            users::totp_enabled.eq(true),
+            users::totp_last_step.eq(Some(step)),
+        ))
+        .execute(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    let codes = replace_recovery_codes(&mut connection, user.user_id)?;
+
+    Ok(json!({ "recovery-codes": codes })
+        .to_string()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .insert_header(("Cache-Control", "no-store"))
+        .with_status(StatusCode::OK))
+}
+
+#[post("/totp/recovery-codes")]
+async fn post_recovery_codes(
+    (state, body, user): (web::Data<AppData<'_>>, web::Json<CodeRequest>, UserClaims),
+) -> super::Result<impl Responder> {
+    let mut connection = state.connect_database();
+    let account = select_user(&mut connection, user.user_id)?;
+
+    let step = account
+        .totp_secret
+        .as_ref()
+        .filter(|_| account.totp_enabled)
+        .and_then(|secret| {
+            totp::verify(secret, &body.code, Utc::now().timestamp(), account.totp_last_step)
+        })
+        .ok_or_else(invalid_code)?;
+
+    if !accept_step(&mut connection, user.user_id, step)? {
+        return Err(invalid_code());
+    }
+
+    let codes = replace_recovery_codes(&mut connection, user.user_id)?;
+
+    Ok(json!({ "recovery-codes": codes })
+        .to_string()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .insert_header(("Cache-Control", "no-store"))
+        .with_status(StatusCode::OK))
+}
+
+#[delete("/totp")]
+async fn delete_totp(
+    (state, query, body, user): (
+        web::Data<AppData<'_>>,
+        web::Query<TotpQuery>,
+        Option<web::Json<CodeRequest>>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    use crate::schema::{totp_recovery_codes, users};
+
+    let mut connection = state.connect_database();
+    let user_id = query.user_id.unwrap_or(user.user_id);
+
+    // a system administrator resets other accounts, everyone else proves possession first
+    if user_id != user.user_id {
+        if !user.can(Permission::TotpManage) {
+            return Err(ResponseError::unauthorized(user));
+        }
+    } else {
+        let account = select_user(&mut connection, user_id)?;
+
+        if account.totp_enabled {
+            let code = body.as_ref().map(|body| body.code.as_str()).ok_or_else(invalid_code)?;
+
+            if !verify_login_code(&mut connection, &account, code, Utc::now().naive_utc())? {
+                return Err(invalid_code());
+            }
+        }
+    }
+
+    connection
+        .transaction::<_, diesel::result::Error, _>(|connection| {
+            let updated = diesel::update(users::table.filter(users::id.eq(user_id)))
+                .set((
+                    users::totp_secret.eq(None::<Vec<u8>>),
+                    users::totp_enabled.eq(false),
+                    users::totp_last_step.eq(None::<i64>),
+                ))
+                .execute(connection)?;
+
+            diesel::delete(totp_recovery_codes::table.filter(totp_recovery_codes::user_id.eq(user_id)))
+                .execute(connection)?;
+
+            Ok(updated)
+        })
+        .or(Err(ResponseError::server_error()))
+        .and_then(|updated| match updated {
+            0 => Err(ResponseError::value_do_not_exist("User")),
+            _ => Ok(HttpResponse::NoContent()),
+        })
+}
+
+#[get("/totp/policies")]
+async fn get_policies(
+    (state, _user): (web::Data<AppData<'_>>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::totp_policies;
+
+    let mut connection = state.connect_database();
+
+    let policies: Vec<TotpPolicy> = totp_policies::table
+        .order_by(totp_policies::assigned_role)
+        .load(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(serde_json::to_string(&policies)
+        .unwrap()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
+#[put("/totp/policies")]
+async fn put_policy(
+    (state, body, _user): (web::Data<AppData<'_>>, web::Json<TotpPolicy>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::totp_policies;
+
+    let mut connection = state.connect_database();
+
+    match diesel::update(totp_policies::table.filter(totp_policies::assigned_role.eq(body.assigned_role)))
+        .set(totp_policies::required.eq(body.required))
+        .execute(&mut connection)
+    {
+        Ok(0) => Err(ResponseError::value_do_not_exist("Policy")),
+        Ok(_) => Ok(HttpResponse::NoContent()),
+        Err(_) => Err(ResponseError::server_error()),
+    }
+}
+
+pub(crate) fn configure(config: &mut web::ServiceConfig) {
+    config
+        .service(post_totp)
+        .service(get_totp_qr)
+        .service(post_totp_confirm)
+        .service(post_recovery_codes)
+        .service(delete_totp)
+        .service(get_policies)
+        .service(put_policy);
+}
@@ -0,0 +1,421 @@
+use actix_web::http::StatusCode;
+use actix_web::{get, patch, post, put, web};
+use actix_web::{HttpRequest, HttpResponse, Responder};
+
+use chrono::Utc;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, PgConnection,
+    PgTextExpressionMethods, QueryDsl, RunQueryDsl, TextExpressionMethods,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::data::AppData;
+use crate::logging::{LogLevel, ResponseError};
+use crate::models::{
+    numbered_role, numbered_role_option, LoginFailureInsert, LoginThrottle, PasswordHash, Permission,
+    UserClaims, UserListEntry, UserRole,
+};
+
+use super::users::end_sessions;
+
+const PASSWORD_MIN_LENGTH: usize = 8;
+const PASSWORD_MAX_LENGTH: usize = 128;
+
+#[derive(Deserialize)]
+pub(crate) struct ListQuery {
+    q: Option<String>,
+    // numbered 1 to 3 like the roles in the list
+    #[serde(alias = "assigned-role", default, deserialize_with = "numbered_role_option")]
+    pub(crate) assigned_role: Option<UserRole>,
+    deactivated: Option<bool>,
+    page: Option<i64>,
+    #[serde(alias = "page-size")]
+    page_size: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct IdQuery {
+    id: uuid::Uuid,
+}
+
+#[derive(Deserialize)]
+struct UpdateUserRequest {
+    #[serde(alias = "first-name")]
+    first_name: Option<String>,
+    #[serde(alias = "last-name")]
+    last_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChangePasswordRequest {
+    #[serde(alias = "old-password")]
+    old_password: String,
+    #[serde(alias = "new-password")]
+    new_password: String,
+}
+
+#[derive(Deserialize)]
+struct ResetPasswordRequest {
+    #[serde(alias = "new-password")]
+    new_password: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ChangeRoleRequest {
+    #[serde(alias = "assigned-role", deserialize_with = "numbered_role")]
+    pub(crate) assigned_role: UserRole,
+}
+
+// Loads the role of another user the caller is allowed to manage
+fn managed_user(
+    connection: &mut PgConnection,
+    user: &UserClaims,
+    id: uuid::Uuid,
+) -> super::Result<UserRole> {
+    use crate::schema::users;
+
+    // changing your own role or status would let anyone escalate or lock themselves out
+    if id == user.user_id || !user.can(Permission::UsersWrite) {
+        return Err(ResponseError::unauthorized(*user));
+    }
+
+    let role: UserRole = users::table
+        .filter(users::id.eq(id))
+        .select(users::assigned_role)
+        .first(connection)
+        .optional()
+        .or(Err(ResponseError::server_error()))?
+        .ok_or_else(|| ResponseError::value_do_not_exist("User"))?;
+
+    if !user.assigned_role.can_manage(role) {
+        return Err(ResponseError::unauthorized(*user));
+    }
+
+    Ok(role)
+}
+
+fn open_sessions(
+    connection: &mut PgConnection,
+    user_id: uuid::Uuid,
+    except: Option<uuid::Uuid>,
+) -> super::Result<Vec<uuid::Uuid>> {
+    use crate::schema::sessions;
+
+    let mut ids: Vec<uuid::Uuid> = sessions::table
+        .filter(sessions::user_id.eq(user_id))
+        .filter(sessions::logout_time.is_null())
+        .select(sessions::id)
+        .load(connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    ids.retain(|id| Some(*id) != except);
+
+    Ok(ids)
+}
+
+async fn new_password_hash(state: &AppData<'_>, password: &str) -> super::Result<PasswordHash> {
+    ResponseError::length_limit_check("Password", password, PASSWORD_MIN_LENGTH, PASSWORD_MAX_LENGTH)?;
+
+    state.hash_password(password).await
+}
+
+#[get("")]
+async fn get_users(
+    (state, query, _user): (web::Data<AppData<'_>>, web::Query<ListQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    use crate::schema::users;
+
+    let page = query.page.unwrap_or(1).max(1);
+    let page_size = query.page_size.unwrap_or(25).clamp(1, 100);
+
+    let filtered = || {
+        let mut filtered = users::table.into_boxed();
+
+        if let Some(q) = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+            let pattern = format!(
+                "%{}%",
+                q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+            );
+
+            filtered = filtered.filter(
+                users::username
+                    .ilike(pattern.clone())
+                    .or(users::first_name.concat(" ").concat(users::last_name).ilike(pattern)),
+            );
+        }
+
+        if let Some(role) = query.assigned_role {
+            filtered = filtered.filter(users::assigned_role.eq(role));
+        }
+
+        if let Some(deactivated) = query.deactivated {
+            filtered = filtered.filter(users::deactivated.eq(deactivated));
+        }
+
+        filtered
+    };
+
+    let mut connection = state.connect_database();
+
+    let total: i64 = filtered()
+        .count()
+        .get_result(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    let list: Vec<UserListEntry> = filtered()
+        .order_by((users::last_name, users::first_name, users::id))
+        .limit(page_size)
+        .offset((page - 1) * page_size)
+        .select((
+            users::id,
+            users::username,
+            users::first_name,
+            users::last_name,
+            users::assigned_role,
+            users::assigned_area,
+            users::deactivated,
+            users::totp_enabled,
+        ))
+        .load(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(json!({
+        "total": total,
+        "page": page,
+        "page-size": page_size,
+        "users": list,
+    })
+    .to_string()
+    .customize()
+    .insert_header(("Content-Type", "application/json"))
+    .with_status(StatusCode::OK))
+}
+
+#[patch("")]
+async fn patch_user(
+    (state, query, body, user): (
+        web::Data<AppData<'_>>,
+        web::Query<IdQuery>,
+        web::Json<UpdateUserRequest>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    use crate::schema::users;
+
+    let mut connection = state.connect_database();
+
+    if query.id != user.user_id {
+        managed_user(&mut connection, &user, query.id)?;
+    }
+
+    if let Some(first_name) = &body.first_name {
+        ResponseError::length_limit_check("First name", first_name.trim(), 1, 80)?;
+    }
+
+    if let Some(last_name) = &body.last_name {
+        ResponseError::length_limit_check("Last name", last_name.trim(), 1, 80)?;
+    }
+
+    if let Some(first_name) = &body.first_name {
+        diesel::update(users::table.filter(users::id.eq(query.id)))
+            .set(users::first_name.eq(first_name.trim()))
+            .execute(&mut connection)
+            .or(Err(ResponseError::server_error()))?;
+    }
+
+    if let Some(last_name) = &body.last_name {
+        diesel::update(users::table.filter(users::id.eq(query.id)))
+            .set(users::last_name.eq(last_name.trim()))
+            .execute(&mut connection)
+            .or(Err(ResponseError::server_error()))?;
+    }
+
+    Ok(HttpResponse::NoContent())
+}
+
+#[put("/password")]
+async fn put_password(
+    (state, body, user, request): (
+        web::Data<AppData<'_>>,
+        web::Json<ChangePasswordRequest>,
+        UserClaims,
+        HttpRequest,
+    ),
+) -> super::Result<impl Responder> {
+    use crate::schema::users;
+
+    let policy = state.login_policy();
+    let ip_address = state.client_address(&request);
+    let now = Utc::now().naive_utc();
+    let mut connection = state.connect_database();
+
+    let (username, current): (String, PasswordHash) = users::table
+        .filter(users::id.eq(user.user_id))
+        .select((users::username, users::password_hash))
+        .first(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    // a stolen session must not be a way around the login throttle
+    let throttle = LoginThrottle::check(
+        &mut connection,
+        policy,
+        &username,
+        Some(user.user_id),
+        ip_address.as_deref(),
+        now,
+    )
+    .or(Err(ResponseError::server_error()))?;
+
+    if let Some(locked_until) = throttle.locked_until {
+        return Err(ResponseError::new(
+            format!("Password change for {username} locked until {locked_until}"),
+            "Too many failed attempts, try again later",
+            LogLevel::Warning,
+            StatusCode::TOO_MANY_REQUESTS,
+        ));
+    }
+
+    drop(connection);
+
+    if state.validate_password(current, &body.old_password).await.is_err() {
+        diesel::insert_into(crate::schema::login_failures::table)
+            .values(LoginFailureInsert::new(&username, Some(user.user_id), ip_address, now))
+            .execute(&mut state.connect_database())
+            .or(Err(ResponseError::server_error()))?;
+
+        tokio::time::sleep(policy.delay(throttle.failures + 1)).await;
+
+        return Err(ResponseError::new(
+            format!("User {} entered a wrong old password", user.user_id),
+            "Old password is incorrect",
+            LogLevel::Information,
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let password_hash = new_password_hash(&state, &body.new_password).await?;
+    let mut connection = state.connect_database();
+
+    diesel::update(users::table.filter(users::id.eq(user.user_id)))
+        .set((
+            users::password_hash.eq(password_hash),
+            users::password_change_required.eq(false),
+        ))
+        .execute(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    // whoever knew the old password loses the sessions it opened
+    let others = open_sessions(&mut connection, user.user_id, Some(user.session_id))?;
+    end_sessions(&mut connection, &others)?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+#[post("/password/reset")]
+async fn post_password_reset(
+    (state, query, body, user): (
+        web::Data<AppData<'_>>,
+        web::Query<IdQuery>,
+        web::Json<ResetPasswordRequest>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    use crate::schema::users;
+
+    managed_user(&mut state.connect_database(), &user, query.id)?;
+
+    let password_hash = new_password_hash(&state, &body.new_password).await?;
+    let mut connection = state.connect_database();
+
+    diesel::update(users::table.filter(users::id.eq(query.id)))
+        .set((
+            users::password_hash.eq(password_hash),
+            users::password_change_required.eq(true),
+        ))
+        .execute(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    let sessions = open_sessions(&mut connection, query.id, None)?;
+    end_sessions(&mut connection, &sessions)?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+async fn set_deactivated(
+    state: web::Data<AppData<'_>>,
+    user: UserClaims,
+    id: uuid::Uuid,
+    deactivated: bool,
+) -> super::Result<HttpResponse> {
+    use crate::schema::users;
+
+    let mut connection = state.connect_database();
+
+    managed_user(&mut connection, &user, id)?;
+
+    diesel::update(users::table.filter(users::id.eq(id)))
+        .set(users::deactivated.eq(deactivated))
+        .execute(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    if deactivated {
+        let sessions = open_sessions(&mut connection, id, None)?;
+        end_sessions(&mut connection, &sessions)?;
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[post("/deactivate")]
+async fn post_deactivate(
+    (state, query, user): (web::Data<AppData<'_>>, web::Query<IdQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    set_deactivated(state, user, query.id, true).await
+}
+
+#[post("/reactivate")]
+async fn post_reactivate(
+    (state, query, user): (web::Data<AppData<'_>>, web::Query<IdQuery>, UserClaims),
+) -> super::Result<impl Responder> {
+    set_deactivated(state, user, query.id, false).await
+}
+
+#[put("/role")]
+async fn put_role(
+    (state, query, body, user): (
+        web::Data<AppData<'_>>,
+        web::Query<IdQuery>,
+        web::Json<ChangeRoleRequest>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    use crate::schema::users;
+
+    let mut connection = state.connect_database();
+
+    managed_user(&mut connection, &user, query.id)?;
+
+    // promoting someone to a role you could not manage would hand out more than you hold
+    if !user.assigned_role.can_manage(body.assigned_role) {
+        return Err(ResponseError::unauthorized(user));
+    }
+
+    diesel::update(users::table.filter(users::id.eq(query.id)))
+        .set(users::assigned_role.eq(body.assigned_role))
+        .execute(&mut connection)
+        .or(Err(ResponseError::server_error()))?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+pub(crate) fn configure(config: &mut web::ServiceConfig) {
+    config
+        .service(get_users)
+        .service(patch_user)
+        .service(put_password)
+        .service(post_password_reset)
+        .service(post_deactivate)
+        .service(post_reactivate)
+        .service(put_role);
+}
@@ -1,25 +1,33 @@
 use std::cmp::Ordering;
 use std::io::{Cursor, Read, Seek, Write};
 
-use actix_web::http::StatusCode;
+use actix_web::cookie::time::Duration as CookieDuration;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::http::{header, StatusCode};
 use actix_web::{post, web};
-use actix_web::{HttpResponse, Responder};
+use actix_web::{CustomizeResponder, HttpRequest, HttpResponse, Responder};
 
 use chrono::Utc;
 use diesel::r2d2::{ConnectionManager, PooledConnection};
 use diesel::result::DatabaseErrorKind;
 use diesel::BoolExpressionMethods;
-use diesel::{ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl};
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, QueryResult,
+    RunQueryDsl,
+};
 use futures_util::StreamExt;
 use image::ImageOutputFormat;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
+use crate::blob_store::BlobError;
 use crate::data::AppData;
 use crate::logging::{LogLevel, ResponseError};
+use crate::server_config::LoginPolicy;
 use crate::models::{
-    DeviceOs, DeviceSignature, JwtClaims, SessionInsert, UserBasicSelect, UserClaims, UserInsert,
-    UserRole, UserSelect,
+    random_token, DeviceOs, DeviceSignature, LoginFailureInsert, LoginThrottle, Permission, RefreshToken,
+    SessionInsert, SessionSelect, UserBasicSelect, UserClaims, UserInsert, UserRole, UserSelect,
+    CSRF_COOKIE, DEVICE_COOKIE, JWT_COOKIE,
 };
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -32,8 +40,32 @@ struct LoginRequest {
     pub device_name: String,
     #[serde(alias = "device-signature")]
     pub device_signature: DeviceSignature,
+    // browsers keep the tokens in HttpOnly cookies instead of reading them from the body
+    #[serde(alias = "set-cookie", default)]
+    pub set_cookie: bool,
+    // a time based code or one of the recovery codes, only for users with two-factor enabled
+    #[serde(alias = "totp-code", default)]
+    pub totp_code: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SessionTokens {
+    jwt: String,
+    #[serde(rename = "refresh-token")]
+    refresh_token: String,
+    // lifetime of the jwt in seconds
+    #[serde(rename = "expires-in")]
+    expires_in: i64,
+}
+
+#[derive(Deserialize)]
+struct RefreshRequest {
+    #[serde(alias = "refresh-token")]
+    refresh_token: String,
 }
 
+const REFRESH_COOKIE: &str = "refresh-token";
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 struct CreateUserOk {
     pub id: uuid::Uuid,
@@ -52,16 +84,16 @@ struct CreateUserRequest {
 }
 
 impl CreateUserRequest {
-    fn model(&self, state: web::Data<AppData<'_>>) -> UserInsert {
-        UserInsert {
+    async fn model(&self, state: web::Data<AppData<'_>>) -> super::Result<UserInsert> {
+        Ok(UserInsert {
             username: self.username.clone(),
             first_name: self.first_name.clone(),
             last_name: self.last_name.clone(),
-            password_hash: state.argon2(&self.password),
+            password_hash: state.hash_password(&self.password).await?,
             deactivated: false,
             assigned_role: self.assigned_role,
             assigned_area: None,
-        }
+        })
     }
 }
 
@@ -72,33 +104,197 @@ struct GetAvatarQuery {
 
 #[post("/login")]
 async fn post_login(
-    (body, state): (web::Json<LoginRequest>, web::Data<AppData<'_>>),
+    (body, state, request): (web::Json<LoginRequest>, web::Data<AppData<'_>>, HttpRequest),
 ) -> super::Result<impl Responder> {
+    let policy = state.login_policy();
+    let ip_address = state.client_address(&request);
+    let now = Utc::now().naive_utc();
+
     let mut database = state.connect_database();
     let user = UserSelect::select_by_username(&mut database, &body.username)?;
 
-    state
-        .validate_password(user.password_hash, &body.password)
-        .await?;
-    let jwt = create_session(state, &mut database, &body, user).await?;
+    let throttle = LoginThrottle::check(
+        &mut database,
+        policy,
+        &body.username,
+        user.as_ref().map(|user| user.id),
+        ip_address.as_deref(),
+        now,
+    )
+    .or(Err(crate::logging::ResponseError::server_error()))?;
+
+    if let Some(locked_until) = throttle.locked_until {
+        return Err(crate::logging::ResponseError::new(
+            format!("Login for {} locked until {locked_until}", body.username),
+            "Too many failed login attempts, try again later",
+            LogLevel::Warning,
+            StatusCode::TOO_MANY_REQUESTS,
+        ));
+    }
+
+    // an unknown username still costs a full password check
+    let password_hash = user
+        .as_ref()
+        .map_or_else(|| state.unknown_user_hash(), |user| user.password_hash.clone());
+    let verified = state.validate_password(password_hash, &body.password).await;
+
+    let (user, upgraded_hash) = match (user, verified) {
+        (Some(user), Ok(upgraded_hash)) if !user.deactivated => (user, upgraded_hash),
+        (user, _) => {
+            let failure = LoginFailureInsert::new(&body.username, user.map(|user| user.id), ip_address, now);
+
+            return Err(reject_login(
+                database,
+                policy,
+                failure,
+                throttle.failures,
+                "Invalid username or password",
+            )
+            .await);
+        }
+    };
+
+    if user.totp_enabled {
+        // answered like a wrong code so that a missing one does not confirm the password any faster
+        let rejection = match body.totp_code.as_deref() {
+            None => Some("Two-factor code required"),
+            Some(code) if !super::totp::verify_login_code(&mut database, &user, code, now)? => {
+                Some("Invalid two-factor code")
+            }
+            Some(_) => None,
+        };
+
+        if let Some(message) = rejection {
+            let failure = LoginFailureInsert::new(&body.username, Some(user.id), ip_address, now);
+
+            return Err(reject_login(database, policy, failure, throttle.failures, message).await);
+        }
+    }
+
+    if let Some(upgraded_hash) = upgraded_hash {
+        use crate::schema::users;
+
+        diesel::update(users::table.filter(users::id.eq(user.id)))
+            .set(users::password_hash.eq(upgraded_hash))
+            .execute(&mut database)
+            .or(Err(crate::logging::ResponseError::server_error()))?;
+    }
 
-    Ok(json!({ "jwt": jwt })
+    let device_hash = state
+        .xxh3_128bits(body.device_signature.into())
+        .await
+        .to_ne_bytes();
+
+    let device_id = super::devices::admit_device(
+        &mut database,
+        &user,
+        body.device_os,
+        &body.device_name,
+        &device_hash,
+        now,
+    )?;
+
+    let tokens = create_session(state.clone(), &mut database, &body, user, &device_hash, device_id).await?;
+    let device_signature = body.set_cookie.then(|| body.device_signature.to_string());
+
+    tokens_response(&state, tokens, device_signature)
+}
+
+// Records the failure and holds the answer back longer with every recent failure
+async fn reject_login(
+    mut database: PooledConnection<ConnectionManager<PgConnection>>,
+    policy: &LoginPolicy,
+    failure: LoginFailureInsert,
+    failures: i64,
+    message: &str,
+) -> crate::logging::ResponseError {
+    let log = format!("Failed login for {}", failure.username);
+
+    if diesel::insert_into(crate::schema::login_failures::table)
+        .values(failure)
+        .execute(&mut database)
+        .is_err()
+    {
+        return crate::logging::ResponseError::server_error();
+    }
+
+    drop(database);
+    tokio::time::sleep(policy.delay(failures + 1)).await;
+
+    crate::logging::ResponseError::new(log, message, LogLevel::Information, StatusCode::UNAUTHORIZED)
+}
+
+// Cookie sessions are answered with cookies, which also carry the device signature
+fn tokens_response(
+    state: &AppData<'_>,
+    tokens: SessionTokens,
+    cookie_signature: Option<String>,
+) -> super::Result<CustomizeResponder<String>> {
+    let device_signature = match cookie_signature {
+        Some(device_signature) => device_signature,
+        None => {
+            return Ok(serde_json::to_string(&tokens)
+                .unwrap()
+                .customize()
+                .append_header(("Content-Type", "application/json"))
+                .with_status(StatusCode::OK));
+        }
+    };
+
+    let csrf_token = random_token().ok_or_else(crate::logging::ResponseError::server_error)?;
+    let refresh_seconds = state.refresh_ttl().num_seconds();
+
+    // the tokens stay out of the body so scripts on the page never see them
+    Ok(json!({ "csrf-token": csrf_token, "expires-in": tokens.expires_in })
         .to_string()
         .customize()
         .append_header(("Content-Type", "application/json"))
+        .append_header((
+            header::SET_COOKIE,
+            session_cookie(JWT_COOKIE, tokens.jwt, "/", true, tokens.expires_in).to_string(),
+        ))
+        .append_header((
+            header::SET_COOKIE,
+            session_cookie(REFRESH_COOKIE, tokens.refresh_token, "/users/refresh", true, refresh_seconds)
+                .to_string(),
+        ))
+        .append_header((
+            header::SET_COOKIE,
+            session_cookie(CSRF_COOKIE, csrf_token, "/", false, refresh_seconds).to_string(),
+        ))
+        .append_header((
+            header::SET_COOKIE,
+            session_cookie(DEVICE_COOKIE, device_signature, "/", true, refresh_seconds).to_string(),
+        ))
         .with_status(StatusCode::OK))
 }
 
+fn session_cookie(
+    name: &'static str,
+    value: String,
+    path: &'static str,
+    http_only: bool,
+    max_age: i64,
+) -> Cookie<'static> {
+    Cookie::build(name, value)
+        .path(path)
+        .secure(true)
+        .http_only(http_only)
+        .same_site(SameSite::Strict)
+        .max_age(CookieDuration::seconds(max_age))
+        .finish()
+}
+
 async fn create_session(
     state: web::Data<AppData<'_>>,
     database: &mut PooledConnection<ConnectionManager<PgConnection>>,
     login_data: &LoginRequest,
     user: UserSelect,
-) -> super::Result<String> {
-    let dev_hash = state
-        .xxh3_128bits(login_data.device_signature.into())
-        .await
-        .to_ne_bytes();
+    dev_hash: &[u8],
+    device: uuid::Uuid,
+) -> super::Result<SessionTokens> {
+    let refresh_token = RefreshToken::generate().ok_or_else(crate::logging::ResponseError::server_error)?;
+    let refresh_expiry = Utc::now().naive_utc() + state.refresh_ttl();
 
     let session_id = {
         use crate::schema::sessions::dsl::*;
@@ -106,20 +302,167 @@ async fn create_session(
             &user.id,
             &login_data.device_os,
             &login_data.device_name,
-            &dev_hash,
+            dev_hash,
+            &device,
         );
 
+        // every user keeps one session per device, logging in again renews it
         diesel::insert_into(sessions)
             .values(&record)
-            .on_conflict(device_hash)
+            .on_conflict((user_id, device_hash))
             .do_update()
-            .set(last_login.eq(Utc::now().naive_utc()))
+            .set((
+                device_id.eq(Some(device)),
+                last_login.eq(Utc::now().naive_utc()),
+                logout_time.eq(None::<chrono::NaiveDateTime>),
+                device_os.eq(login_data.device_os),
+                device_name.eq(&login_data.device_name),
+            ))
             .returning(id)
             .get_result::<uuid::Uuid>(&mut *database)
             .unwrap()
     };
 
-    state.jwt_encode(&JwtClaims::new(session_id))
+    {
+        use crate::schema::{sessions, used_refresh_tokens};
+
+        // tokens of an earlier login on this device must not end the new one when replayed
+        diesel::delete(used_refresh_tokens::table.filter(used_refresh_tokens::session_id.eq(session_id)))
+            .execute(&mut *database)
+            .or(Err(crate::logging::ResponseError::server_error()))?;
+
+        diesel::update(sessions::table.filter(sessions::id.eq(session_id)))
+            .set((
+                sessions::refresh_hash.eq(Some(refresh_token.hash())),
+                sessions::refresh_expires.eq(Some(refresh_expiry)),
+            ))
+            .execute(&mut *database)
+            .or(Err(crate::logging::ResponseError::server_error()))?;
+    }
+
+    Ok(SessionTokens {
+        jwt: state.jwt_encode(session_id)?,
+        refresh_token: refresh_token.as_str().to_owned(),
+        expires_in: state.access_ttl().num_seconds(),
+    })
+}
+
+#[post("/refresh")]
+async fn post_refresh(
+    (body, state, request): (
+        Option<web::Json<RefreshRequest>>,
+        web::Data<AppData<'_>>,
+        HttpRequest,
+    ),
+) -> super::Result<impl Responder> {
+    use crate::schema::{sessions, used_refresh_tokens, users};
+
+    // the token comes from the body, or from the cookie set by a login with set-cookie
+    let (presented, set_cookie) = match (body, request.cookie(REFRESH_COOKIE)) {
+        (Some(body), _) => (RefreshToken::hash_of(&body.refresh_token), false),
+        (None, Some(cookie)) => {
+            UserClaims::verify_csrf(&request)?;
+            (RefreshToken::hash_of(cookie.value()), true)
+        }
+        (None, None) => {
+            return Err(crate::logging::ResponseError::new(
+                "Refresh token not found",
+                "Invalid Session",
+                LogLevel::Information,
+                StatusCode::UNAUTHORIZED,
+            ))
+        }
+    };
+
+    // a refresh token only works from the device its session belongs to
+    let signature = UserClaims::device_signature(&request)?;
+    let device_hash = state.xxh3_128bits(signature.into()).await.to_ne_bytes();
+
+    let mut connection = state.connect_database();
+    let refresh_token = RefreshToken::generate().ok_or_else(crate::logging::ResponseError::server_error)?;
+    let now = Utc::now().naive_utc();
+
+    // swapping on the old hash makes concurrent refreshes with the same token race for one winner
+    let rotated = connection
+        .transaction::<_, diesel::result::Error, _>(|connection| {
+            let session_id = diesel::update(
+                sessions::table
+                    .filter(sessions::refresh_hash.eq(&presented))
+                    .filter(sessions::device_hash.eq(&device_hash[..]))
+                    .filter(sessions::refresh_expires.gt(now))
+                    .filter(sessions::logout_time.is_null())
+                    .filter(
+                        sessions::user_id.eq_any(
+                            users::table
+                                .filter(users::deactivated.eq(false))
+                                .select(users::id),
+                        ),
+                    ),
+            )
+            .set((
+                sessions::refresh_hash.eq(Some(refresh_token.hash())),
+                sessions::refresh_expires.eq(Some(now + state.refresh_ttl())),
+            ))
+            .returning(sessions::id)
+            .get_result::<uuid::Uuid>(connection)
+            .optional()?;
+
+            if let Some(session_id) = session_id {
+                diesel::insert_into(used_refresh_tokens::table)
+                    .values((
+                        used_refresh_tokens::token_hash.eq(&presented),
+                        used_refresh_tokens::session_id.eq(session_id),
+                        used_refresh_tokens::used_time.eq(now),
+                    ))
+                    .on_conflict_do_nothing()
+                    .execute(connection)?;
+
+                diesel::delete(
+                    used_refresh_tokens::table
+                        .filter(used_refresh_tokens::used_time.lt(now - state.refresh_ttl())),
+                )
+                .execute(connection)?;
+            }
+
+            Ok(session_id)
+        })
+        .or(Err(crate::logging::ResponseError::server_error()))?;
+
+    if let Some(session_id) = rotated {
+        let tokens = SessionTokens {
+            jwt: state.jwt_encode(session_id)?,
+            refresh_token: refresh_token.as_str().to_owned(),
+            expires_in: state.access_ttl().num_seconds(),
+        };
+
+        return tokens_response(&state, tokens, set_cookie.then(|| signature.to_string()));
+    }
+
+    let reused: Option<uuid::Uuid> = used_refresh_tokens::table
+        .filter(used_refresh_tokens::token_hash.eq(&presented))
+        .select(used_refresh_tokens::session_id)
+        .first(&mut connection)
+        .optional()
+        .or(Err(crate::logging::ResponseError::server_error()))?;
+
+    // a rotated token showing up again means it leaked, so nobody keeps the session
+    if let Some(session_id) = reused {
+        end_sessions(&mut connection, &[session_id])?;
+
+        return Err(crate::logging::ResponseError::new(
+            format!("Refresh token reused, ended session: {session_id}"),
+            "Invalid Session",
+            LogLevel::Warning,
+            StatusCode::UNAUTHORIZED,
+        ));
+    }
+
+    Err(crate::logging::ResponseError::new(
+        "Invalid refresh token",
+        "Invalid Session",
+        LogLevel::Information,
+        StatusCode::UNAUTHORIZED,
+    ))
 }
 
 #[actix_web::get("/current")]
@@ -129,15 +472,16 @@ async fn get_current(
     use crate::schema::users;
 
     let database = &mut *state.connect_database();
-    let (username, first_name, last_name, assigned_role) = match users::table
+    let (username, first_name, last_name, assigned_role, password_change) = match users::table
         .select((
             users::username,
             users::first_name,
             users::last_name,
             users::assigned_role,
+            users::password_change_required,
         ))
         .filter(users::id.eq(user.user_id))
-        .first::<(String, String, String, UserRole)>(database)
+        .first::<(String, String, String, UserRole, bool)>(database)
         .optional()
     {
         Ok(Some(val)) => val,
@@ -164,7 +508,8 @@ async fn get_current(
         "username": username,
         "first-name": first_name,
         "last-name": last_name,
-        "assigned-role": assigned_role
+        "assigned-role": assigned_role,
+        "password-change-required": password_change
     })
     .to_string()
     .customize()
@@ -182,18 +527,12 @@ async fn post_register(
 ) -> super::Result<impl Responder> {
     use crate::schema::users::dsl::*;
 
-    let mut connection = state.connect_database();
-    let model = request.model(state);
-
-    if user.assigned_role == UserRole::SecurityGuard {
+    if !user.assigned_role.can_manage(request.assigned_role) {
         return Err(crate::logging::ResponseError::unauthorized(user));
     }
 
-    if user.assigned_role == UserRole::SecurityGuard
-        && request.assigned_role == UserRole::SystemAdmin
-    {
-        return Err(crate::logging::ResponseError::unauthorized(user));
-    }
+    let model = request.model(state.clone()).await?;
+    let mut connection = state.connect_database();
 
     let user_id = diesel::insert_into(users)
         .values(&model)
@@ -224,14 +563,10 @@ async fn post_register(
 
 #[actix_web::get("/unassigned")]
 async fn get_unassigned(
-    (state, user): (web::Data<AppData<'_>>, UserClaims),
+    (state, _user): (web::Data<AppData<'_>>, UserClaims),
 ) -> super::Result<impl Responder> {
     use crate::schema::users::dsl::*;
 
-    if user.assigned_role == UserRole::SecurityGuard {
-        return Err(crate::logging::ResponseError::unauthorized(user));
-    }
-
     let mut connection = state.connect_database();
 
     let guards = users
@@ -252,9 +587,10 @@ async fn get_unassigned(
 
 #[actix_web::get("/avatar")]
 async fn get_avatar(
-    (state, query, user): (
+    (state, query, request, user): (
         web::Data<AppData<'_>>,
         web::Query<GetAvatarQuery>,
+        HttpRequest,
         UserClaims,
     ),
 ) -> super::Result<impl Responder> {
@@ -264,16 +600,28 @@ async fn get_avatar(
 
     let mut connection = state.connect_database();
 
-    let avatar: Option<Vec<u8>> = users::table
+    let (avatar_key, avatar): (Option<String>, Option<Vec<u8>>) = users::table
         .filter(users::id.eq(user_id))
-        .select(users::avatar)
+        .select((users::avatar_key, users::avatar))
         .get_result(&mut connection)
         .or(Err(crate::logging::ResponseError::server_error()))?;
 
+    if let Some(key) = avatar_key {
+        return crate::blob_store::blob_response(
+            state.blob_store().as_ref(),
+            &key,
+            "image/jpeg",
+            &request,
+        )
+        .await;
+    }
+
     if let Some(bytes) = avatar {
-        return Ok(HttpResponse::build(StatusCode::OK)
-            .content_type("image/jpeg")
-            .body(bytes));
+        return Ok(crate::blob_store::bytes_response(
+            bytes,
+            "image/jpeg",
+            &request,
+        ));
     }
 
     let first_name: String = users::table
@@ -296,8 +644,6 @@ async fn patch_avatar(
     ),
 ) -> super::Result<impl Responder> {
     while let Some(item) = payload.next().await {
-        use crate::schema::users;
-
         let mut field = item.unwrap();
 
         if field.name() == "image" {
@@ -365,20 +711,28 @@ async fn patch_avatar(
                     .or(Err(crate::logging::ResponseError::server_error()))?;
             }
 
+            let blob_store = state.blob_store();
             let mut connection = state.connect_database();
 
-            return match diesel::update(users::table.filter(users::id.eq(user.user_id)))
-                .set(users::avatar.eq(image_bytes))
-                .execute(&mut connection)
-            {
-                Ok(row_count) => {
-                    if row_count == 1 {
-                        Ok(HttpResponse::NoContent())
-                    } else {
-                        Err(crate::logging::ResponseError::value_do_not_exist("User"))
-                    }
+            let replaced = crate::blob_store::store(
+                blob_store.as_ref(),
+                &mut connection,
+                image_bytes,
+                "image/jpeg",
+                |connection, key| replace_avatar(connection, user.user_id, Some(key)),
+            )
+            .await;
+
+            return match replaced {
+                Ok(Some(previous)) => {
+                    crate::blob_store::release(blob_store.as_ref(), &mut connection, &previous)
+                        .await
+                        .ok();
+
+                    Ok(HttpResponse::NoContent())
                 }
-                Err(_) => Err(crate::logging::ResponseError::server_error()),
+                Ok(None) => Ok(HttpResponse::NoContent()),
+                Err(error) => Err(avatar_error(error)),
             };
         }
     }
@@ -397,30 +751,241 @@ async fn delete_avatar(
 ) -> super::Result<impl Responder> {
     let mut connection = state.connect_database();
 
+    let previous = replace_avatar(&mut connection, user.user_id, None)
+        .map_err(|error| avatar_error(error.into()))?;
+
+    if let Some(previous) = previous {
+        crate::blob_store::release(state.blob_store().as_ref(), &mut connection, &previous)
+            .await
+            .ok();
+    }
+
+    Ok(HttpResponse::NoContent())
+}
+
+// Returns the key of the replaced avatar so its blob can be released
+fn replace_avatar(
+    connection: &mut PgConnection,
+    user_id: uuid::Uuid,
+    key: Option<String>,
+) -> QueryResult<Option<String>> {
     use crate::schema::users;
 
-    match diesel::update(users::table.filter(users::id.eq(user.user_id)))
-        .set(users::avatar.eq(Option::<Vec<u8>>::None))
-        .execute(&mut connection)
-    {
-        Ok(row_count) => {
-            if row_count == 1 {
-                Ok(HttpResponse::NoContent())
-            } else {
-                Err(crate::logging::ResponseError::value_do_not_exist("User"))
-            }
+    let previous: Option<String> = users::table
+        .filter(users::id.eq(user_id))
+        .select(users::avatar_key)
+        .get_result(connection)?;
+
+    diesel::update(users::table.filter(users::id.eq(user_id)))
+        .set((users::avatar_key.eq(key.as_ref()), users::avatar.eq(None::<Vec<u8>>)))
+        .execute(connection)?;
+
+    Ok(previous.filter(|previous| Some(previous) != key.as_ref()))
+}
+
+fn avatar_error(error: BlobError) -> crate::logging::ResponseError {
+    match error {
+        BlobError::Backend(_) => crate::logging::ResponseError::server_error(),
+        BlobError::NotFound => crate::logging::ResponseError::value_do_not_exist("User"),
+    }
+}
+
+#[post("/logout")]
+async fn post_logout(
+    (state, user): (web::Data<AppData<'_>>, UserClaims),
+) -> super::Result<impl Responder> {
+    let mut connection = state.connect_database();
+
+    end_sessions(&mut connection, &[user.session_id])?;
+
+    let mut response = HttpResponse::NoContent();
+
+    // expire whatever cookies a login with set-cookie left behind
+    for (name, path, http_only) in [
+        (JWT_COOKIE, "/", true),
+        (REFRESH_COOKIE, "/users/refresh", true),
+        (CSRF_COOKIE, "/", false),
+        (DEVICE_COOKIE, "/", true),
+    ] {
+        response.cookie(session_cookie(name, String::new(), path, http_only, 0));
+    }
+
+    Ok(response.finish())
+}
+
+#[derive(Deserialize)]
+struct SessionsQuery {
+    #[serde(alias = "user-id")]
+    user_id: Option<uuid::Uuid>,
+}
+
+#[derive(Serialize)]
+struct SessionEntry {
+    #[serde(flatten)]
+    session: SessionSelect,
+    current: bool,
+}
+
+#[actix_web::get("/sessions")]
+async fn get_sessions(
+    (state, query, user): (
+        web::Data<AppData<'_>>,
+        web::Query<SessionsQuery>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    use crate::schema::sessions;
+
+    let user_id = session_owner(&user, query.user_id)?;
+    let mut connection = state.connect_database();
+
+    let list: Vec<SessionEntry> = sessions::table
+        .filter(sessions::user_id.eq(user_id))
+        .filter(sessions::logout_time.is_null())
+        .order_by(sessions::last_login.desc())
+        .select((
+            sessions::id,
+            sessions::device_os,
+            sessions::device_name,
+            sessions::created_time,
+            sessions::last_login,
+        ))
+        .load::<SessionSelect>(&mut connection)
+        .or(Err(crate::logging::ResponseError::server_error()))?
+        .into_iter()
+        .map(|session| SessionEntry {
+            current: session.id == user.session_id,
+            session,
+        })
+        .collect();
+
+    Ok(serde_json::to_string(&list)
+        .unwrap()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
+#[derive(Deserialize)]
+struct RevokeSessionQuery {
+    id: uuid::Uuid,
+}
+
+#[actix_web::delete("/sessions")]
+async fn delete_session(
+    (state, query, user): (
+        web::Data<AppData<'_>>,
+        web::Query<RevokeSessionQuery>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    use crate::schema::sessions;
+
+    let mut connection = state.connect_database();
+
+    let owner: uuid::Uuid = sessions::table
+        .filter(sessions::id.eq(query.id))
+        .select(sessions::user_id)
+        .first(&mut connection)
+        .optional()
+        .or(Err(crate::logging::ResponseError::server_error()))?
+        .ok_or_else(|| crate::logging::ResponseError::value_do_not_exist("Session"))?;
+
+    session_owner(&user, Some(owner))?;
+    end_sessions(&mut connection, &[query.id])?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+#[actix_web::delete("/sessions/others")]
+async fn delete_other_sessions(
+    (state, user): (web::Data<AppData<'_>>, UserClaims),
+) -> super::Result<impl Responder> {
+    let mut connection = state.connect_database();
+
+    let mut others = user_sessions(&mut connection, user.user_id)?;
+    others.retain(|id| *id != user.session_id);
+
+    end_sessions(&mut connection, &others)?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+#[actix_web::delete("/sessions/all")]
+async fn delete_all_sessions(
+    (state, query, user): (
+        web::Data<AppData<'_>>,
+        web::Query<SessionsQuery>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    let user_id = session_owner(&user, query.user_id)?;
+    let mut connection = state.connect_database();
+
+    let sessions = user_sessions(&mut connection, user_id)?;
+    end_sessions(&mut connection, &sessions)?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+// Sessions of another user are only open to those who manage sessions
+pub(crate) fn session_owner(user: &UserClaims, owner: Option<uuid::Uuid>) -> super::Result<uuid::Uuid> {
+    match owner {
+        Some(owner) if owner != user.user_id && !user.can(Permission::SessionsManage) => {
+            Err(crate::logging::ResponseError::unauthorized(*user))
         }
-        Err(_) => Err(crate::logging::ResponseError::server_error()),
+        Some(owner) => Ok(owner),
+        None => Ok(user.user_id),
     }
 }
 
+pub(crate) fn end_sessions(connection: &mut PgConnection, ids: &[uuid::Uuid]) -> super::Result<usize> {
+    use crate::schema::sessions;
+
+    diesel::update(
+        sessions::table
+            .filter(sessions::id.eq_any(ids))
+            .filter(sessions::logout_time.is_null()),
+    )
+    .set((
+        sessions::logout_time.eq(Some(Utc::now().naive_utc())),
+        sessions::refresh_hash.eq(None::<Vec<u8>>),
+        sessions::refresh_expires.eq(None::<chrono::NaiveDateTime>),
+    ))
+    .execute(connection)
+    .or(Err(crate::logging::ResponseError::server_error()))
+}
+
+fn user_sessions(
+    connection: &mut PgConnection,
+    user_id: uuid::Uuid,
+) -> super::Result<Vec<uuid::Uuid>> {
+    use crate::schema::sessions;
+
+    sessions::table
+        .filter(sessions::user_id.eq(user_id))
+        .filter(sessions::logout_time.is_null())
+        .select(sessions::id)
+        .load(connection)
+        .or(Err(crate::logging::ResponseError::server_error()))
+}
+
 pub fn scope() -> actix_web::Scope {
     web::scope("/users")
         .service(post_login)
+        .service(post_refresh)
         .service(get_current)
         .service(post_register)
         .service(get_unassigned)
         .service(get_avatar)
         .service(patch_avatar)
         .service(delete_avatar)
+        .service(post_logout)
+        .service(get_sessions)
+        .service(delete_session)
+        .service(delete_other_sessions)
+        .service(delete_all_sessions)
+        .configure(super::totp::configure)
+        .configure(super::devices::configure)
+        .configure(super::user_admin::configure)
 }
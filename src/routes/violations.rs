@@ -1,8 +1,17 @@
 use crate::logging::LogLevel;
-use crate::models::{Category, IdentifiedViolation, UserRole, ViolationUnknown};
+use crate::models::{
+    AccessKind, Category, EscalationInsert, FaceEmbeddingInsert, GuardAssignment,
+    IdentifiedViolation, Permission, PersonSelect, ViolationUnknown,
+};
+use crate::notifier::Notification;
+use crate::reports::{ReportFilter, ReportFormat};
+use crate::server_config::PrivacyMode;
 use crate::{data::AppData, models::UserClaims};
+use actix_files::NamedFile;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
 use actix_web::http::StatusCode;
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::NaiveDate;
 use diesel::BoolExpressionMethods;
 use diesel::ExpressionMethods;
 use diesel::QueryDsl;
@@ -13,22 +22,25 @@ use serde::Deserialize;
 async fn get_unidentified(
     (state, user): (web::Data<AppData<'_>>, UserClaims),
 ) -> super::Result<impl Responder> {
-    use crate::schema::users;
     use crate::schema::violations::dsl::*;
 
     let mut connection = state.connect_database();
     let mut list = Vec::new();
 
-    if user.assigned_role == UserRole::SecurityGuard {
-        let assigned_area: Option<String> = users::table
-            .filter(users::id.eq(user.user_id))
-            .select(users::assigned_area)
-            .first(&mut connection)
-            .unwrap();
+    // without the wider permission only the areas the caller is posted to right now are listed
+    if !user.can(Permission::ViolationsReadAllAreas) {
+        let areas = GuardAssignment::current_areas(
+            &mut connection,
+            &[user.user_id],
+            chrono::Utc::now().naive_utc(),
+        )
+        .or(Err(crate::logging::ResponseError::server_error()))?
+        .remove(&user.user_id)
+        .unwrap_or_default();
 
-        if let Some(area) = assigned_area {
+        if !areas.is_empty() {
             list = violations
-                .filter(identified.eq(false).and(area_code.eq(area)))
+                .filter(identified.eq(false).and(area_code.eq_any(areas)))
                 .order_by(date_time)
                 .select((id, area_code, violation_kind, date_time))
                 .get_results::<ViolationUnknown>(&mut connection)
@@ -57,7 +69,7 @@ struct GetIdentifiedQuery {
 
 #[actix_web::get("/identified")]
 async fn get_identified(
-    (state, user, query): (
+    (state, _user, query): (
         web::Data<AppData<'_>>,
         UserClaims,
         web::Query<GetIdentifiedQuery>,
@@ -67,10 +79,6 @@ async fn get_identified(
 
     let mut connection = state.connect_database();
 
-    if user.assigned_role == UserRole::SystemAdmin {
-        return Err(crate::logging::ResponseError::unauthorized(user));
-    }
-
     let list = match query.area_code.clone() {
         Some(area) => violations
             .filter(identified.eq(true))
@@ -82,6 +90,7 @@ async fn get_identified(
                 violation_kind,
                 date_time,
                 personnel_id,
+                person_id,
                 first_name,
                 last_name,
                 category,
@@ -97,6 +106,7 @@ async fn get_identified(
                 violation_kind,
                 date_time,
                 personnel_id,
+                person_id,
                 first_name,
                 last_name,
                 category,
@@ -118,16 +128,21 @@ struct GetImageQuery {
 
 #[actix_web::get("/image")]
 async fn get_image(
-    (state, query, _user): (web::Data<AppData<'_>>, web::Query<GetImageQuery>, UserClaims),
+    (state, query, request, user): (
+        web::Data<AppData<'_>>,
+        web::Query<GetImageQuery>,
+        HttpRequest,
+        UserClaims,
+    ),
 ) -> super::Result<impl Responder> {
     use crate::schema::violations::dsl::*;
 
     let mut connection = state.connect_database();
 
-    let image = violations
+    let (key, bytes, archive) = violations
         .filter(id.eq(query.id))
-        .select(image_bytes)
-        .first::<Vec<u8>>(&mut connection)
+        .select((image_key, image_bytes, image_archive))
+        .first::<(Option<String>, Option<Vec<u8>>, Option<String>)>(&mut connection)
         .or(Err(crate::logging::ResponseError::new(
             "Failed to find image",
             "Image not found",
@@ -135,21 +150,146 @@ async fn get_image(
             StatusCode::NOT_FOUND,
         )))?;
 
-    Ok(HttpResponse::build(StatusCode::OK)
-        .content_type("image/jpeg")
-        .body(image))
+    let mode = crate::privacy::mode_for(state.privacy_policy(), &user);
+
+    if mode == PrivacyMode::Off {
+        crate::privacy::record_access(
+            &mut connection,
+            &user,
+            AccessKind::ViolationImage,
+            Some(query.id),
+            state.client_address(&request),
+        )?;
+    }
+
+    // images not yet moved into the blob store are still served from the row
+    let image = match (key, bytes, archive) {
+        (Some(key), _, _) if mode == PrivacyMode::Off => {
+            return crate::blob_store::blob_response(
+                state.blob_store().as_ref(),
+                &key,
+                "image/jpeg",
+                &request,
+            )
+            .await
+            .map(crate::privacy::uncached);
+        }
+        (Some(key), _, _) => state
+            .blob_store()
+            .get(&key, None)
+            .await
+            .map_err(crate::blob_store::blob_error)?,
+        (None, Some(bytes), _) => bytes,
+        (None, None, Some(archive)) => {
+            let archive_dir = state.archive_dir().to_owned();
+
+            web::block(move || crate::retention::read_archived(&archive_dir, &archive))
+                .await
+                .or(Err(crate::logging::ResponseError::server_error()))?
+                .map_err(|error| {
+                    crate::logging::ResponseError::new(
+                        format!("Failed to read archived image: {error}"),
+                        "Image not found",
+                        LogLevel::Warning,
+                        StatusCode::NOT_FOUND,
+                    )
+                })?
+        }
+        (None, None, None) => {
+            return Err(crate::logging::ResponseError::new(
+                "Violation has no image",
+                "Image not found",
+                LogLevel::Information,
+                StatusCode::NOT_FOUND,
+            ))
+        }
+    };
+
+    let image = match mode {
+        PrivacyMode::Off => image,
+        _ => {
+            let policy = state.privacy_policy().clone();
+
+            web::block(move || crate::privacy::redact_jpeg(&image, &policy, mode))
+                .await
+                .or(Err(crate::logging::ResponseError::server_error()))?
+                .map_err(|error| {
+                    crate::logging::ResponseError::new(
+                        format!("Failed to redact image: {error}"),
+                        "Failed to load image",
+                        LogLevel::Error,
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                    )
+                })?
+        }
+    };
+
+    Ok(crate::privacy::uncached(crate::blob_store::bytes_response(
+        image,
+        "image/jpeg",
+        &request,
+    )))
+}
+
+// The clip recorded around the violation, seekable with range requests. Clips cannot be
+// redacted, so they are only shown to users allowed to see footage unredacted
+#[actix_web::get("/clip")]
+async fn get_clip(
+    (state, query, request, user): (
+        web::Data<AppData<'_>>,
+        web::Query<GetImageQuery>,
+        HttpRequest,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    use crate::schema::violations::dsl::*;
+
+    if crate::privacy::mode_for(state.privacy_policy(), &user) != PrivacyMode::Off {
+        return Err(crate::privacy::unredacted_only());
+    }
+
+    let mut connection = state.connect_database();
+
+    let key = violations
+        .filter(id.eq(query.id))
+        .select(clip_key)
+        .first::<Option<String>>(&mut connection)
+        .ok()
+        .flatten()
+        .ok_or_else(|| {
+            crate::logging::ResponseError::new(
+                "Violation has no clip",
+                "Clip not found",
+                LogLevel::Information,
+                StatusCode::NOT_FOUND,
+            )
+        })?;
+
+    crate::privacy::record_access(
+        &mut connection,
+        &user,
+        AccessKind::ViolationClip,
+        Some(query.id),
+        state.client_address(&request),
+    )?;
+
+    crate::blob_store::blob_response(state.blob_store().as_ref(), &key, "video/mp4", &request)
+        .await
+        .map(crate::privacy::uncached)
 }
 
 #[derive(Deserialize)]
 struct PatchRecordRequest {
     #[serde(alias = "violation-id")]
     id: uuid::Uuid,
+    #[serde(alias = "person-id")]
+    person_id: Option<uuid::Uuid>,
     #[serde(alias = "first-name")]
-    first_name: String,
+    first_name: Option<String>,
     #[serde(alias = "last-name")]
-    last_name: String,
+    last_name: Option<String>,
     #[serde(alias = "category")]
-    category: Category,
+    category: Option<Category>,
 }
 
 #[actix_web::patch("/record")]
@@ -162,30 +302,218 @@ async fn patch_record(
 ) -> super::Result<impl Responder> {
     use crate::schema::violations;
 
-    if user.assigned_role == UserRole::SystemAdmin {
-        return Err(crate::logging::ResponseError::unauthorized(user));
-    }
-
     let mut connection = state.connect_database();
 
-    diesel::update(violations::table.filter(violations::id.eq(request.id)))
+    let (first_name, last_name, category) = match request.person_id {
+        Some(person_id) => {
+            let person = PersonSelect::select_by_id(&mut connection, person_id)?;
+            (person.first_name, person.last_name, person.category)
+        }
+        None => match (&request.first_name, &request.last_name, request.category) {
+            (Some(first_name), Some(last_name), Some(category)) => {
+                (first_name.clone(), last_name.clone(), category)
+            }
+            _ => {
+                return Err(crate::logging::ResponseError::new(
+                    "Incomplete violator details",
+                    "Specify a person-id or the first-name, last-name and category",
+                    LogLevel::Information,
+                    StatusCode::BAD_REQUEST,
+                ))
+            }
+        },
+    };
+
+    match diesel::update(violations::table.filter(violations::id.eq(request.id)))
         .set((
             violations::personnel_id.eq(Some(user.user_id)),
-            violations::first_name.eq(Some(&request.first_name)),
-            violations::last_name.eq(Some(&request.last_name)),
-            violations::category.eq(Some(&request.category)),
+            violations::person_id.eq(request.person_id),
+            violations::first_name.eq(Some(&first_name)),
+            violations::last_name.eq(Some(&last_name)),
+            violations::category.eq(Some(&category)),
             violations::identified.eq(true),
         ))
         .execute(&mut connection)
-        .unwrap();
+    {
+        Err(_) => return Err(crate::logging::ResponseError::server_error()),
+        Ok(0) => return Err(crate::logging::ResponseError::value_do_not_exist("Violation")),
+        Ok(_) => (),
+    }
+
+    if let Some(person_id) = request.person_id {
+        let escalations = EscalationInsert::evaluate(&mut connection, person_id)
+            .or(Err(crate::logging::ResponseError::server_error()))?;
+
+        if !escalations.is_empty() {
+            state
+                .notifier()
+                .await
+                .notify(Notification::NewEscalations(escalations));
+        }
+    }
 
     Ok(HttpResponse::build(StatusCode::OK))
 }
 
+// Previously identified persons alike the violator, most alike first, to fill the record with
+#[actix_web::get("/suggestions")]
+async fn get_suggestions(
+    (state, query, _user): (
+        web::Data<AppData<'_>>,
+        web::Query<GetImageQuery>,
+        UserClaims,
+    ),
+) -> super::Result<impl Responder> {
+    use crate::schema::persons;
+
+    let policy = state.embedding_policy().clone();
+    let model = policy.model_name().ok_or_else(|| {
+        crate::logging::ResponseError::new(
+            "Suggestions are not enabled",
+            "Suggestions are not enabled",
+            LogLevel::Information,
+            StatusCode::CONFLICT,
+        )
+    })?;
+
+    let pool = state.database_pool();
+    let violation_id = query.id;
+
+    // an image not embedded yet has nothing to suggest
+    let matches = web::block(move || {
+        let mut connection = pool.get().or(Err(()))?;
+
+        match FaceEmbeddingInsert::of_violation(&mut connection, &model, violation_id).or(Err(()))? {
+            Some(target) => FaceEmbeddingInsert::identified(&mut connection, &model, violation_id)
+                .map(|candidates| {
+                    crate::embedding::rank(&target, candidates, policy.min_similarity, policy.suggestions)
+                })
+                .or(Err(())),
+            None => Ok(Vec::new()),
+        }
+    })
+    .await
+    .or(Err(crate::logging::ResponseError::server_error()))?
+    .or(Err(crate::logging::ResponseError::server_error()))?;
+
+    let mut connection = state.connect_database();
+    let persons: Vec<PersonSelect> = persons::table
+        .filter(persons::id.eq_any(matches.iter().map(|found| found.person_id)))
+        .load(&mut connection)
+        .or(Err(crate::logging::ResponseError::server_error()))?;
+
+    let suggestions: Vec<serde_json::Value> = matches
+        .iter()
+        .filter_map(|found| {
+            persons.iter().find(|person| person.id == found.person_id).map(|person| {
+                serde_json::json!({
+                    "person": person,
+                    "violation-id": found.violation_id,
+                    "similarity": found.similarity,
+                })
+            })
+        })
+        .collect();
+
+    Ok(serde_json::to_string(&suggestions)
+        .unwrap()
+        .customize()
+        .insert_header(("Content-Type", "application/json"))
+        .with_status(StatusCode::OK))
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    format: ReportFormat,
+    #[serde(alias = "area-code")]
+    area_code: Option<String>,
+    identified: Option<bool>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+#[actix_web::get("/export")]
+async fn get_export(
+    (state, query, user, request): (
+        web::Data<AppData<'_>>,
+        web::Query<ExportQuery>,
+        UserClaims,
+        HttpRequest,
+    ),
+) -> super::Result<HttpResponse> {
+    let query = query.into_inner();
+    let file_name = query.format.file_name();
+    let pool = state.database_pool();
+    let filter = ReportFilter {
+        area_code: query.area_code,
+        identified: query.identified,
+        from: query.from,
+        to: query.to,
+    };
+
+    let file = match query.format {
+        ReportFormat::Csv => {
+            return Ok(HttpResponse::build(StatusCode::OK)
+                .content_type("text/csv")
+                .append_header((
+                    "Content-Disposition",
+                    format!("attachment; filename=\"{file_name}\""),
+                ))
+                .streaming(crate::reports::csv_stream(pool, filter)));
+        }
+        ReportFormat::Xlsx => web::block(move || crate::reports::write_xlsx(&pool, &filter)).await,
+        ReportFormat::Pdf => {
+            let font_bytes = state.font_bytes();
+            let archive_dir = state.archive_dir().to_owned();
+            let blob_store = state.blob_store();
+            let runtime = tokio::runtime::Handle::current();
+            let policy = state.privacy_policy().clone();
+            let mode = crate::privacy::mode_for(&policy, &user);
+
+            // only PDFs carry the images
+            if mode == PrivacyMode::Off {
+                crate::privacy::record_access(
+                    &mut state.connect_database(),
+                    &user,
+                    AccessKind::ViolationReport,
+                    None,
+                    state.client_address(&request),
+                )?;
+            }
+
+            web::block(move || {
+                crate::reports::write_pdf(
+                    &pool,
+                    &filter,
+                    font_bytes,
+                    &runtime,
+                    blob_store.as_ref(),
+                    &archive_dir,
+                    &policy,
+                    mode,
+                )
+            })
+            .await
+        }
+    }
+    .or(Err(crate::logging::ResponseError::server_error()))??;
+
+    Ok(NamedFile::from_file(file, file_name)
+        .or(Err(crate::logging::ResponseError::server_error()))?
+        .set_content_disposition(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file_name.to_owned())],
+        })
+        .into_response(&request))
+}
+
 pub fn scope() -> actix_web::Scope {
     web::scope("/violations")
         .service(get_unidentified)
         .service(get_identified)
         .service(get_image)
+        .service(get_clip)
         .service(patch_record)
+        .service(get_suggestions)
+        .service(get_export)
 }
@@ -1,9 +1,44 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    access_logs (id) {
+        id -> Uuid,
+        user_id -> Nullable<Uuid>,
+        access_kind -> Int2,
+        subject_id -> Nullable<Uuid>,
+        ip_address -> Nullable<Varchar>,
+        access_time -> Timestamp,
+    }
+}
+
 diesel::table! {
     areas (code) {
         code -> Varchar,
         name -> Varchar,
+        checkin_secret -> Nullable<Bytea>,
+        archived_time -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    attendance (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        area_code -> Varchar,
+        assignment_id -> Nullable<Uuid>,
+        shift_start -> Nullable<Timestamp>,
+        check_in_time -> Timestamp,
+        check_out_time -> Nullable<Timestamp>,
+        code_verified -> Bool,
+    }
+}
+
+diesel::table! {
+    camera_credentials (camera_id) {
+        camera_id -> Uuid,
+        key_id -> Varchar,
+        nonce -> Bytea,
+        ciphertext -> Bytea,
     }
 }
 
@@ -14,6 +49,118 @@ diesel::table! {
         area_code -> Varchar,
         camera_url -> Varchar,
         deactivated -> Bool,
+        onvif_url -> Nullable<Varchar>,
+        onvif_profile -> Nullable<Varchar>,
+        recording_quota_mb -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    devices (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        device_hash -> Bytea,
+        device_os -> Int2,
+        device_name -> Varchar,
+        device_status -> Int2,
+        created_time -> Timestamp,
+        last_seen -> Timestamp,
+    }
+}
+
+diesel::table! {
+    escalation_rules (id) {
+        id -> Uuid,
+        label -> Varchar,
+        violation_count -> Int4,
+        period_days -> Int4,
+    }
+}
+
+diesel::table! {
+    escalations (id) {
+        id -> Uuid,
+        person_id -> Uuid,
+        rule_id -> Uuid,
+        violation_count -> Int4,
+        created_time -> Timestamp,
+        resolved -> Bool,
+    }
+}
+
+diesel::table! {
+    face_embeddings (violation_id) {
+        violation_id -> Uuid,
+        model -> Varchar,
+        embedding -> Nullable<Array<Float4>>,
+        created_time -> Timestamp,
+    }
+}
+
+diesel::table! {
+    guard_assignments (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        area_code -> Varchar,
+        start_time -> Timestamp,
+        end_time -> Timestamp,
+        recurrence -> Int2,
+        repeat_until -> Nullable<Date>,
+    }
+}
+
+diesel::table! {
+    login_failures (id) {
+        id -> Uuid,
+        username -> Varchar,
+        user_id -> Nullable<Uuid>,
+        ip_address -> Nullable<Varchar>,
+        attempt_time -> Timestamp,
+    }
+}
+
+diesel::table! {
+    missed_shifts (assignment_id, shift_start) {
+        assignment_id -> Uuid,
+        shift_start -> Timestamp,
+        user_id -> Uuid,
+        area_code -> Varchar,
+        reported_time -> Timestamp,
+    }
+}
+
+diesel::table! {
+    persons (id) {
+        id -> Uuid,
+        external_id -> Nullable<Varchar>,
+        first_name -> Varchar,
+        last_name -> Varchar,
+        category -> Int2,
+        department -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    recording_segments (id) {
+        id -> Uuid,
+        camera_id -> Uuid,
+        start_time -> Timestamp,
+        end_time -> Timestamp,
+        file_path -> Varchar,
+        bytes -> Int8,
+    }
+}
+
+diesel::table! {
+    retention_runs (id) {
+        id -> Uuid,
+        started_time -> Timestamp,
+        finished_time -> Timestamp,
+        compacted -> Int4,
+        archived -> Int4,
+        purged -> Int4,
+        failed -> Int4,
+        bytes_reclaimed -> Int8,
     }
 }
 
@@ -27,6 +174,33 @@ diesel::table! {
         device_os -> Int2,
         device_name -> Varchar,
         device_hash -> Bytea,
+        refresh_hash -> Nullable<Bytea>,
+        refresh_expires -> Nullable<Timestamp>,
+        device_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    totp_policies (assigned_role) {
+        assigned_role -> Int2,
+        required -> Bool,
+    }
+}
+
+diesel::table! {
+    totp_recovery_codes (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        code_hash -> Bytea,
+        used_time -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    used_refresh_tokens (token_hash) {
+        token_hash -> Bytea,
+        session_id -> Uuid,
+        used_time -> Timestamp,
     }
 }
 
@@ -36,11 +210,16 @@ diesel::table! {
         username -> Varchar,
         first_name -> Varchar,
         last_name -> Varchar,
-        password_hash -> Bytea,
+        password_hash -> Varchar,
         deactivated -> Bool,
         assigned_role -> Int2,
         assigned_area -> Nullable<Varchar>,
         avatar -> Nullable<Bytea>,
+        avatar_key -> Nullable<Varchar>,
+        totp_secret -> Nullable<Bytea>,
+        totp_enabled -> Bool,
+        totp_last_step -> Nullable<Int8>,
+        password_change_required -> Bool,
     }
 }
 
@@ -50,25 +229,66 @@ diesel::table! {
         area_code -> Varchar,
         violation_kind -> Int2,
         date_time -> Timestamp,
-        image_bytes -> Bytea,
+        image_bytes -> Nullable<Bytea>,
         identified -> Bool,
         personnel_id -> Nullable<Uuid>,
         first_name -> Nullable<Varchar>,
         last_name -> Nullable<Varchar>,
         category -> Nullable<Int2>,
+        person_id -> Nullable<Uuid>,
+        image_compacted -> Bool,
+        image_archive -> Nullable<Varchar>,
+        image_key -> Nullable<Varchar>,
+        clip_key -> Nullable<Varchar>,
     }
 }
 
+diesel::joinable!(access_logs -> users (user_id));
+diesel::joinable!(attendance -> areas (area_code));
+diesel::joinable!(attendance -> guard_assignments (assignment_id));
+diesel::joinable!(attendance -> users (user_id));
+diesel::joinable!(camera_credentials -> cameras (camera_id));
 diesel::joinable!(cameras -> areas (area_code));
+diesel::joinable!(devices -> users (user_id));
+diesel::joinable!(escalations -> escalation_rules (rule_id));
+diesel::joinable!(escalations -> persons (person_id));
+diesel::joinable!(face_embeddings -> violations (violation_id));
+diesel::joinable!(guard_assignments -> areas (area_code));
+diesel::joinable!(guard_assignments -> users (user_id));
+diesel::joinable!(login_failures -> users (user_id));
+diesel::joinable!(missed_shifts -> areas (area_code));
+diesel::joinable!(missed_shifts -> guard_assignments (assignment_id));
+diesel::joinable!(missed_shifts -> users (user_id));
+diesel::joinable!(recording_segments -> cameras (camera_id));
+diesel::joinable!(sessions -> devices (device_id));
 diesel::joinable!(sessions -> users (user_id));
+diesel::joinable!(totp_recovery_codes -> users (user_id));
+diesel::joinable!(used_refresh_tokens -> sessions (session_id));
 diesel::joinable!(users -> areas (assigned_area));
 diesel::joinable!(violations -> areas (area_code));
+diesel::joinable!(violations -> persons (person_id));
 diesel::joinable!(violations -> users (personnel_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    access_logs,
     areas,
+    attendance,
+    camera_credentials,
     cameras,
+    devices,
+    escalation_rules,
+    escalations,
+    face_embeddings,
+    guard_assignments,
+    login_failures,
+    missed_shifts,
+    persons,
+    recording_segments,
+    retention_runs,
     sessions,
+    totp_policies,
+    totp_recovery_codes,
+    used_refresh_tokens,
     users,
     violations,
 );
@@ -1,9 +1,168 @@
 use dotenvy::dotenv;
-use std::net::{Ipv4Addr, SocketAddrV4};
+use jsonwebtoken::Algorithm;
+use serde::Deserialize;
+use std::net::{IpAddr, Ipv4Addr, SocketAddrV4};
+use std::path::PathBuf;
+use std::str::FromStr;
 
 pub struct ServerConfig {
     pub port: u16,
     pub database_url: String,
+    pub retention: RetentionPolicy,
+    pub blob_store: BlobStoreConfig,
+    pub password: PasswordPolicy,
+    pub login: LoginPolicy,
+    // reverse proxies whose X-Forwarded-For is believed, none by default
+    pub trusted_proxies: Vec<IpAddr>,
+    pub attendance: AttendancePolicy,
+    pub clips: ClipPolicy,
+    pub recording: RecordingPolicy,
+    pub privacy: PrivacyPolicy,
+    pub embedding: EmbeddingPolicy,
+    pub jwt: JwtConfig,
+    pub credentials: CredentialKeysConfig,
+}
+
+#[derive(Clone, Debug)]
+pub struct JwtConfig {
+    pub issuer: String,
+    pub audience: String,
+    pub access_ttl_minutes: i64,
+    pub refresh_ttl_days: i64,
+    pub signing_kid: String,
+    pub keys: Vec<JwtKeyConfig>,
+}
+
+// Tokens signed by any listed key stay valid, only `signing_kid` signs new ones
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct JwtKeyConfig {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    pub secret: Option<String>,
+    pub private_key: Option<PathBuf>,
+    pub public_key: Option<PathBuf>,
+}
+
+// Camera credentials are sealed with `active_kid`, the other keys only open older ones
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CredentialKeysConfig {
+    pub active_kid: String,
+    pub keys: Vec<CredentialKeyConfig>,
+}
+
+// A base64 encoded 256 bit AES key
+#[derive(Clone, Debug, Deserialize)]
+pub struct CredentialKeyConfig {
+    pub kid: String,
+    pub key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct JwtKeysFile {
+    signing_kid: String,
+    keys: Vec<JwtKeyConfig>,
+}
+
+// Argon2id cost parameters, new hashes use them and older ones are upgraded on login
+#[derive(Clone, Debug)]
+pub struct PasswordPolicy {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+// Failures are counted per username and per address within `window_minutes`
+#[derive(Clone, Debug)]
+pub struct LoginPolicy {
+    pub max_failures: i64,
+    pub max_address_failures: i64,
+    pub window_minutes: i64,
+    pub lockout_minutes: i64,
+    pub delay_ms: u64,
+    pub max_delay_ms: u64,
+    // shown as the account name in authenticator apps
+    pub totp_issuer: String,
+}
+
+// A shift counts as missed once `grace_minutes` pass without a check-in
+#[derive(Clone, Debug)]
+pub struct AttendancePolicy {
+    pub early_minutes: i64,
+    pub grace_minutes: i64,
+    pub interval_minutes: u64,
+}
+
+// Each camera keeps `seconds_before` of frames, sampled at `fps`, to cut violation clips from
+#[derive(Clone, Debug)]
+pub struct ClipPolicy {
+    pub seconds_before: u64,
+    pub seconds_after: u64,
+    pub fps: u32,
+}
+
+// Cameras are recorded under `dir` in files of `segment_seconds`, each one kept for `retention_days`
+// and within `quota_mb` of its camera, unless the camera sets a quota of its own
+#[derive(Clone, Debug)]
+pub struct RecordingPolicy {
+    pub dir: Option<PathBuf>,
+    pub segment_seconds: u32,
+    pub retention_days: u32,
+    pub quota_mb: u32,
+    pub ffmpeg: PathBuf,
+}
+
+// What is hidden of footage shown to roles that may not see it unredacted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrivacyMode {
+    Off,
+    Faces,
+    Bodies,
+}
+
+// Faces are found with the OpenCV Haar cascade in `face_cascade`
+#[derive(Clone, Debug)]
+pub struct PrivacyPolicy {
+    pub mode: PrivacyMode,
+    pub face_cascade: PathBuf,
+}
+
+// Crops are embedded with the ONNX model at `model`, which takes a 1x3x`input_size`x`input_size`
+// RGB tensor scaled to -1..1 and gives one vector. Persons are suggested for a violation when one of
+// their violations is at least `min_similarity` alike
+#[derive(Clone, Debug)]
+pub struct EmbeddingPolicy {
+    pub model: Option<PathBuf>,
+    pub input_size: u32,
+    pub interval_seconds: u64,
+    pub batch_size: i64,
+    pub suggestions: usize,
+    pub min_similarity: f32,
+}
+
+#[derive(Clone, Debug)]
+pub enum BlobStoreConfig {
+    Filesystem {
+        root: PathBuf,
+    },
+    S3 {
+        bucket: String,
+        region: String,
+        endpoint: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct RetentionPolicy {
+    pub compact_after_days: Option<u32>,
+    pub archive_after_days: Option<u32>,
+    pub purge_after_days: Option<u32>,
+    pub archive_dir: PathBuf,
+    pub interval_hours: u64,
+    pub jpeg_quality: u8,
+    pub max_dimension: u32,
 }
 
 impl ServerConfig {
@@ -20,6 +179,92 @@ impl ServerConfig {
             database_url: {
                 std::env::var("CLIENT_DB_URL").expect("Please set env: DB_FOR_CLIENT_URL")
             },
+            retention: RetentionPolicy {
+                compact_after_days: optional_env("RETENTION_COMPACT_DAYS"),
+                archive_after_days: optional_env("RETENTION_ARCHIVE_DAYS"),
+                purge_after_days: optional_env("RETENTION_PURGE_DAYS"),
+                archive_dir: std::env::var("RETENTION_ARCHIVE_DIR")
+                    .unwrap_or_else(|_| String::from("archive"))
+                    .into(),
+                interval_hours: optional_env("RETENTION_INTERVAL_HOURS").unwrap_or(24),
+                jpeg_quality: optional_env("RETENTION_JPEG_QUALITY").unwrap_or(70),
+                max_dimension: optional_env("RETENTION_MAX_DIMENSION").unwrap_or(480),
+            },
+            blob_store: match std::env::var("BLOB_STORE").as_deref() {
+                Ok("s3") => BlobStoreConfig::S3 {
+                    bucket: std::env::var("S3_BUCKET").expect("Please set env: S3_BUCKET"),
+                    region: std::env::var("S3_REGION").unwrap_or_else(|_| String::from("us-east-1")),
+                    endpoint: std::env::var("S3_ENDPOINT").ok(),
+                },
+                Ok("fs") | Err(_) => BlobStoreConfig::Filesystem {
+                    root: std::env::var("BLOB_DIR")
+                        .unwrap_or_else(|_| String::from("blobs"))
+                        .into(),
+                },
+                Ok(_) => panic!("Invalid BLOB_STORE"),
+            },
+            password: PasswordPolicy {
+                memory_kib: optional_env("ARGON2_MEMORY_KIB").unwrap_or(19456),
+                iterations: optional_env("ARGON2_ITERATIONS").unwrap_or(2),
+                parallelism: optional_env("ARGON2_PARALLELISM").unwrap_or(1),
+            },
+            login: LoginPolicy {
+                max_failures: optional_env("LOGIN_MAX_FAILURES").unwrap_or(5),
+                max_address_failures: optional_env("LOGIN_MAX_ADDRESS_FAILURES").unwrap_or(50),
+                window_minutes: optional_env("LOGIN_WINDOW_MINUTES").unwrap_or(15),
+                lockout_minutes: optional_env("LOGIN_LOCKOUT_MINUTES").unwrap_or(15),
+                delay_ms: optional_env("LOGIN_DELAY_MS").unwrap_or(250),
+                max_delay_ms: optional_env("LOGIN_MAX_DELAY_MS").unwrap_or(8000),
+                totp_issuer: std::env::var("TOTP_ISSUER")
+                    .unwrap_or_else(|_| String::from("UNC AI Surveillance")),
+            },
+            trusted_proxies: std::env::var("TRUSTED_PROXIES")
+                .map(|proxies| {
+                    proxies
+                        .split(',')
+                        .map(|proxy| proxy.trim())
+                        .filter(|proxy| !proxy.is_empty())
+                        .map(|proxy| proxy.parse().expect("Invalid TRUSTED_PROXIES"))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            attendance: AttendancePolicy {
+                early_minutes: optional_env("ATTENDANCE_EARLY_MINUTES").unwrap_or(30),
+                grace_minutes: optional_env("ATTENDANCE_GRACE_MINUTES").unwrap_or(15),
+                interval_minutes: optional_env("ATTENDANCE_INTERVAL_MINUTES").unwrap_or(5),
+            },
+            clips: ClipPolicy {
+                seconds_before: optional_env("CLIP_SECONDS_BEFORE").unwrap_or(5),
+                seconds_after: optional_env("CLIP_SECONDS_AFTER").unwrap_or(5),
+                fps: optional_env("CLIP_FPS").unwrap_or(10),
+            },
+            recording: RecordingPolicy {
+                dir: std::env::var("RECORDING_DIR").ok().map(PathBuf::from),
+                segment_seconds: optional_env("RECORDING_SEGMENT_SECONDS").unwrap_or(10),
+                retention_days: optional_env("RECORDING_RETENTION_DAYS").unwrap_or(7),
+                quota_mb: optional_env("RECORDING_QUOTA_MB").unwrap_or(20480),
+                ffmpeg: std::env::var("FFMPEG_PATH")
+                    .unwrap_or_else(|_| String::from("ffmpeg"))
+                    .into(),
+            },
+            privacy: PrivacyPolicy {
+                mode: optional_env("PRIVACY_MODE").unwrap_or(PrivacyMode::Faces),
+                face_cascade: std::env::var("PRIVACY_FACE_CASCADE")
+                    .unwrap_or_else(|_| {
+                        String::from("/usr/share/opencv4/haarcascades/haarcascade_frontalface_default.xml")
+                    })
+                    .into(),
+            },
+            embedding: EmbeddingPolicy {
+                model: std::env::var("EMBEDDING_MODEL").ok().map(PathBuf::from),
+                input_size: optional_env("EMBEDDING_INPUT_SIZE").unwrap_or(112),
+                interval_seconds: optional_env("EMBEDDING_INTERVAL_SECONDS").unwrap_or(60),
+                batch_size: optional_env("EMBEDDING_BATCH_SIZE").unwrap_or(50),
+                suggestions: optional_env("EMBEDDING_SUGGESTIONS").unwrap_or(5),
+                min_similarity: optional_env("EMBEDDING_MIN_SIMILARITY").unwrap_or(0.5),
+            },
+            jwt: JwtConfig::load(),
+            credentials: CredentialKeysConfig::load(),
         }
     }
 
@@ -27,3 +272,132 @@ impl ServerConfig {
         SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), self.port)
     }
 }
+
+impl JwtConfig {
+    fn load() -> Self {
+        let (signing_kid, keys) = match std::env::var("JWT_KEYS_FILE") {
+            Ok(path) => {
+                let file = std::fs::read_to_string(&path)
+                    .unwrap_or_else(|_| panic!("Cannot read JWT_KEYS_FILE: {path}"));
+                let file: JwtKeysFile =
+                    serde_yaml::from_str(&file).expect("Invalid JWT_KEYS_FILE");
+
+                (file.signing_kid, file.keys)
+            }
+            Err(_) => (
+                String::from("default"),
+                vec![JwtKeyConfig {
+                    kid: String::from("default"),
+                    algorithm: Algorithm::HS256,
+                    secret: Some(
+                        std::env::var("JWT_SECRET")
+                            .expect("Please set env: JWT_SECRET or JWT_KEYS_FILE"),
+                    ),
+                    private_key: None,
+                    public_key: None,
+                }],
+            ),
+        };
+
+        Self {
+            issuer: std::env::var("JWT_ISSUER")
+                .unwrap_or_else(|_| String::from("unc-ai-surveillance-server")),
+            audience: std::env::var("JWT_AUDIENCE")
+                .unwrap_or_else(|_| String::from("unc-ai-surveillance-client")),
+            access_ttl_minutes: optional_env("JWT_ACCESS_TTL_MINUTES").unwrap_or(15),
+            refresh_ttl_days: optional_env("JWT_REFRESH_TTL_DAYS").unwrap_or(15),
+            signing_kid,
+            keys,
+        }
+    }
+}
+
+impl CredentialKeysConfig {
+    fn load() -> Self {
+        match std::env::var("CREDENTIAL_KEYS_FILE") {
+            Ok(path) => {
+                let file = std::fs::read_to_string(&path)
+                    .unwrap_or_else(|_| panic!("Cannot read CREDENTIAL_KEYS_FILE: {path}"));
+
+                serde_yaml::from_str(&file).expect("Invalid CREDENTIAL_KEYS_FILE")
+            }
+            Err(_) => Self {
+                active_kid: String::from("default"),
+                keys: vec![CredentialKeyConfig {
+                    kid: String::from("default"),
+                    key: std::env::var("CREDENTIAL_KEY")
+                        .expect("Please set env: CREDENTIAL_KEY or CREDENTIAL_KEYS_FILE"),
+                }],
+            },
+        }
+    }
+}
+
+impl LoginPolicy {
+    // doubles with every recent failure so guessing slows down long before the lockout
+    pub fn delay(&self, failures: i64) -> std::time::Duration {
+        let exponent = failures.saturating_sub(1).clamp(0, 16) as u32;
+
+        std::time::Duration::from_millis(
+            self.delay_ms
+                .saturating_mul(2u64.pow(exponent))
+                .min(self.max_delay_ms),
+        )
+    }
+}
+
+impl RetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.compact_after_days.is_some()
+            || self.archive_after_days.is_some()
+            || self.purge_after_days.is_some()
+    }
+}
+
+impl ClipPolicy {
+    // CLIP_FPS=0 turns clips off
+    pub fn is_enabled(&self) -> bool {
+        self.fps > 0
+    }
+}
+
+impl RecordingPolicy {
+    // cameras are only recorded when RECORDING_DIR is set
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+}
+
+impl EmbeddingPolicy {
+    // suggestions are only made when EMBEDDING_MODEL is set
+    pub fn is_enabled(&self) -> bool {
+        self.model.is_some()
+    }
+
+    // Vectors of different models cannot be compared, each is stored under the model file name
+    pub fn model_name(&self) -> Option<String> {
+        self.model
+            .as_ref()
+            .and_then(|model| model.file_stem())
+            .map(|name| name.to_string_lossy().into_owned())
+    }
+}
+
+impl FromStr for PrivacyMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "off" => Ok(PrivacyMode::Off),
+            "faces" => Ok(PrivacyMode::Faces),
+            "bodies" => Ok(PrivacyMode::Bodies),
+            _ => Err(()),
+        }
+    }
+}
+
+fn optional_env<T: FromStr>(name: &str) -> Option<T> {
+    std::env::var(name)
+        .ok()
+        .map(|value| value.parse::<T>().unwrap_or_else(|_| panic!("Invalid {name}")))
+}
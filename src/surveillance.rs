@@ -9,6 +9,7 @@ use ndarray::IxDyn;
 use ndarray::{s, Array, Axis};
 use opencv::core::{Point3_, CV_8UC3};
 use opencv::videoio::{VideoCaptureTraitConst, CAP_PROP_FRAME_HEIGHT, CAP_PROP_FRAME_WIDTH};
+use opencv::videoio::{VideoWriter, VideoWriterTrait, VideoWriterTraitConst};
 use opencv::{
     self,
     core::{Mat, Size_},
@@ -17,9 +18,16 @@ use opencv::{
     videoio::{VideoCapture, VideoCaptureTrait, CAP_FFMPEG},
 };
 use ort::{tensor::InputTensor, ExecutionProvider};
+use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tokio::sync::Mutex;
 
-use crate::data::AppData;
+use crate::clip_buffer::ClipBuffer;
+use crate::data::{AppData, CredentialVault};
+use crate::logging::{LogLevel, LogRecorder, LoggableError};
+use crate::models::{CameraCredential, ViolationKind};
+use crate::server_config::ClipPolicy;
 
 pub struct Surveillance<'s>(Arc<RwLock<SurveillanceInner<'s>>>);
 
@@ -29,8 +37,12 @@ struct SurveillanceInner<'s> {
 }
 
 impl<'s> Surveillance<'s> {
-    pub fn new(mut connection: PooledConnection<ConnectionManager<PgConnection>>) -> Self {
-        use crate::schema::cameras;
+    pub fn new(
+        mut connection: PooledConnection<ConnectionManager<PgConnection>>,
+        vault: &CredentialVault,
+        clips: &ClipPolicy,
+    ) -> Self {
+        use crate::schema::{camera_credentials, cameras};
 
         let entries: Vec<(uuid::Uuid, String, String, String)> = cameras::table
             .select((
@@ -43,12 +55,21 @@ impl<'s> Surveillance<'s> {
             .load(&mut connection)
             .unwrap();
 
+        let credentials: HashMap<uuid::Uuid, CameraCredential> = camera_credentials::table
+            .load::<CameraCredential>(&mut connection)
+            .unwrap()
+            .into_iter()
+            .map(|credential| (credential.camera_id, credential))
+            .collect();
+
         Self(Arc::new(RwLock::new(SurveillanceInner {
             cameras: entries
                 .iter()
                 .map(|(id, url, label, area_code)| {
-                    let capture: Option<VideoCapture> =
-                        VideoCapture::from_file(url, CAP_FFMPEG).ok();
+                    // a camera whose credentials cannot be opened is left unconnected
+                    let capture: Option<VideoCapture> = vault
+                        .camera_url(url, credentials.get(id))
+                        .and_then(|url| VideoCapture::from_file(&url, CAP_FFMPEG).ok());
 
                     let mut width = 640.0;
                     let mut height = 640.0;
@@ -80,6 +101,7 @@ impl<'s> Surveillance<'s> {
                         }),
                         width: width as _,
                         height: height as _,
+                        clip: ClipBuffer::new(clips.clone()),
                     }
                 })
                 .collect(),
@@ -102,45 +124,58 @@ impl<'s> Surveillance<'s> {
         })))
     }
 
-    pub async fn run(&mut self, app_data: &AppData<'s>) {
+    pub async fn run(&mut self, app_data: &AppData<'s>, logger: &web::Data<Mutex<LogRecorder>>) {
         loop {
             for camera in self.0.clone().write().unwrap().cameras.iter_mut() {
                 if let Some(frame) = camera.next() {
                     frame.fit(camera.width(), camera.height());
                     let predictions = self.infer(&frame);
                     for predicted_box in predictions {
-                        match predicted_box.class {
-                            Label::FacingBackwards => {
-                                app_data
-                                    .store_violation(
-                                        camera.area_code(),
-                                        crate::models::ViolationKind::FootTraffic,
-                                        predicted_box.get_image(&frame),
-                                    )
-                                    .await
-                            }
-                            Label::MaskWearedIncorrect => {
-                                app_data
-                                    .store_violation(
-                                        camera.area_code(),
-                                        crate::models::ViolationKind::FacemaskProtocol,
-                                        predicted_box.get_image(&frame),
-                                    )
-                                    .await
+                        let violation_kind = match predicted_box.class {
+                            Label::FacingBackwards => ViolationKind::FootTraffic,
+                            Label::MaskWearedIncorrect | Label::WithoutMask => {
+                                ViolationKind::FacemaskProtocol
                             }
-                            Label::WithoutMask => {
-                                app_data
-                                    .store_violation(
-                                        camera.area_code(),
-                                        crate::models::ViolationKind::FacemaskProtocol,
-                                        predicted_box.get_image(&frame),
-                                    )
-                                    .await
+                            _ => continue,
+                        };
+
+                        // a blob store that is down loses this detection, not the cameras
+                        match app_data
+                            .store_violation(
+                                camera.area_code(),
+                                violation_kind,
+                                predicted_box.get_image(&frame),
+                            )
+                            .await
+                        {
+                            Ok(violation) => camera.clip.record(Instant::now(), violation),
+                            Err(error) => {
+                                let log = LoggableError::new(
+                                    format!(
+                                        "Violation in area {} was not stored: {error}",
+                                        camera.area_code()
+                                    ),
+                                    LogLevel::Error,
+                                );
+                                logger.lock().await.record(&log, None);
                             }
-                            _ => (),
                         }
                     }
                 }
+
+                if let Some((violations, frames, fps)) = camera.clip.finished(Instant::now()) {
+                    let pool = app_data.database_pool();
+                    let blob_store = app_data.blob_store();
+
+                    // a clip that fails to encode or store leaves its violations with the image alone
+                    tokio::spawn(async move {
+                        if let Ok(Ok(clip)) =
+                            tokio::task::spawn_blocking(move || encode_clip(&frames, fps)).await
+                        {
+                            AppData::store_clip(pool, blob_store, violations, clip).await.ok();
+                        }
+                    });
+                }
             }
         }
     }
@@ -236,6 +271,7 @@ pub struct RtspCamera {
     buffer: Frame,
     width: u32,
     height: u32,
+    clip: ClipBuffer<Frame>,
 }
 
 unsafe impl Sync for RtspCamera {}
@@ -255,6 +291,7 @@ impl RtspCamera {
                 .read(&mut self.buffer.0)
                 .ok()
         {
+            self.clip.push(Instant::now(), || Frame(self.buffer.0.clone()));
             return Some(Frame(self.buffer.0.clone()));
         }
 
@@ -274,6 +311,41 @@ impl RtspCamera {
     }
 }
 
+// MP4 in H.264 when the OpenCV build can encode it, MPEG-4 part 2 otherwise
+fn encode_clip(frames: &[Frame], fps: f64) -> opencv::Result<Vec<u8>> {
+    let io_error = |error: std::io::Error| opencv::Error::new(opencv::core::StsError, error.to_string());
+
+    let size = frames[0].0.size()?;
+    let file = tempfile::Builder::new()
+        .suffix(".mp4")
+        .tempfile()
+        .map_err(io_error)?;
+    let path = file.path().to_string_lossy().into_owned();
+
+    let mut writer = None;
+
+    for [c1, c2, c3, c4] in [['a', 'v', 'c', '1'], ['m', 'p', '4', 'v']] {
+        let candidate = VideoWriter::new(&path, VideoWriter::fourcc(c1, c2, c3, c4)?, fps, size, true)?;
+
+        if candidate.is_opened()? {
+            writer = Some(candidate);
+            break;
+        }
+    }
+
+    let mut writer = writer
+        .ok_or_else(|| opencv::Error::new(opencv::core::StsError, "No MP4 encoder available".to_owned()))?;
+
+    // a camera that changed resolution mid clip only has the frames of the first one kept
+    for frame in frames.iter().filter(|frame| frame.0.size().ok() == Some(size)) {
+        writer.write(&frame.0)?;
+    }
+
+    writer.release()?;
+
+    std::fs::read(file.path()).map_err(io_error)
+}
+
 pub struct Frame(Mat);
 
 impl Frame {
@@ -11,4 +11,950 @@ fn test_hexadecimal() {
 
     assert!(u128::from_hexadecimal("A00000060A344").unwrap() == 0xA00000060A344,
     "Erroneous conversion from hexadecimal");
-}
\ No newline at end of file
+}
+#[test]
+fn test_jwt_key_rotation() {
+    use crate::data::JwtKeys;
+    use crate::server_config::{JwtConfig, JwtKeyConfig};
+
+    let key = |kid: &str, secret: &str| JwtKeyConfig {
+        kid: kid.to_owned(),
+        algorithm: jsonwebtoken::Algorithm::HS256,
+        secret: Some(secret.to_owned()),
+        private_key: None,
+        public_key: None,
+    };
+
+    let config = |signing_kid: &str, audience: &str, keys: Vec<JwtKeyConfig>| JwtConfig {
+        issuer: "issuer".to_owned(),
+        audience: audience.to_owned(),
+        access_ttl_minutes: 15,
+        refresh_ttl_days: 15,
+        signing_kid: signing_kid.to_owned(),
+        keys,
+    };
+
+    let session_id = uuid::Uuid::new_v4();
+    let old = JwtKeys::load(&config("old", "client", vec![key("old", "secret-1")]));
+    let jwt = old.encode(session_id).unwrap();
+
+    let rotated = JwtKeys::load(&config("new", "client", vec![key("new", "secret-2"), key("old", "secret-1")]));
+    assert!(rotated.decode(&jwt).unwrap().session_id == session_id,
+        "Token signed by a retired key was rejected");
+
+    let removed = JwtKeys::load(&config("new", "client", vec![key("new", "secret-2")]));
+    assert!(removed.decode(&jwt).is_err(), "Token signed by a removed key was accepted");
+
+    let other_audience = JwtKeys::load(&config("old", "other", vec![key("old", "secret-1")]));
+    assert!(other_audience.decode(&jwt).is_err(), "Token for another audience was accepted");
+}
+
+#[test]
+fn test_totp_rfc_vectors() {
+    use crate::data::totp;
+
+    let secret = b"12345678901234567890";
+
+    // RFC 4226 appendix D
+    assert_eq!(totp::hotp(secret, 0), "755224");
+    assert_eq!(totp::hotp(secret, 9), "520489");
+
+    // RFC 6238 appendix B at T = 59, truncated to six digits
+    assert_eq!(totp::verify(secret, "287082", 59, None), Some(1));
+    assert_eq!(totp::verify(secret, "287082", 59, Some(1)), None, "A used step was accepted again");
+    assert_eq!(totp::verify(secret, "287083", 59, None), None);
+}
+
+#[test]
+fn test_permission_policy() {
+    use crate::models::{Permission, UserRole, POLICY};
+
+    for (index, (permission, name, _)) in POLICY.iter().enumerate() {
+        assert!(POLICY[index + 1..].iter().all(|(other, other_name, _)| other != permission && other_name != name),
+            "Permission {name} is listed twice");
+        assert_eq!(permission.name(), *name);
+    }
+
+    assert!(Permission::LogsRead.allows(UserRole::SystemAdmin));
+    assert!(!Permission::LogsRead.allows(UserRole::SecurityHead), "Logs are open to security heads");
+    assert!(!Permission::UsersWrite.allows(UserRole::SecurityGuard), "Guards can manage users");
+    assert!(!Permission::ViolationsReadAllAreas.allows(UserRole::SecurityGuard), "Guards see every area");
+    assert!(!Permission::CamerasWrite.allows(UserRole::SecurityGuard), "Guards can change cameras");
+}
+
+#[actix_web::test]
+async fn test_route_policy() {
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use crate::models::{Access, ROUTES};
+
+    let app = test::init_service(
+        App::new()
+            .service(crate::routes::users::scope())
+            .service(crate::routes::logs::scope())
+            .service(crate::routes::areas::scope())
+            .service(crate::routes::violations::scope())
+            .service(crate::routes::persons::scope())
+            .service(crate::routes::attendance::scope())
+            .service(crate::routes::socket::resource()),
+    )
+    .await;
+
+    // every entry of the table reaches its own handler, without app data it fails there instead of routing
+    for (index, (method, path, _)) in ROUTES.iter().enumerate() {
+        assert!(ROUTES[index + 1..].iter().all(|(other_method, other_path, _)| (other_method, other_path) != (method, path)),
+            "Route {method} {path} is listed twice");
+
+        let request = test::TestRequest::default()
+            .method(method.parse().unwrap())
+            .uri(path)
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        let status = response.status();
+
+        assert!(status != StatusCode::NOT_FOUND && status != StatusCode::METHOD_NOT_ALLOWED,
+            "Route {method} {path} is not served");
+        assert_eq!(response.request().match_pattern().as_deref(), Some(*path),
+            "Route {method} {path} is served by another handler");
+    }
+
+    // and whatever else is served under those paths has an entry too, the session extractor
+    // refuses a route without one so a handler left out fails closed either way
+    for path in ROUTES.iter().map(|(_, path, _)| *path) {
+        for method in ["GET", "POST", "PUT", "PATCH", "DELETE"] {
+            let request = test::TestRequest::default()
+                .method(method.parse().unwrap())
+                .uri(path)
+                .to_request();
+            let response = test::call_service(&app, request).await;
+
+            if response.status() == StatusCode::NOT_FOUND || response.status() == StatusCode::METHOD_NOT_ALLOWED {
+                continue;
+            }
+
+            let pattern = response.request().match_pattern().unwrap();
+            assert!(Access::of(method, &pattern).is_some(), "Route {method} {pattern} has no access policy");
+        }
+    }
+}
+
+#[test]
+fn test_roster_shifts() {
+    use chrono::NaiveDate;
+    use crate::models::{GuardAssignment, Recurrence};
+
+    let at = |day, hour| NaiveDate::from_ymd_opt(2023, 6, day).unwrap().and_hms_opt(hour, 0, 0).unwrap();
+
+    // a night shift every day until the third
+    let assignment = GuardAssignment {
+        id: uuid::Uuid::nil(),
+        user_id: uuid::Uuid::nil(),
+        area_code: "GATE1".to_owned(),
+        start_time: at(1, 22),
+        end_time: at(2, 6),
+        recurrence: Recurrence::Daily,
+        repeat_until: NaiveDate::from_ymd_opt(2023, 6, 3),
+    };
+
+    assert!(assignment.covers(at(1, 23)));
+    assert!(assignment.covers(at(3, 2)));
+    assert!(!assignment.covers(at(1, 21)), "The shift started early");
+    assert!(!assignment.covers(at(2, 12)), "The shift covered the day");
+    assert!(assignment.covers(at(4, 5)), "The last shift was cut at midnight");
+    assert!(!assignment.covers(at(4, 23)), "The shift repeated past its end date");
+
+    assert_eq!(assignment.shifts(at(2, 0), at(30, 0)).collect::<Vec<_>>(),
+        vec![(at(1, 22), at(2, 6)), (at(2, 22), at(3, 6)), (at(3, 22), at(4, 6))]);
+
+    let once = GuardAssignment { recurrence: Recurrence::Once, repeat_until: None, ..assignment };
+    assert_eq!(once.shifts(at(1, 0), at(30, 0)).count(), 1);
+    assert!(!once.covers(at(2, 23)), "A single shift repeated");
+}
+
+#[test]
+fn test_credential_vault() {
+    use crate::data::credential_vault::{join_credentials, split_credentials};
+    use crate::data::CredentialVault;
+    use crate::server_config::{CredentialKeyConfig, CredentialKeysConfig};
+
+    let key = |kid: &str, byte: u8| CredentialKeyConfig {
+        kid: kid.to_owned(),
+        key: base64::Engine::encode(&base64::engine::general_purpose::STANDARD, [byte; 32]),
+    };
+
+    let config = |active_kid: &str, keys: Vec<CredentialKeyConfig>| CredentialKeysConfig {
+        active_kid: active_kid.to_owned(),
+        keys,
+    };
+
+    let url = "rtsp://admin:p@ss@192.168.100.2:554/stream?channel=1";
+    let (stripped, userinfo) = split_credentials(url);
+    assert_eq!(stripped, "rtsp://192.168.100.2:554/stream?channel=1");
+    assert_eq!(userinfo.as_deref(), Some("admin:p@ss"));
+    assert_eq!(join_credentials(&stripped, "admin:p@ss"), url);
+    assert_eq!(split_credentials("rtsp://192.168.100.2/a@b").1, None, "Path was taken for credentials");
+
+    let camera_id = uuid::Uuid::new_v4();
+    let old = CredentialVault::load(&config("old", vec![key("old", 1)]));
+    let sealed = old.seal(camera_id, "admin:p@ss").unwrap();
+    assert_eq!(old.open(&sealed).as_deref(), Some("admin:p@ss"));
+
+    let mut moved = sealed.clone();
+    moved.camera_id = uuid::Uuid::new_v4();
+    assert!(old.open(&moved).is_none(), "Credentials opened for another camera");
+
+    let rotated = CredentialVault::load(&config("new", vec![key("new", 2), key("old", 1)]));
+    let resealed = rotated.seal(camera_id, &rotated.open(&sealed).unwrap()).unwrap();
+    assert_eq!(resealed.key_id, "new");
+
+    let removed = CredentialVault::load(&config("new", vec![key("new", 2)]));
+    assert!(removed.open(&sealed).is_none(), "Credentials opened without their key");
+    assert_eq!(removed.open(&resealed).as_deref(), Some("admin:p@ss"));
+}
+
+#[test]
+fn test_onvif_mock_device() {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, UdpSocket};
+    use std::time::Duration;
+    use base64::Engine;
+    use crate::onvif::{self, OnvifClient, OnvifError};
+
+    // Answers like a camera would, with a fault when the password digest does not check out
+    fn answer(request: &str, base_url: &str) -> (u16, String) {
+        let document = roxmltree::Document::parse(request).unwrap();
+        let text = |name: &str| {
+            document
+                .descendants()
+                .find(|node| node.has_tag_name(name))
+                .and_then(|node| node.text())
+                .unwrap_or_default()
+        };
+
+        let nonce = base64::engine::general_purpose::STANDARD.decode(text("Nonce")).unwrap_or_default();
+        let expected = ring::digest::digest(
+            &ring::digest::SHA1_FOR_LEGACY_USE_ONLY,
+            &[&nonce[..], text("Created").as_bytes(), b"p@ss:1"].concat(),
+        );
+
+        if text("Username") != "admin"
+            || text("Password") != base64::engine::general_purpose::STANDARD.encode(expected.as_ref())
+        {
+            return (
+                400,
+                concat!(
+                    r#"<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope" xmlns:ter="http://www.onvif.org/ver10/error">"#,
+                    "<s:Body><s:Fault><s:Code><s:Value>s:Sender</s:Value><s:Subcode><s:Value>ter:NotAuthorized</s:Value>",
+                    "</s:Subcode></s:Code><s:Reason><s:Text>Sender not authorized</s:Text></s:Reason></s:Fault></s:Body></s:Envelope>",
+                )
+                .into(),
+            );
+        }
+
+        let body = match document.descendants().find(|node| node.has_tag_name("Body")).and_then(|body| body.first_element_child()) {
+            Some(call) if call.has_tag_name("GetCapabilities") => format!(
+                "<tds:GetCapabilitiesResponse><tds:Capabilities><tt:Media><tt:XAddr>{base_url}/media</tt:XAddr></tt:Media>\
+                 <tt:PTZ><tt:XAddr>{base_url}/ptz</tt:XAddr></tt:PTZ></tds:Capabilities></tds:GetCapabilitiesResponse>"
+            ),
+            Some(call) if call.has_tag_name("GetProfiles") => concat!(
+                r#"<trt:GetProfilesResponse><trt:Profiles token="main"><tt:Name>Main</tt:Name><tt:VideoEncoderConfiguration>"#,
+                "<tt:Resolution><tt:Width>1920</tt:Width><tt:Height>1080</tt:Height></tt:Resolution>",
+                r#"</tt:VideoEncoderConfiguration><tt:PTZConfiguration token="ptz"/></trt:Profiles>"#,
+                r#"<trt:Profiles token="sub"><tt:Name>Sub</tt:Name></trt:Profiles></trt:GetProfilesResponse>"#,
+            )
+            .into(),
+            Some(call) if call.has_tag_name("GetStreamUri") => format!(
+                "<trt:GetStreamUriResponse><trt:MediaUri><tt:Uri>rtsp://127.0.0.1:554/{}</tt:Uri></trt:MediaUri></trt:GetStreamUriResponse>",
+                text("ProfileToken")
+            ),
+            Some(call) if call.has_tag_name("GetPresets") => concat!(
+                r#"<tptz:GetPresetsResponse><tptz:Preset token="1"><tt:Name>Gate</tt:Name></tptz:Preset>"#,
+                r#"<tptz:Preset token="2"/></tptz:GetPresetsResponse>"#,
+            )
+            .into(),
+            Some(call) if call.has_tag_name("SetPreset") => {
+                assert_eq!(text("PresetName"), "Loading <bay>");
+                "<tptz:SetPresetResponse><tptz:PresetToken>3</tptz:PresetToken></tptz:SetPresetResponse>".into()
+            }
+            Some(call) if call.has_tag_name("GotoPreset") && text("PresetToken") == "1" => {
+                "<tptz:GotoPresetResponse/>".into()
+            }
+            _ => {
+                return (
+                    500,
+                    concat!(
+                        r#"<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope"><s:Body><s:Fault>"#,
+                        "<s:Reason><s:Text>No such preset</s:Text></s:Reason></s:Fault></s:Body></s:Envelope>",
+                    )
+                    .into(),
+                )
+            }
+        };
+
+        (
+            200,
+            format!(
+                concat!(
+                    r#"<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope" xmlns:tds="http://www.onvif.org/ver10/device/wsdl""#,
+                    r#" xmlns:trt="http://www.onvif.org/ver10/media/wsdl" xmlns:tptz="http://www.onvif.org/ver20/ptz/wsdl""#,
+                    r#" xmlns:tt="http://www.onvif.org/ver10/schema"><s:Body>{}</s:Body></s:Envelope>"#,
+                ),
+                body
+            ),
+        )
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let server_url = base_url.clone();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+
+                if line.trim().is_empty() {
+                    break;
+                }
+
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+
+            let mut request = vec![0; length];
+            reader.read_exact(&mut request).unwrap();
+
+            let (status, body) = answer(&String::from_utf8(request).unwrap(), &server_url);
+            write!(
+                stream,
+                "HTTP/1.1 {status} X\r\nContent-Type: application/soap+xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        }
+    });
+
+    let timeout = Duration::from_secs(5);
+    let (username, password) = onvif::credentials(&onvif::userinfo("admin", "p@ss:1"));
+    assert_eq!((username.as_str(), password.as_str()), ("admin", "p@ss:1"));
+
+    let client = OnvifClient::new(&base_url, Some((username, password)), timeout);
+    let services = client.services().unwrap();
+    let media_url = services.media.unwrap();
+    let ptz_url = services.ptz.unwrap();
+    assert_eq!(media_url, format!("{base_url}/media"));
+
+    let profiles = client.profiles(&media_url).unwrap();
+    assert_eq!(profiles.len(), 2);
+    assert_eq!((profiles[0].token.as_str(), profiles[0].width, profiles[0].ptz), ("main", Some(1920), true));
+    assert_eq!((profiles[1].name.as_deref(), profiles[1].width, profiles[1].ptz), (Some("Sub"), None, false));
+    assert_eq!(client.stream_url(&media_url, "sub").unwrap(), "rtsp://127.0.0.1:554/sub");
+
+    let presets = client.presets(&ptz_url, "main").unwrap();
+    assert_eq!(presets.len(), 2);
+    assert_eq!((presets[0].token.as_str(), presets[0].name.as_deref()), ("1", Some("Gate")));
+    assert_eq!(client.set_preset(&ptz_url, "main", "Loading <bay>").unwrap(), "3");
+    client.goto_preset(&ptz_url, "main", "1").unwrap();
+
+    match client.goto_preset(&ptz_url, "main", "9") {
+        Err(OnvifError::Fault(message)) => assert_eq!(message, "No such preset"),
+        other => panic!("Expected a fault, got {:?}", other.map(|_| ())),
+    }
+
+    // the services are announced on 127.0.0.1, which is not the host the client was given
+    let elsewhere = OnvifClient::new(
+        &base_url.replace("127.0.0.1", "localhost"),
+        Some(onvif::credentials(&onvif::userinfo("admin", "p@ss:1"))),
+        timeout,
+    );
+    assert!(matches!(elsewhere.services(), Err(OnvifError::Response(_))));
+
+    let refused = OnvifClient::new(&base_url, Some(("admin".into(), "wrong".into())), timeout);
+    assert!(matches!(refused.services(), Err(OnvifError::Unauthorized)));
+
+    // a device that answers the probe twice is listed once
+    let device = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let device_address = device.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        let (length, sender) = device.recv_from(&mut buffer).unwrap();
+        assert!(String::from_utf8_lossy(&buffer[..length]).contains("NetworkVideoTransmitter"));
+
+        let matches = concat!(
+            r#"<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope" xmlns:a="http://schemas.xmlsoap.org/ws/2004/08/addressing""#,
+            r#" xmlns:d="http://schemas.xmlsoap.org/ws/2005/04/discovery"><s:Body><d:ProbeMatches><d:ProbeMatch>"#,
+            "<a:EndpointReference><a:Address>urn:uuid:6c1d4a1e-0000-4000-8000-000000000001</a:Address></a:EndpointReference>",
+            "<d:Scopes>onvif://www.onvif.org/name/Gate%20Camera onvif://www.onvif.org/hardware/PTZ-100</d:Scopes>",
+            "<d:XAddrs>http://192.168.100.7/onvif/device_service</d:XAddrs>",
+            "</d:ProbeMatch></d:ProbeMatches></s:Body></s:Envelope>",
+        );
+
+        device.send_to(matches.as_bytes(), sender).unwrap();
+        device.send_to(matches.as_bytes(), sender).unwrap();
+        device.send_to(b"not xml", sender).unwrap();
+    });
+
+    let devices = onvif::probe(device_address, Duration::from_millis(500)).unwrap();
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].name.as_deref(), Some("Gate Camera"));
+    assert_eq!(devices[0].hardware.as_deref(), Some("PTZ-100"));
+    assert_eq!(devices[0].device_urls, ["http://192.168.100.7/onvif/device_service"]);
+}
+
+#[test]
+fn test_recording_playlist() {
+    use chrono::{Duration, NaiveDate};
+    use crate::models::RecordingSegment;
+    use crate::recorder::{parse_segment, playlist};
+
+    let (name, seconds) = parse_segment("/recordings/cam/20230606-101010.ts,20.000000,29.600000\n").unwrap();
+    assert_eq!(name, "20230606-101010.ts");
+    assert!((seconds - 9.6).abs() < 1e-9);
+    assert_eq!(parse_segment("20230606-101010.ts,30.0,20.0"), None, "A segment ended before it started");
+    assert_eq!(parse_segment("[segment @ 0x5581] Opening file"), None);
+
+    let start = NaiveDate::from_ymd_opt(2023, 6, 6).unwrap().and_hms_opt(10, 0, 0).unwrap();
+    let segment = |offset: i64, millis: i64| RecordingSegment {
+        id: uuid::Uuid::new_v4(),
+        camera_id: uuid::Uuid::nil(),
+        start_time: start + Duration::seconds(offset),
+        end_time: start + Duration::seconds(offset) + Duration::milliseconds(millis),
+        bytes: 0,
+    };
+
+    // two back to back, then one after the camera was gone for a minute
+    let segments = [segment(0, 10000), segment(10, 9500), segment(80, 10200)];
+    let playlist = playlist(&segments);
+    let lines: Vec<&str> = playlist.lines().collect();
+
+    assert_eq!(lines[0], "#EXTM3U");
+    assert!(lines.contains(&"#EXT-X-TARGETDURATION:11"), "The target is below the longest segment");
+    assert_eq!(lines.last(), Some(&"#EXT-X-ENDLIST"));
+    assert_eq!(lines.iter().filter(|line| **line == "#EXT-X-DISCONTINUITY").count(), 1);
+    assert!(lines.contains(&"#EXTINF:9.500,"));
+    assert!(lines.contains(&"#EXT-X-PROGRAM-DATE-TIME:2023-06-06T10:01:20.000Z"));
+
+    let uris: Vec<String> = segments.iter().map(|segment| format!("segments/{}", segment.id)).collect();
+    assert_eq!(lines.iter().filter(|line| !line.starts_with('#')).copied().collect::<Vec<_>>(), uris);
+
+    // the discontinuity comes right before the segment after the gap
+    let gap = lines.iter().position(|line| *line == "#EXT-X-DISCONTINUITY").unwrap();
+    assert_eq!(lines[gap + 1], "#EXT-X-PROGRAM-DATE-TIME:2023-06-06T10:01:20.000Z");
+}
+
+#[test]
+fn test_privacy_redaction() {
+    use std::path::PathBuf;
+    use image::{Rgb, RgbImage};
+    use crate::privacy::{blur_regions, redact, Region};
+    use crate::server_config::{PrivacyMode, PrivacyPolicy};
+
+    let checkerboard = RgbImage::from_fn(64, 64, |x, y| {
+        if (x + y) % 2 == 0 { Rgb([255, 255, 255]) } else { Rgb([0, 0, 0]) }
+    });
+    let contrast = |image: &RgbImage, region: Region| {
+        let values: Vec<u8> = (region.y..region.y + region.height)
+            .flat_map(|y| (region.x..region.x + region.width).map(move |x| (x, y)))
+            .map(|(x, y)| image.get_pixel(x, y)[0])
+            .collect();
+        values.iter().max().unwrap() - values.iter().min().unwrap()
+    };
+
+    let face = Region { x: 16, y: 8, width: 24, height: 24 };
+    let mut image = checkerboard.clone();
+    blur_regions(&mut image, &[face]);
+
+    assert!(contrast(&image, face) < 64, "The region is not blurred");
+    assert_eq!(image.get_pixel(0, 0), checkerboard.get_pixel(0, 0));
+    assert_eq!(image.get_pixel(63, 63), checkerboard.get_pixel(63, 63));
+    assert_eq!(image.get_pixel(40, 32), checkerboard.get_pixel(40, 32));
+
+    let whole = Region { x: 0, y: 0, width: 64, height: 64 };
+    let policy = PrivacyPolicy {
+        mode: PrivacyMode::Faces,
+        face_cascade: PathBuf::from("/nonexistent/cascade.xml"),
+    };
+
+    let mut image = checkerboard.clone();
+    redact(&mut image, &policy, PrivacyMode::Off);
+    assert_eq!(image, checkerboard);
+
+    redact(&mut image, &policy, PrivacyMode::Bodies);
+    assert!(contrast(&image, whole) < 64);
+
+    // no face found, nothing is left to recognise
+    let mut image = checkerboard.clone();
+    redact(&mut image, &policy, PrivacyMode::Faces);
+    assert!(contrast(&image, whole) < 64);
+
+    assert_eq!("bodies".parse::<PrivacyMode>(), Ok(PrivacyMode::Bodies));
+    assert!("everything".parse::<PrivacyMode>().is_err());
+}
+
+#[test]
+fn test_embedding_rank() {
+    use image::{Rgb, RgbImage};
+    use crate::embedding::{input_tensor, normalize, rank};
+
+    let tensor = input_tensor(&RgbImage::from_pixel(40, 60, Rgb([255, 0, 128])), 16);
+    assert_eq!(tensor.shape(), &[1, 3, 16, 16]);
+    assert_eq!(tensor[[0, 0, 15, 3]], 1.0);
+    assert_eq!(tensor[[0, 1, 0, 0]], -1.0);
+
+    assert_eq!(normalize(vec![3.0, 4.0]), vec![0.6, 0.8]);
+    assert_eq!(normalize(vec![0.0, 0.0]), vec![0.0, 0.0], "A zero vector is left as is");
+
+    let (alice, bob, carol) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+    let violation = |_| uuid::Uuid::new_v4();
+    let candidates = vec![
+        (alice, violation(0), normalize(vec![1.0, 0.2])),
+        (alice, violation(1), normalize(vec![1.0, 0.0])),
+        (bob, violation(2), normalize(vec![0.8, 0.6])),
+        (carol, violation(3), normalize(vec![0.0, 1.0])),
+        // from another model
+        (carol, violation(4), vec![1.0, 0.0, 0.0]),
+    ];
+    let best_of_alice = candidates[1].1;
+
+    let matches = rank(&[1.0, 0.0], candidates.clone(), 0.5, 5);
+    assert_eq!(matches.iter().map(|found| found.person_id).collect::<Vec<_>>(), vec![alice, bob]);
+    assert_eq!(matches[0].violation_id, best_of_alice, "A person is suggested by the most alike violation");
+    assert!((matches[0].similarity - 1.0).abs() < 1e-6);
+    assert!((matches[1].similarity - 0.8).abs() < 1e-6);
+
+    assert_eq!(rank(&[1.0, 0.0], candidates, 0.5, 1).len(), 1);
+}
+
+#[test]
+fn test_forwarded_client() {
+    use std::net::IpAddr;
+    use crate::data::forwarded_client;
+
+    let address = |text: &str| text.parse::<IpAddr>().unwrap();
+    let proxy = address("172.18.0.1");
+    let client = address("203.0.113.7");
+
+    assert_eq!(forwarded_client(client, "198.51.100.1", &[proxy]), client, "Only a trusted proxy is believed");
+    assert_eq!(forwarded_client(proxy, "203.0.113.7", &[]), proxy, "No proxy is trusted by default");
+    assert_eq!(forwarded_client(proxy, "203.0.113.7", &[proxy]), client);
+
+    // whatever the client put before the address the proxy appended is ignored
+    assert_eq!(forwarded_client(proxy, "198.51.100.1, 203.0.113.7", &[proxy]), client);
+
+    let inner = address("10.0.0.2");
+    assert_eq!(forwarded_client(inner, "203.0.113.7, 172.18.0.1", &[proxy, inner]), client);
+    assert_eq!(forwarded_client(proxy, "garbage", &[proxy]), proxy);
+    assert_eq!(forwarded_client(proxy, "", &[proxy]), proxy);
+}
+
+#[test]
+fn test_login_delay() {
+    use std::time::Duration;
+    use crate::server_config::LoginPolicy;
+
+    let policy = LoginPolicy {
+        max_failures: 5,
+        max_address_failures: 20,
+        window_minutes: 15,
+        lockout_minutes: 15,
+        delay_ms: 250,
+        max_delay_ms: 4000,
+        totp_issuer: String::from("UNC"),
+    };
+
+    assert_eq!(policy.delay(0), Duration::from_millis(250), "A first failure is delayed as well");
+    assert_eq!(policy.delay(1), Duration::from_millis(250));
+    assert_eq!(policy.delay(2), Duration::from_millis(500));
+    assert_eq!(policy.delay(4), Duration::from_millis(2000));
+    assert_eq!(policy.delay(5), Duration::from_millis(4000));
+    assert_eq!(policy.delay(6), Duration::from_millis(4000), "The delay stops at max_delay_ms");
+    assert_eq!(policy.delay(i64::MAX), Duration::from_millis(4000));
+    assert_eq!(policy.delay(-1), Duration::from_millis(250));
+
+    let unbounded = LoginPolicy { max_delay_ms: u64::MAX, ..policy };
+    assert_eq!(unbounded.delay(17), Duration::from_millis(250 << 16), "The doubling stops before it overflows");
+    assert_eq!(unbounded.delay(100), unbounded.delay(17));
+}
+
+#[actix_web::test]
+async fn test_blob_response() {
+    use actix_web::body::to_bytes;
+    use actix_web::http::{header, StatusCode};
+    use actix_web::test::TestRequest;
+    use crate::blob_store::{bytes_response, content_key};
+
+    let key = content_key(b"abc");
+    assert_eq!(key, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert_eq!(content_key(b"abc"), key, "Identical content gets the same key");
+
+    let bytes = b"0123456789".to_vec();
+    let etag = format!("\"{}\"", content_key(&bytes));
+    let respond = |headers: &[(header::HeaderName, &str)]| {
+        let mut request = TestRequest::default();
+
+        for (name, value) in headers {
+            request = request.insert_header((name.clone(), *value));
+        }
+
+        bytes_response(bytes.clone(), "image/jpeg", &request.to_http_request())
+    };
+
+    let response = respond(&[]);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get(header::ETAG).unwrap().to_str().unwrap(), etag);
+    assert_eq!(to_bytes(response.into_body()).await.unwrap().as_ref(), b"0123456789");
+
+    let response = respond(&[(header::RANGE, "bytes=2-4")]);
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.headers().get(header::CONTENT_RANGE).unwrap(), "bytes 2-4/10");
+    assert_eq!(to_bytes(response.into_body()).await.unwrap().as_ref(), b"234");
+
+    // only the first of several ranges is served
+    let response = respond(&[(header::RANGE, "bytes=-3, 0-1")]);
+    assert_eq!(response.headers().get(header::CONTENT_RANGE).unwrap(), "bytes 7-9/10");
+
+    let response = respond(&[(header::RANGE, "bytes=20-30")]);
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(response.headers().get(header::CONTENT_RANGE).unwrap(), "bytes */10");
+
+    let response = respond(&[(header::RANGE, "bytes=2-4"), (header::IF_RANGE, &etag)]);
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+
+    let response = respond(&[(header::RANGE, "bytes=2-4"), (header::IF_RANGE, "\"stale\"")]);
+    assert_eq!(response.status(), StatusCode::OK, "A stale If-Range gets the whole image");
+
+    let matching = format!("\"other\", W/{etag}");
+    for if_none_match in [etag.as_str(), matching.as_str(), "*"] {
+        let response = respond(&[(header::IF_NONE_MATCH, if_none_match)]);
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{if_none_match}");
+        assert_eq!(response.headers().get(header::ETAG).unwrap().to_str().unwrap(), etag);
+    }
+
+    let response = respond(&[(header::IF_NONE_MATCH, "\"other\""), (header::RANGE, "bytes=0-0")]);
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+}
+
+#[test]
+fn test_device_signature_parse() {
+    use crate::models::DeviceSignature;
+
+    let signature: DeviceSignature = "0123456789ABCDEF0123456789abcdef".parse().unwrap();
+    assert_eq!(signature.to_string(), "0123456789ABCDEF0123456789ABCDEF");
+    assert_eq!(signature.to_string().parse::<DeviceSignature>().unwrap(), signature,
+        "A signature reads back as it is shown");
+    assert_eq!(serde_json::from_str::<DeviceSignature>("\"0123456789abcdef0123456789ABCDEF\"").unwrap(), signature,
+        "A header or cookie reads the same as the login body");
+
+    assert_eq!("60A344".parse::<DeviceSignature>().unwrap().to_string(), "0000000000000000000000000060A344");
+    assert_eq!(<[u8; 16]>::from("1".parse::<DeviceSignature>().unwrap()), 1u128.to_ne_bytes());
+
+    assert!("".parse::<DeviceSignature>().is_err(), "An empty signature is refused");
+    assert!("0123456789ABCDEG".parse::<DeviceSignature>().is_err(), "Only hexadecimal digits are taken");
+    assert!(" 60A344".parse::<DeviceSignature>().is_err());
+}
+
+#[test]
+fn test_clip_buffer() {
+    use std::time::{Duration, Instant};
+    use crate::clip_buffer::ClipBuffer;
+    use crate::server_config::ClipPolicy;
+
+    let policy = ClipPolicy { seconds_before: 2, seconds_after: 1, fps: 4 };
+    let start = Instant::now();
+    let at = |ms: u64| start + Duration::from_millis(ms);
+
+    let mut clip = ClipBuffer::new(policy.clone());
+
+    // frames come every 100ms, of which one each 300ms is at least 250ms after the last kept
+    for ms in (0..=3000).step_by(100) {
+        clip.push(at(ms), || ms);
+    }
+
+    assert!(clip.finished(at(3000)).is_none(), "There is no clip without a violation");
+
+    clip.record(at(3000), uuid::Uuid::nil());
+    let second = uuid::Uuid::from_u128(2);
+    clip.record(at(3500), second);
+
+    for ms in (3100..=4000).step_by(100) {
+        clip.push(at(ms), || ms);
+    }
+
+    assert!(clip.finished(at(3900)).is_none(), "A clip waits for the seconds after its first violation");
+
+    let (violations, frames, fps) = clip.finished(at(4000)).unwrap();
+    assert_eq!(violations, vec![uuid::Uuid::nil(), second], "A violation during a clip joins it");
+    assert_eq!(frames, vec![1200, 1500, 1800, 2100, 2400, 2700, 3000, 3300, 3600, 3900],
+        "The clip starts seconds_before the violation and keeps every frame after it");
+    assert!((fps - 10.0 / 3.0).abs() < 1e-9, "The rate is the one frames were taken at, {fps}");
+
+    assert!(clip.finished(at(5000)).is_none(), "A clip is given once");
+
+    // a frame late from a lagging inference is kept as it comes, but the rate never goes under 1
+    let mut slow = ClipBuffer::new(ClipPolicy { seconds_before: 10, ..policy.clone() });
+    for ms in [0, 5000, 10000] {
+        slow.push(at(ms), || ms);
+    }
+    slow.record(at(10000), uuid::Uuid::nil());
+    let (_, frames, fps) = slow.finished(at(11000)).unwrap();
+    assert_eq!((frames.len(), fps), (3, 1.0));
+
+    // a single frame has no rate of its own and takes the policy's
+    let mut single = ClipBuffer::new(policy.clone());
+    single.push(at(0), || 0);
+    single.record(at(0), uuid::Uuid::nil());
+    assert_eq!(single.finished(at(1000)).unwrap().2, 4.0);
+
+    let mut disabled = ClipBuffer::new(ClipPolicy { fps: 0, ..policy });
+    disabled.push(at(0), || 0);
+    disabled.record(at(0), uuid::Uuid::nil());
+    assert!(disabled.finished(at(5000)).is_none(), "CLIP_FPS=0 records no clips");
+}
+
+#[test]
+fn test_violation_report() {
+    use chrono::NaiveDate;
+    use diesel::pg::Pg;
+    use crate::models::{Category, ViolationKind, ViolationReportRow};
+    use crate::reports::{encode_rows, ReportFilter};
+
+    let sql = |filter: ReportFilter| diesel::debug_query::<Pg, _>(&filter.query()).to_string();
+
+    let everything = sql(ReportFilter::default());
+    assert!(!everything.contains("WHERE"), "No filter reports every violation: {everything}");
+    assert!(everything.contains(r#"ORDER BY "violations"."date_time", "violations"."id""#), "{everything}");
+
+    let filtered = sql(ReportFilter {
+        area_code: Some(String::from("JH-C1")),
+        identified: Some(false),
+        from: NaiveDate::from_ymd_opt(2023, 5, 1),
+        to: NaiveDate::from_ymd_opt(2023, 5, 31),
+    });
+    assert!(filtered.contains(r#""violations"."area_code" = $1"#), "{filtered}");
+    assert!(filtered.contains(r#""violations"."identified" = $2"#), "{filtered}");
+    assert!(filtered.contains(r#""violations"."date_time" >= $3"#), "{filtered}");
+    assert!(filtered.contains(r#""violations"."date_time" < $4"#), "{filtered}");
+    assert!(filtered.contains(r#"["JH-C1", false, 2023-05-01T00:00:00, 2023-06-01T00:00:00]"#),
+        "The end date is taken in whole: {filtered}");
+
+    let date_time = NaiveDate::from_ymd_opt(2023, 5, 2).unwrap().and_hms_opt(8, 30, 5).unwrap();
+    let rows = [
+        ViolationReportRow {
+            id: uuid::Uuid::from_u128(1),
+            area_code: String::from("JH-C1"),
+            violation_kind: ViolationKind::FacemaskProtocol,
+            date_time,
+            identified: true,
+            first_name: Some(String::from("Dela Cruz, Juan")),
+            last_name: Some(String::from("\"JR\"")),
+            category: Some(Category::Student),
+        },
+        ViolationReportRow {
+            id: uuid::Uuid::from_u128(2),
+            area_code: String::from("JH-C1"),
+            violation_kind: ViolationKind::FootTraffic,
+            date_time,
+            identified: false,
+            first_name: None,
+            last_name: None,
+            category: None,
+        },
+    ];
+
+    let csv = String::from_utf8(encode_rows(&rows, true).unwrap()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "Violation ID,Date Time (UTC),Area Code,Violation Kind,Identified,First Name,Last Name,Category");
+    assert_eq!(lines[1], "00000000-0000-0000-0000-000000000001,2023-05-02 08:30:05,JH-C1,Facemask Protocol,Yes,\"Dela Cruz, Juan\",\"\"\"JR\"\"\",Student");
+    assert_eq!(lines[2], "00000000-0000-0000-0000-000000000002,2023-05-02 08:30:05,JH-C1,Foot Traffic,No,,,");
+
+    let next_page = String::from_utf8(encode_rows(&rows[1..], false).unwrap()).unwrap();
+    assert_eq!(next_page.lines().collect::<Vec<_>>(), vec![lines[2]], "Later pages carry no header");
+}
+
+#[test]
+fn test_session_listing() {
+    use chrono::NaiveDate;
+    use crate::models::{DeviceOs, SessionSelect};
+
+    let time = NaiveDate::from_ymd_opt(2023, 5, 2).unwrap().and_hms_opt(8, 30, 0).unwrap();
+    let session = SessionSelect {
+        id: uuid::Uuid::from_u128(1),
+        device_os: serde_json::from_str("\"android\"").unwrap(),
+        device_name: String::from("Pixel 7"),
+        created_time: time,
+        last_login: time,
+    };
+
+    assert_eq!(session.device_os, DeviceOs::Android);
+    assert_eq!(
+        serde_json::to_value(&session).unwrap(),
+        serde_json::json!({
+            "id": "00000000-0000-0000-0000-000000000001",
+            "device-os": "Android",
+            "device-name": "Pixel 7",
+            "created-time": "2023-05-02T08:30:00",
+            "last-login": "2023-05-02T08:30:00",
+        }),
+        "Sessions are listed with kebab-case keys"
+    );
+}
+
+#[test]
+fn test_refresh_token_hash() {
+    use crate::models::RefreshToken;
+
+    let token = RefreshToken::generate().unwrap();
+    assert_eq!(token.as_str().len(), 43, "32 random bytes in unpadded base64");
+    assert!(token.as_str().bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_'),
+        "The token is safe in a cookie: {}", token.as_str());
+    assert_ne!(token.as_str(), RefreshToken::generate().unwrap().as_str());
+
+    assert_eq!(token.hash(), RefreshToken::hash_of(token.as_str()), "A presented token finds its stored hash");
+    assert_eq!(token.hash().len(), 32);
+    assert_ne!(token.hash(), token.as_str().as_bytes(), "Only the hash is stored");
+
+    assert_eq!(RefreshToken::hash_of("abc")[..4], [0xba, 0x78, 0x16, 0xbf], "The hash is SHA-256");
+}
+
+#[test]
+fn test_csrf_check() {
+    use actix_web::cookie::Cookie;
+    use actix_web::http::Method;
+    use actix_web::test::TestRequest;
+    use crate::models::{UserClaims, CSRF_COOKIE};
+
+    let request = |method: Method, cookie: Option<&str>, header: Option<&str>| {
+        let mut request = TestRequest::default().method(method);
+
+        if let Some(cookie) = cookie {
+            request = request.cookie(Cookie::new(CSRF_COOKIE, cookie.to_owned()));
+        }
+
+        if let Some(header) = header {
+            request = request.insert_header(("X-CSRF-Token", header));
+        }
+
+        UserClaims::verify_csrf(&request.to_http_request()).is_ok()
+    };
+
+    assert!(request(Method::POST, Some("token"), Some("token")));
+    assert!(!request(Method::POST, Some("token"), Some("other")), "The header must echo the cookie");
+    assert!(!request(Method::POST, Some("token"), Some("token2")), "A longer value does not match");
+    assert!(!request(Method::PUT, Some("token"), None), "The cookie alone is what other sites can send");
+    assert!(!request(Method::DELETE, None, Some("token")));
+    assert!(!request(Method::PATCH, None, None));
+    assert!(!request(Method::POST, Some(""), Some("")), "An empty token is no token");
+
+    // reading changes nothing, so it needs no token
+    for method in [Method::GET, Method::HEAD, Method::OPTIONS] {
+        assert!(request(method, None, None));
+    }
+}
+
+#[test]
+fn test_retention_steps() {
+    use std::io::Cursor;
+    use std::path::PathBuf;
+    use chrono::NaiveDate;
+    use image::{ImageOutputFormat, Rgb, RgbImage};
+    use crate::retention::{cutoff, read_archived, recompress};
+    use crate::server_config::RetentionPolicy;
+
+    let now = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap().and_hms_opt(2, 0, 0).unwrap();
+    assert_eq!(cutoff(now, 30), NaiveDate::from_ymd_opt(2023, 1, 30).unwrap().and_hms_opt(2, 0, 0).unwrap(),
+        "A cutoff is whole days back from the run, at the same time of day");
+    assert_eq!(cutoff(now, 0), now);
+
+    let policy = RetentionPolicy {
+        compact_after_days: Some(30),
+        archive_after_days: None,
+        purge_after_days: None,
+        archive_dir: PathBuf::from("/nonexistent"),
+        interval_hours: 24,
+        jpeg_quality: 60,
+        max_dimension: 64,
+    };
+
+    let noisy = RgbImage::from_fn(256, 128, |x, y| Rgb([(x * 7 + y * 13) as u8, (x ^ y) as u8, (x * y) as u8]));
+    let mut original = Cursor::new(Vec::new());
+    noisy.write_to(&mut original, ImageOutputFormat::Jpeg(100)).unwrap();
+    let original = original.into_inner();
+
+    let smaller = recompress(&original, &policy).unwrap().unwrap();
+    assert!(smaller.len() < original.len());
+    let smaller = image::load_from_memory(&smaller).unwrap();
+    assert_eq!((smaller.width(), smaller.height()), (64, 32), "The longer side is brought down to max_dimension");
+
+    let tiny = RgbImage::from_pixel(4, 4, Rgb([0, 0, 0]));
+    let mut already_small = Cursor::new(Vec::new());
+    tiny.write_to(&mut already_small, ImageOutputFormat::Jpeg(10)).unwrap();
+    let finer = RetentionPolicy { jpeg_quality: 100, ..policy.clone() };
+    assert_eq!(recompress(&already_small.into_inner(), &finer), Some(None), "An image that would grow is kept as it is");
+
+    assert_eq!(recompress(b"not an image", &policy), None);
+
+    for relative in ["../etc/passwd", "/etc/passwd", "2023/05/../../x.jpg"] {
+        let error = read_archived(&policy.archive_dir, relative).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput, "{relative} leaves the archive");
+    }
+}
+
+#[test]
+fn test_totp_policy_roles() {
+    use crate::models::{TotpPolicy, UserRole};
+
+    // what GET lists is what PUT takes back, for every role
+    for role in [UserRole::SecurityGuard, UserRole::SecurityHead, UserRole::SystemAdmin] {
+        let listed = serde_json::to_string(&TotpPolicy { assigned_role: role, required: true }).unwrap();
+        let sent: TotpPolicy = serde_json::from_str(&listed).unwrap();
+
+        assert_eq!(sent.assigned_role, role, "{listed} changes another role");
+        assert!(sent.required);
+    }
+
+    assert_eq!(serde_json::to_value(TotpPolicy { assigned_role: UserRole::SecurityHead, required: false }).unwrap(),
+        serde_json::json!({ "assigned-role": 2, "required": false }));
+
+    for role in [0, 4] {
+        let body = format!(r#"{{ "assigned-role": {role}, "required": true }}"#);
+        assert!(serde_json::from_str::<TotpPolicy>(&body).is_err(), "{role} is not a role");
+    }
+}
+
+#[test]
+fn test_user_admin_roles() {
+    use actix_web::web::Query;
+    use crate::models::UserRole;
+    use crate::routes::user_admin::{ChangeRoleRequest, ListQuery};
+
+    let documented = [(1, UserRole::SecurityGuard), (2, UserRole::SecurityHead), (3, UserRole::SystemAdmin)];
+
+    for (number, role) in documented {
+        let query = Query::<ListQuery>::from_query(&format!("assigned-role={number}&page=1")).unwrap();
+        assert_eq!(query.assigned_role, Some(role), "?assigned-role={number} lists another role");
+
+        let body: ChangeRoleRequest = serde_json::from_str(&format!(r#"{{ "assigned-role": {number} }}"#)).unwrap();
+        assert_eq!(body.assigned_role, role, "assigned-role {number} assigns another role");
+        assert_eq!(serde_json::to_value(role).unwrap(), number, "The list shows {role:?} as {number}");
+    }
+
+    let query = Query::<ListQuery>::from_query("q=juan").unwrap();
+    assert_eq!(query.assigned_role, None, "The role filter is optional");
+
+    assert!(Query::<ListQuery>::from_query("assigned-role=0").is_err());
+    assert!(serde_json::from_str::<ChangeRoleRequest>(r#"{ "assigned-role": 4 }"#).is_err());
+
+    // what the role check is then made against
+    assert!(!UserRole::SecurityHead.can_manage(UserRole::SystemAdmin));
+    assert!(UserRole::SecurityHead.can_manage(UserRole::SecurityGuard));
+    assert!(UserRole::SystemAdmin.can_manage(UserRole::SecurityHead));
+}